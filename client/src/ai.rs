@@ -0,0 +1,676 @@
+//! The CPU opponent, shared by the browser game, the terminal client and the tournament
+//! runner.
+
+use std::fmt;
+use std::str::FromStr;
+
+use rand::prelude::SliceRandom;
+use rand::{Rng, SeedableRng};
+use rand_chacha::ChaChaRng;
+
+use crate::engine::{Board, Side, EMPTY};
+use crate::notation::{GameNotation, NotationError, Ply, Position};
+use crate::{CpuLevel, FirstMove, GameType, UnknownValue};
+
+/// What a won position scores at least, beyond anything `Evaluator::Windows` gives one
+/// still in play.
+const WIN_SCORE: i32 = 1_000_000;
+
+/// The generator games against the CPU are played with.
+pub type SeededRng = ChaChaRng;
+
+/// A random number generator that makes the same choices every time for the same seed. It is
+/// a fixed algorithm rather than `StdRng`, whose choices may change between `rand` releases,
+/// so saved seeds keep replaying.
+pub fn seeded_rng(seed: u64) -> SeededRng {
+    SeededRng::seed_from_u64(seed)
+}
+
+/// The side the user plays against the CPU. In Toot & Otto their word is the first thing
+/// drawn from a game's seed; in Connect 4 they are always red and nothing is drawn.
+pub fn draw_user_side<R: Rng>(game_type: &GameType, rng: &mut R) -> Side {
+    match game_type {
+        GameType::Connect4 => Side::One,
+        GameType::TootAndOtto => {
+            if rng.gen_bool(0.5) {
+                Side::One
+            } else {
+                Side::Two
+            }
+        }
+    }
+}
+
+/// How the search scores a position it stops at before anyone has won.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Evaluator {
+    /// Every unfinished position is even, unless the side to move can win straight away.
+    /// This is what the game view's CPU uses.
+    Outcome,
+    /// Counts the fours each side could still complete, weighing each by the square of
+    /// the pieces already in it.
+    Windows,
+}
+
+impl Evaluator {
+    /// The score of `board` from `side`'s point of view.
+    fn score(self, board: &Board, game_type: &GameType, side: Side) -> i32 {
+        match self {
+            Evaluator::Outcome => 0,
+            Evaluator::Windows => {
+                let lines: Vec<Vec<char>> = board
+                    .lines()
+                    .iter()
+                    .map(|line| line.chars().collect())
+                    .collect();
+
+                let potential = |side: Side| -> i32 {
+                    let sequence: Vec<char> = side.sequence(game_type).chars().collect();
+
+                    lines
+                        .iter()
+                        .flat_map(|line| line.windows(sequence.len()))
+                        .filter(|window| {
+                            window
+                                .iter()
+                                .zip(&sequence)
+                                .all(|(&cell, &wanted)| cell == EMPTY || cell == wanted)
+                        })
+                        .map(|window| {
+                            let placed =
+                                window.iter().filter(|&&cell| cell != EMPTY).count() as i32;
+                            placed * placed
+                        })
+                        .sum()
+                };
+
+                potential(side) - potential(side.other())
+            }
+        }
+    }
+}
+
+/// A way of picking moves, written like `random`, `minmax:4` or `windows:6`. The CPU
+/// levels `easy`, `medium` and `hard` are `minmax` at their depths.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Engine {
+    /// Any legal move and piece.
+    Random,
+    /// Negamax with alpha-beta pruning, looking `depth` plies ahead.
+    Search { depth: i32, evaluator: Evaluator },
+}
+
+impl Engine {
+    /// Picks a move for `side` as `(column, piece)`. The board is left as it was.
+    pub fn choose_move<R: Rng>(
+        &self,
+        board: &mut Board,
+        game_type: &GameType,
+        side: Side,
+        rng: &mut R,
+    ) -> (usize, char) {
+        match *self {
+            Engine::Random => {
+                let col = *board.valid_moves().choose(rng).expect("no moves left");
+                let piece = *side.pieces(game_type).choose(rng).expect("no pieces");
+
+                (col, piece)
+            }
+            Engine::Search { depth, evaluator } => {
+                let mut search = Search {
+                    game_type,
+                    evaluator,
+                    rng,
+                };
+                let (_, col, piece) = search.negamax(board, side, depth, -i32::MAX, i32::MAX);
+
+                (col, piece)
+            }
+        }
+    }
+}
+
+impl From<CpuLevel> for Engine {
+    fn from(level: CpuLevel) -> Self {
+        Engine::Search {
+            depth: level.search_depth(),
+            evaluator: Evaluator::Outcome,
+        }
+    }
+}
+
+impl fmt::Display for Engine {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Engine::Random => f.write_str("random"),
+            Engine::Search {
+                depth,
+                evaluator: Evaluator::Outcome,
+            } => write!(f, "minmax:{}", depth),
+            Engine::Search {
+                depth,
+                evaluator: Evaluator::Windows,
+            } => write!(f, "windows:{}", depth),
+        }
+    }
+}
+
+impl FromStr for Engine {
+    type Err = UnknownValue;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let unknown = || UnknownValue(s.to_string());
+
+        match s {
+            "random" => return Ok(Engine::Random),
+            "easy" => return Ok(CpuLevel::Easy.into()),
+            "medium" => return Ok(CpuLevel::Medium.into()),
+            "hard" => return Ok(CpuLevel::Hard.into()),
+            _ => {}
+        }
+
+        let (name, depth) = s.split_once(':').ok_or_else(unknown)?;
+        let depth = match depth.parse::<i32>() {
+            Ok(depth) if depth >= 1 => depth,
+            _ => return Err(unknown()),
+        };
+        let evaluator = match name {
+            "minmax" => Evaluator::Outcome,
+            "windows" => Evaluator::Windows,
+            _ => return Err(unknown()),
+        };
+
+        Ok(Engine::Search { depth, evaluator })
+    }
+}
+
+/// Picks the CPU's next move as `(column, piece)`.
+pub fn cpu_move<R: Rng>(
+    board: &mut Board,
+    game_type: &GameType,
+    cpu: Side,
+    level: CpuLevel,
+    rng: &mut R,
+) -> (usize, char) {
+    Engine::from(level).choose_move(board, game_type, cpu, rng)
+}
+
+/// What stays the same throughout one search.
+struct Search<'a, R> {
+    game_type: &'a GameType,
+    evaluator: Evaluator,
+    rng: &'a mut R,
+}
+
+impl<R: Rng> Search<'_, R> {
+    /// Whether `side`'s four in a row, and not the other side's, is on the board.
+    fn has_won(&self, board: &Board, side: Side) -> bool {
+        match (side, board.wins(self.game_type)) {
+            (Side::One, (one, two)) => one && !two,
+            (Side::Two, (one, two)) => two && !one,
+        }
+    }
+
+    /// Whether `side` can win with its next piece.
+    fn can_win_now(&self, board: &mut Board, side: Side) -> bool {
+        for col in board.valid_moves() {
+            for &piece in side.pieces(self.game_type) {
+                board.perform_move(col, piece);
+                let won = self.has_won(board, side);
+                board.undo_move(col);
+
+                if won {
+                    return true;
+                }
+            }
+        }
+
+        false
+    }
+
+    /// Searches `depth` plies ahead with `side` to move, returning the score from `side`'s
+    /// point of view along with the best move and piece. Wins score more the sooner they
+    /// come, and losses less the later they come. The score is exact when it lies strictly
+    /// between `alpha` and `beta`; otherwise it is only a bound.
+    fn negamax(
+        &mut self,
+        board: &mut Board,
+        side: Side,
+        depth: i32,
+        mut alpha: i32,
+        beta: i32,
+    ) -> (i32, usize, char) {
+        // In Toot & Otto a piece can finish either word, so the last move may have won the
+        // game for the side now to move
+        if self.has_won(board, side) {
+            return (WIN_SCORE + depth, 0, EMPTY);
+        } else if self.has_won(board, side.other()) {
+            return (-(WIN_SCORE + depth), 0, EMPTY);
+        } else if board.is_full() || board.wins(self.game_type) == (true, true) {
+            return (0, 0, EMPTY);
+        } else if depth == 0 {
+            // Looking no further, but a win on the very next move is certain. It scores as
+            // searching one ply past the limit would score it.
+            let score = if self.can_win_now(board, side) {
+                WIN_SCORE - 1
+            } else {
+                self.evaluator.score(board, self.game_type, side)
+            };
+            return (score, 0, EMPTY);
+        }
+
+        let mut best = (-i32::MAX, 0, EMPTY);
+
+        // Shuffled so equally good moves are chosen between at random
+        let mut moves = board.valid_moves();
+        moves.shuffle(self.rng);
+
+        let mut pieces = side.pieces(self.game_type).to_vec();
+        pieces.shuffle(self.rng);
+
+        for col in moves {
+            for &piece in &pieces {
+                board.perform_move(col, piece);
+                let (score, _, _) = self.negamax(board, side.other(), depth - 1, -beta, -alpha);
+                board.undo_move(col);
+
+                let score = -score;
+                if score > best.0 {
+                    best = (score, col, piece);
+                }
+
+                alpha = alpha.max(score);
+                if alpha >= beta {
+                    return best;
+                }
+            }
+        }
+
+        best
+    }
+}
+
+/// Plays a game against the CPU at `level` again from the seed it was played with, checking
+/// who moved first and that the CPU chooses every move it chose the first time. Returns the
+/// final position.
+pub fn replay(
+    game: &GameNotation,
+    level: CpuLevel,
+    first_move: FirstMove,
+    seed: u32,
+) -> Result<Position, NotationError> {
+    let mut rng = seeded_rng(seed.into());
+    let user = draw_user_side(&game.game_type, &mut rng);
+    let cpu = user.other();
+    let first = if first_move.user_first(&mut rng) {
+        user
+    } else {
+        cpu
+    };
+
+    if first != game.first {
+        return Err(NotationError(format!(
+            "{} moves first with this seed, not {}",
+            first.name(&game.game_type),
+            game.first.name(&game.game_type)
+        )));
+    }
+
+    let mut position = Position::new(game.game_type.clone(), game.rows, game.cols, game.first);
+
+    for (index, &ply) in game.plies.iter().enumerate() {
+        if position.to_move == cpu && !position.is_over() {
+            let (col, piece) = cpu_move(&mut position.board, &game.game_type, cpu, level, &mut rng);
+
+            if (Ply { col, piece }) != ply {
+                return Err(NotationError(format!(
+                    "ply {}: the CPU plays {}{} here, not {}{}",
+                    index + 1,
+                    col + 1,
+                    piece,
+                    ply.col + 1,
+                    ply.piece
+                )));
+            }
+        }
+
+        position
+            .play(ply)
+            .map_err(|err| NotationError(format!("ply {}: {}", index + 1, err)))?;
+    }
+
+    Ok(position)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Plays `seed`'s game against the CPU, the user moving at random from `user_seed`.
+    fn play(
+        game_type: GameType,
+        level: CpuLevel,
+        first_move: FirstMove,
+        seed: u32,
+        user_seed: u64,
+    ) -> GameNotation {
+        let mut rng = seeded_rng(seed.into());
+        let mut user_rng = seeded_rng(user_seed);
+        let user = draw_user_side(&game_type, &mut rng);
+        let first = if first_move.user_first(&mut rng) {
+            user
+        } else {
+            user.other()
+        };
+        let mut position = Position::new(game_type.clone(), 5, 5, first);
+        let mut plies = vec![];
+
+        while !position.is_over() {
+            let (col, piece) = if position.to_move == user {
+                Engine::Random.choose_move(&mut position.board, &game_type, user, &mut user_rng)
+            } else {
+                cpu_move(
+                    &mut position.board,
+                    &game_type,
+                    user.other(),
+                    level,
+                    &mut rng,
+                )
+            };
+
+            position.play(Ply { col, piece }).unwrap();
+            plies.push(Ply { col, piece });
+        }
+
+        GameNotation {
+            game_type,
+            rows: 5,
+            cols: 5,
+            first,
+            plies,
+        }
+    }
+
+    fn from_rows(rows: &[&str]) -> Board {
+        Board::from_cells(rows.iter().map(|row| row.chars().collect()).collect())
+    }
+
+    /// Plain minimax to the end of the game, scored like `Search::negamax`.
+    fn minimax(board: &mut Board, game_type: &GameType, side: Side, depth: i32) -> i32 {
+        let (mine, theirs) = match (side, board.wins(game_type)) {
+            (Side::One, (one, two)) => (one, two),
+            (Side::Two, (one, two)) => (two, one),
+        };
+
+        if mine && !theirs {
+            return WIN_SCORE + depth;
+        } else if theirs && !mine {
+            return -(WIN_SCORE + depth);
+        } else if mine || board.is_full() {
+            return 0;
+        }
+
+        let mut best = -i32::MAX;
+        for col in board.valid_moves() {
+            for &piece in side.pieces(game_type) {
+                board.perform_move(col, piece);
+                best = best.max(-minimax(board, game_type, side.other(), depth - 1));
+                board.undo_move(col);
+            }
+        }
+
+        best
+    }
+
+    /// Positions a few plies from the end, reached by playing at random from `seed`.
+    fn endgames(
+        game_type: &GameType,
+        rows: usize,
+        cols: usize,
+        empty: usize,
+    ) -> Vec<(Board, Side)> {
+        (0..40)
+            .filter_map(|seed| {
+                let mut rng = seeded_rng(seed);
+                let mut position = Position::new(game_type.clone(), rows, cols, Side::One);
+
+                while rows * cols - position.board.piece_count() > empty {
+                    if position.is_over() {
+                        return None;
+                    }
+
+                    let side = position.to_move;
+                    let (col, piece) =
+                        Engine::Random.choose_move(&mut position.board, game_type, side, &mut rng);
+                    position.play(Ply { col, piece }).unwrap();
+                }
+
+                (!position.is_over()).then_some((position.board, position.to_move))
+            })
+            .collect()
+    }
+
+    #[test]
+    fn negamax_agrees_with_exhaustive_minimax() {
+        let cases = [
+            (GameType::Connect4, 4, 4, 7),
+            (GameType::Connect4, 5, 4, 7),
+            (GameType::TootAndOtto, 4, 4, 5),
+            (GameType::TootAndOtto, 4, 5, 5),
+        ];
+
+        for (game_type, rows, cols, empty) in cases {
+            let positions = endgames(&game_type, rows, cols, empty);
+            assert!(positions.len() >= 10, "too few {:?} endgames", game_type);
+            let mut wins_next_move = 0;
+
+            for (seed, (mut board, side)) in positions.into_iter().enumerate() {
+                let depth = empty as i32;
+                let expected = minimax(&mut board, &game_type, side, depth);
+
+                let mut rng = seeded_rng(seed as u64);
+                let mut search = Search {
+                    game_type: &game_type,
+                    evaluator: Evaluator::Outcome,
+                    rng: &mut rng,
+                };
+
+                // At the depth limit, a win on the next move scores what searching on gives it
+                let beyond_limit = minimax(&mut board, &game_type, side, 0);
+                if beyond_limit == WIN_SCORE - 1 {
+                    wins_next_move += 1;
+                    assert_eq!(
+                        search.negamax(&mut board, side, 0, -i32::MAX, i32::MAX).0,
+                        beyond_limit,
+                        "{:?} {:?}",
+                        game_type,
+                        board
+                    );
+                }
+                let (score, col, piece) =
+                    search.negamax(&mut board, side, depth, -i32::MAX, i32::MAX);
+                assert_eq!(score, expected, "{:?} {:?}", game_type, board);
+
+                // The move it picked is worth what it said
+                board.perform_move(col, piece).unwrap();
+                assert_eq!(
+                    -minimax(&mut board, &game_type, side.other(), depth - 1),
+                    expected
+                );
+            }
+
+            assert!(
+                wins_next_move > 0,
+                "no {:?} wins on the next move",
+                game_type
+            );
+        }
+    }
+
+    #[test]
+    fn a_win_on_the_next_move_counts_at_the_depth_limit() {
+        let mut board = from_rows(&["____", "____", "B___", "RRR_"]);
+        let mut rng = seeded_rng(0);
+        let mut search = Search {
+            game_type: &GameType::Connect4,
+            evaluator: Evaluator::Outcome,
+            rng: &mut rng,
+        };
+
+        assert_eq!(
+            search
+                .negamax(&mut board, Side::One, 0, -i32::MAX, i32::MAX)
+                .0,
+            WIN_SCORE - 1
+        );
+        assert_eq!(
+            search
+                .negamax(&mut board, Side::Two, 0, -i32::MAX, i32::MAX)
+                .0,
+            0
+        );
+    }
+
+    #[test]
+    fn either_side_takes_wins_and_blocks_them() {
+        let engine = Engine::Search {
+            depth: 2,
+            evaluator: Evaluator::Outcome,
+        };
+
+        for seed in 0..10 {
+            let mut rng = seeded_rng(seed);
+
+            // Blue wins rather than blocking red
+            let mut board = from_rows(&["_____", "R____", "RR___", "RBBB_"]);
+            let choice = engine.choose_move(&mut board, &GameType::Connect4, Side::Two, &mut rng);
+            assert_eq!(choice, (4, 'B'));
+
+            // and blocks red when it can't win
+            let mut board = from_rows(&["_____", "R____", "RR___", "RBB__"]);
+            let choice = engine.choose_move(&mut board, &GameType::Connect4, Side::Two, &mut rng);
+            assert_eq!(choice, (0, 'B'));
+
+            // O would spell OTTO, so TOOT has to block with T
+            let mut board = from_rows(&["____", "____", "____", "OTT_"]);
+            let choice =
+                engine.choose_move(&mut board, &GameType::TootAndOtto, Side::One, &mut rng);
+            assert_eq!(choice, (3, 'T'));
+
+            // and OTTO takes the win when it's their move
+            let mut board = from_rows(&["____", "____", "____", "OTT_"]);
+            let choice =
+                engine.choose_move(&mut board, &GameType::TootAndOtto, Side::Two, &mut rng);
+            assert_eq!(choice, (3, 'O'));
+        }
+    }
+
+    #[test]
+    fn the_same_seed_makes_the_same_choices() {
+        for game_type in [GameType::Connect4, GameType::TootAndOtto] {
+            for seed in 0..5 {
+                assert_eq!(
+                    play(
+                        game_type.clone(),
+                        CpuLevel::Easy,
+                        FirstMove::Random,
+                        seed,
+                        1
+                    ),
+                    play(
+                        game_type.clone(),
+                        CpuLevel::Easy,
+                        FirstMove::Random,
+                        seed,
+                        1
+                    )
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn a_seed_makes_the_same_choices_across_releases() {
+        // Changes here mean games saved with a seed no longer replay
+        let mut rng = seeded_rng(42);
+        let draws: Vec<u32> = (0..3).map(|_| rng.gen()).collect();
+        assert_eq!(draws, [3618982008, 2207824913, 2736219033]);
+
+        let mut rng = seeded_rng(42);
+        let mut board = Board::new(6, 7);
+        let mut side = Side::One;
+        let mut moves = vec![];
+        for _ in 0..4 {
+            let (col, piece) = cpu_move(
+                &mut board,
+                &GameType::Connect4,
+                side,
+                CpuLevel::Easy,
+                &mut rng,
+            );
+            board.perform_move(col, piece).unwrap();
+            moves.push((col, piece));
+            side = side.other();
+        }
+        assert_eq!(moves, [(6, 'R'), (2, 'B'), (6, 'R'), (5, 'B')]);
+    }
+
+    #[test]
+    fn replays_games_from_their_seed() {
+        for game_type in [GameType::Connect4, GameType::TootAndOtto] {
+            for seed in 0..5 {
+                for first_move in FirstMove::ALL {
+                    let game = play(
+                        game_type.clone(),
+                        CpuLevel::Easy,
+                        first_move,
+                        seed,
+                        seed.into(),
+                    );
+                    let position = replay(&game, CpuLevel::Easy, first_move, seed).unwrap();
+                    assert_eq!(position, game.replay().unwrap());
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn random_first_moves_come_from_the_seed() {
+        let games: Vec<GameNotation> = (0..20)
+            .map(|seed| {
+                play(
+                    GameType::Connect4,
+                    CpuLevel::Easy,
+                    FirstMove::Random,
+                    seed,
+                    1,
+                )
+            })
+            .collect();
+
+        // Red is the user in Connect 4, and each of them opens some of the games
+        assert!(games.iter().any(|game| game.first == Side::One));
+        assert!(games.iter().any(|game| game.first == Side::Two));
+
+        for (seed, game) in (0..).zip(&games) {
+            assert!(replay(game, CpuLevel::Easy, FirstMove::Random, seed).is_ok());
+
+            // Saying the other side was picked to open doesn't fit the moves
+            let other = match game.first {
+                Side::One => FirstMove::Cpu,
+                Side::Two => FirstMove::User,
+            };
+            assert!(replay(game, CpuLevel::Easy, other, seed).is_err());
+        }
+    }
+
+    #[test]
+    fn replay_spots_a_move_the_cpu_wouldnt_make() {
+        let mut game = play(GameType::Connect4, CpuLevel::Easy, FirstMove::User, 3, 3);
+        let cpu_ply = &mut game.plies[1];
+        cpu_ply.col = (cpu_ply.col + 1) % 5;
+
+        let err = replay(&game, CpuLevel::Easy, FirstMove::User, 3).unwrap_err();
+        assert!(err.0.starts_with("ply 2: the CPU plays"), "{}", err);
+    }
+}
@@ -0,0 +1,251 @@
+//! Typed access to the server's REST API.
+//!
+//! The base URL defaults to the `API_BASE_URL` environment variable at build time
+//! (e.g. `API_BASE_URL=https://api.example.com trunk build`), and can be replaced at
+//! runtime by an `api_base_url` entry in a `/config.json` served at the root of the site.
+//! An empty base URL sends requests to the origin the client was served from.
+
+use std::fmt;
+use std::sync::OnceLock;
+
+use client::{
+    AccountDeletion, AuditEntry, ConnectGame, GameRecord, GameType, LeaderboardPage,
+    LeaderboardQuery, PasswordChange, Profile, Session, User, UserSummary, UsernameChange,
+};
+use gloo_net::http::{Request, Response};
+use serde::{de::DeserializeOwned, Deserialize};
+
+const DEFAULT_BASE_URL: &str = match option_env!("API_BASE_URL") {
+    Some(url) => url,
+    None => "http://127.0.0.1:8000",
+};
+
+static BASE_URL: OnceLock<String> = OnceLock::new();
+
+#[derive(Deserialize)]
+struct Config {
+    api_base_url: Option<String>,
+}
+
+/// Reads `config.json`, if there is one. Must run before the first request.
+pub async fn load_config() {
+    // From the root, since routes like `/profile/alice` would otherwise change the path
+    let config = match Request::get("/config.json").send().await {
+        Ok(response) if response.ok() => match response.json::<Config>().await {
+            Ok(config) => Some(config),
+            Err(err) => {
+                log::warn!("Ignoring an invalid config.json: {}", err);
+                None
+            }
+        },
+        Ok(response) => {
+            log::info!("No config.json ({}), using the built-in API URL", response.status());
+            None
+        }
+        Err(err) => {
+            log::warn!("Couldn't fetch config.json, using the built-in API URL: {}", err);
+            None
+        }
+    };
+
+    if let Some(url) = config.and_then(|config| config.api_base_url) {
+        let _ = BASE_URL.set(url.trim_end_matches('/').to_string());
+    }
+}
+
+fn url(path: &str) -> String {
+    let base_url = BASE_URL
+        .get()
+        .map(String::as_str)
+        .unwrap_or(DEFAULT_BASE_URL);
+    format!("{}{}", base_url, path)
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum ApiError {
+    /// The request could not be built or sent.
+    Request(String),
+    /// The server answered with a non-2xx status code.
+    Status(u16),
+    /// The response body was not what we expected.
+    Decode(String),
+}
+
+impl fmt::Display for ApiError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ApiError::Request(err) => write!(f, "Request failed: {}", err),
+            ApiError::Status(500) => write!(f, "Database not available"),
+            ApiError::Status(status) => write!(f, "Server responded with {}", status),
+            ApiError::Decode(err) => write!(f, "Unexpected response: {}", err),
+        }
+    }
+}
+
+pub type ApiResult<T> = Result<T, ApiError>;
+
+/// A client for the API, optionally acting on behalf of a logged in user.
+#[derive(Clone, Default, PartialEq)]
+pub struct Api {
+    token: Option<String>,
+}
+
+impl Api {
+    pub fn new() -> Self {
+        Api { token: None }
+    }
+
+    pub fn authenticated(session: &Session) -> Self {
+        Api {
+            token: Some(session.token.clone()),
+        }
+    }
+
+    async fn send(&self, request: Request) -> ApiResult<Response> {
+        let request = match &self.token {
+            Some(token) => request.header("Authorization", &format!("Bearer {}", token)),
+            None => request,
+        };
+
+        let response = request
+            .send()
+            .await
+            .map_err(|err| ApiError::Request(err.to_string()))?;
+
+        if !response.ok() {
+            return Err(ApiError::Status(response.status()));
+        }
+
+        Ok(response)
+    }
+
+    async fn send_json<B: serde::Serialize>(
+        &self,
+        request: Request,
+        body: &B,
+    ) -> ApiResult<Response> {
+        let request = request
+            .json(body)
+            .map_err(|err| ApiError::Request(err.to_string()))?;
+
+        self.send(request).await
+    }
+
+    async fn decode<T: DeserializeOwned>(response: Response) -> ApiResult<T> {
+        response
+            .json()
+            .await
+            .map_err(|err| ApiError::Decode(err.to_string()))
+    }
+
+    pub async fn login(&self, user: &User) -> ApiResult<Session> {
+        let response = self
+            .send_json(Request::post(&url("/users/login")), user)
+            .await?;
+        Self::decode(response).await
+    }
+
+    /// Registers a new account. Called on a guest's behalf, it also claims the guest's games.
+    pub async fn register(&self, user: &User) -> ApiResult<Session> {
+        let response = self
+            .send_json(Request::post(&url("/users/register")), user)
+            .await?;
+        Self::decode(response).await
+    }
+
+    /// Signs the session this was created with out on the server.
+    pub async fn logout(&self) -> ApiResult<()> {
+        self.send(Request::post(&url("/users/logout"))).await?;
+        Ok(())
+    }
+
+    pub async fn guest(&self) -> ApiResult<Session> {
+        let response = self.send(Request::post(&url("/users/guest"))).await?;
+        Self::decode(response).await
+    }
+
+    pub async fn change_password(&self, change: &PasswordChange) -> ApiResult<()> {
+        self.send_json(Request::put(&url("/users/me/password")), change)
+            .await?;
+        Ok(())
+    }
+
+    pub async fn change_username(&self, change: &UsernameChange) -> ApiResult<Session> {
+        let response = self
+            .send_json(Request::put(&url("/users/me/username")), change)
+            .await?;
+        Self::decode(response).await
+    }
+
+    pub async fn delete_account(&self, deletion: &AccountDeletion) -> ApiResult<()> {
+        self.send_json(Request::delete(&url("/users/me")), deletion)
+            .await?;
+        Ok(())
+    }
+
+    pub async fn create_game(&self, game: &ConnectGame) -> ApiResult<()> {
+        self.send_json(Request::post(&url("/games/create")), game)
+            .await?;
+        Ok(())
+    }
+
+    pub async fn leaderboard(
+        &self,
+        game_type: &GameType,
+        query: &LeaderboardQuery,
+    ) -> ApiResult<LeaderboardPage> {
+        let path = format!("/leaderboard/{}", game_type.slug());
+        let request = Request::get(&url(&path)).query(query.to_pairs());
+        let response = self.send(request).await?;
+        Self::decode(response).await
+    }
+
+    pub async fn profile(&self, username: &str, page: u32, limit: u32) -> ApiResult<Profile> {
+        let username = String::from(js_sys::encode_uri_component(username));
+        let request = Request::get(&url(&format!("/users/{}/profile", username)))
+            .query([("page", page.to_string()), ("limit", limit.to_string())]);
+        let response = self.send(request).await?;
+        Self::decode(response).await
+    }
+
+    pub async fn search_games(&self, player: &str) -> ApiResult<Vec<GameRecord>> {
+        let request = Request::get(&url("/admin/games")).query([("player", player)]);
+        let response = self.send(request).await?;
+        Self::decode(response).await
+    }
+
+    pub async fn delete_game(&self, id: &str) -> ApiResult<()> {
+        let id = String::from(js_sys::encode_uri_component(id));
+        self.send(Request::delete(&url(&format!("/admin/games/{}", id))))
+            .await?;
+        Ok(())
+    }
+
+    pub async fn search_users(&self, search: &str) -> ApiResult<Vec<UserSummary>> {
+        let request = Request::get(&url("/admin/users")).query([("search", search)]);
+        let response = self.send(request).await?;
+        Self::decode(response).await
+    }
+
+    pub async fn set_banned(&self, username: &str, banned: bool) -> ApiResult<()> {
+        let username = String::from(js_sys::encode_uri_component(username));
+        let action = if banned { "ban" } else { "unban" };
+        self.send(Request::post(&url(&format!(
+            "/admin/users/{}/{}",
+            username, action
+        ))))
+        .await?;
+        Ok(())
+    }
+
+    pub async fn recompute_leaderboards(&self) -> ApiResult<()> {
+        self.send(Request::post(&url("/admin/leaderboard/recompute")))
+            .await?;
+        Ok(())
+    }
+
+    pub async fn audit_log(&self) -> ApiResult<Vec<AuditEntry>> {
+        let response = self.send(Request::get(&url("/admin/audit"))).await?;
+        Self::decode(response).await
+    }
+}
@@ -0,0 +1,220 @@
+use client::{AccountDeletion, GameRetention, PasswordChange, Session, User, UsernameChange};
+use gloo_dialogs::{alert, confirm};
+use yew::prelude::*;
+
+use crate::api::{Api, ApiError};
+
+#[derive(Clone, PartialEq, Properties)]
+pub struct Props {
+    pub session: Session,
+    pub set_session: Callback<Option<Session>>,
+}
+
+fn input_value(node: &NodeRef) -> String {
+    node.cast::<web_sys::HtmlInputElement>()
+        .map(|input| input.value())
+        .unwrap_or_default()
+}
+
+#[function_component]
+pub fn Account(props: &Props) -> Html {
+    let old_password_ref = use_node_ref();
+    let new_password_ref = use_node_ref();
+    let username_ref = use_node_ref();
+    let purge_ref = use_node_ref();
+    let delete_password_ref = use_node_ref();
+    let register_username_ref = use_node_ref();
+    let register_password_ref = use_node_ref();
+
+    let api = Api::authenticated(&props.session);
+
+    let change_password = {
+        let old_password_ref = old_password_ref.clone();
+        let new_password_ref = new_password_ref.clone();
+        let api = api.clone();
+
+        move |_| {
+            let change = PasswordChange {
+                old_password: input_value(&old_password_ref),
+                new_password: input_value(&new_password_ref),
+            };
+
+            if change.old_password.is_empty() || change.new_password.is_empty() {
+                alert("Both passwords are required");
+                return;
+            }
+
+            let api = api.clone();
+
+            wasm_bindgen_futures::spawn_local(async move {
+                match api.change_password(&change).await {
+                    Ok(()) => alert("Password changed"),
+                    Err(ApiError::Status(401)) => alert("Current password is wrong"),
+                    Err(err) => alert(&format!("Error: {}", err)),
+                }
+            });
+        }
+    };
+
+    let change_username = {
+        let username_ref = username_ref.clone();
+        let api = api.clone();
+        let set_session = props.set_session.clone();
+
+        move |_| {
+            let change = UsernameChange {
+                username: input_value(&username_ref),
+            };
+
+            if change.username.is_empty() {
+                alert("Username is required");
+                return;
+            }
+
+            let api = api.clone();
+            let set_session = set_session.clone();
+
+            wasm_bindgen_futures::spawn_local(async move {
+                match api.change_username(&change).await {
+                    Ok(session) => set_session.emit(Some(session)),
+                    Err(ApiError::Status(409)) => alert("Username is already taken"),
+                    Err(err) => alert(&format!("Error: {}", err)),
+                }
+            });
+        }
+    };
+
+    let delete_account = {
+        let purge_ref = purge_ref.clone();
+        let delete_password_ref = delete_password_ref.clone();
+        let api = api.clone();
+        let set_session = props.set_session.clone();
+
+        move |_| {
+            let password = input_value(&delete_password_ref);
+
+            if password.is_empty() {
+                alert("Your password is required");
+                return;
+            }
+
+            if !confirm("Delete your account? This cannot be undone.") {
+                return;
+            }
+
+            let purge = purge_ref
+                .cast::<web_sys::HtmlInputElement>()
+                .map(|input| input.checked())
+                .unwrap_or(false);
+
+            let deletion = AccountDeletion {
+                password,
+                games: if purge {
+                    GameRetention::Purge
+                } else {
+                    GameRetention::Anonymize
+                },
+            };
+
+            let api = api.clone();
+            let set_session = set_session.clone();
+
+            wasm_bindgen_futures::spawn_local(async move {
+                match api.delete_account(&deletion).await {
+                    Ok(()) => set_session.emit(None),
+                    Err(ApiError::Status(401)) => alert("Password is wrong"),
+                    Err(err) => alert(&format!("Error: {}", err)),
+                }
+            });
+        }
+    };
+
+    if props.session.guest {
+        let register = {
+            let register_username_ref = register_username_ref.clone();
+            let register_password_ref = register_password_ref.clone();
+            let api = api.clone();
+            let set_session = props.set_session.clone();
+
+            move |_| {
+                let user = User {
+                    username: input_value(&register_username_ref),
+                    password: input_value(&register_password_ref),
+                };
+
+                if user.username.is_empty() || user.password.is_empty() {
+                    alert("Username and password are required");
+                    return;
+                }
+
+                let api = api.clone();
+                let set_session = set_session.clone();
+
+                // Registering with the guest's token hands their games over to the new account
+                wasm_bindgen_futures::spawn_local(async move {
+                    match api.register(&user).await {
+                        Ok(session) => set_session.emit(Some(session)),
+                        Err(ApiError::Status(409)) => alert("Username is already taken"),
+                        Err(err) => alert(&format!("Error: {}", err)),
+                    }
+                });
+            }
+        };
+
+        return html! {
+            <>
+                <h1>{"Account"}</h1>
+                <h2>{format!("Playing as {}", props.session.username)}</h2>
+                <p>{"Register to keep your games and appear on the leaderboard."}</p>
+                <div>
+                    <label for="username">{"Username"}</label>
+                    <input type="text" name="username" ref={register_username_ref}/>
+                </div>
+                <div>
+                    <label for="password">{"Password"}</label>
+                    <input type="password" name="password" ref={register_password_ref}/>
+                </div>
+                <button type="submit" onclick={register}>{"Register"}</button>
+            </>
+        };
+    }
+
+    html! {
+        <>
+            <h1>{"Account"}</h1>
+            <h2>{format!("Signed in as {}", props.session.username)}</h2>
+            <div>
+                <h2>{"Change password"}</h2>
+                <div>
+                    <label for="old_password">{"Current"}</label>
+                    <input type="password" name="old_password" ref={old_password_ref}/>
+                </div>
+                <div>
+                    <label for="new_password">{"New"}</label>
+                    <input type="password" name="new_password" ref={new_password_ref}/>
+                </div>
+                <button type="submit" onclick={change_password}>{"Change password"}</button>
+            </div>
+            <div>
+                <h2>{"Change username"}</h2>
+                <div>
+                    <label for="username">{"Username"}</label>
+                    <input type="text" name="username" ref={username_ref}/>
+                </div>
+                <button type="submit" onclick={change_username}>{"Change username"}</button>
+            </div>
+            <div>
+                <h2>{"Delete account"}</h2>
+                <div>
+                    <label for="delete_password">{"Password"}</label>
+                    <input type="password" name="delete_password" ref={delete_password_ref}/>
+                </div>
+                <div>
+                    <input type="checkbox" name="purge" ref={purge_ref}/>
+                    <label for="purge" style="width: auto;">{"Also delete my games"}</label>
+                </div>
+                <button type="submit" onclick={delete_account}>{"Delete account"}</button>
+            </div>
+        </>
+    }
+}
@@ -0,0 +1,271 @@
+use client::{AuditEntry, GameRecord, Session, UserSummary};
+use gloo_dialogs::{alert, confirm};
+use yew::prelude::*;
+
+use crate::api::Api;
+
+#[derive(Clone, PartialEq, Properties)]
+pub struct Props {
+    pub session: Session,
+}
+
+fn input_value(node: &NodeRef) -> String {
+    node.cast::<web_sys::HtmlInputElement>()
+        .map(|input| input.value())
+        .unwrap_or_default()
+}
+
+#[function_component]
+pub fn Admin(props: &Props) -> Html {
+    let player_ref = use_node_ref();
+    let user_search_ref = use_node_ref();
+
+    let games = use_state(Vec::<GameRecord>::new);
+    let users = use_state(Vec::<UserSummary>::new);
+    let audit_log = use_state(Vec::<AuditEntry>::new);
+    // Bumped after every action so the audit log is fetched again
+    let revision = use_state(|| 0);
+
+    let api = Api::authenticated(&props.session);
+
+    {
+        let audit_log = audit_log.clone();
+        let api = api.clone();
+        use_effect_with_deps(
+            move |_| {
+                wasm_bindgen_futures::spawn_local(async move {
+                    if let Ok(entries) = api.audit_log().await {
+                        audit_log.set(entries);
+                    }
+                });
+                || ()
+            },
+            *revision,
+        );
+    }
+
+    let search_games = {
+        let player_ref = player_ref.clone();
+        let games = games.clone();
+        let revision = revision.clone();
+        let api = api.clone();
+
+        Callback::from(move |_| {
+            let player = input_value(&player_ref);
+            let games = games.clone();
+            let revision = revision.clone();
+            let api = api.clone();
+
+            wasm_bindgen_futures::spawn_local(async move {
+                match api.search_games(&player).await {
+                    Ok(found) => games.set(found),
+                    Err(err) => alert(&format!("Error: {}", err)),
+                }
+
+                revision.set(*revision + 1);
+            });
+        })
+    };
+
+    let delete_game = {
+        let games = games.clone();
+        let revision = revision.clone();
+        let api = api.clone();
+
+        move |id: String| {
+            let games = games.clone();
+            let revision = revision.clone();
+            let api = api.clone();
+
+            Callback::from(move |_| {
+                if !confirm("Delete this game?") {
+                    return;
+                }
+
+                let id = id.clone();
+                let games = games.clone();
+                let revision = revision.clone();
+                let api = api.clone();
+
+                wasm_bindgen_futures::spawn_local(async move {
+                    match api.delete_game(&id).await {
+                        Ok(()) => {
+                            games.set(games.iter().filter(|game| game.id != id).cloned().collect());
+                        }
+                        Err(err) => alert(&format!("Error: {}", err)),
+                    }
+
+                    revision.set(*revision + 1);
+                });
+            })
+        }
+    };
+
+    let search_users = {
+        let user_search_ref = user_search_ref.clone();
+        let users = users.clone();
+        let revision = revision.clone();
+        let api = api.clone();
+
+        Callback::from(move |_| {
+            let search = input_value(&user_search_ref);
+            let users = users.clone();
+            let revision = revision.clone();
+            let api = api.clone();
+
+            wasm_bindgen_futures::spawn_local(async move {
+                match api.search_users(&search).await {
+                    Ok(found) => users.set(found),
+                    Err(err) => alert(&format!("Error: {}", err)),
+                }
+
+                revision.set(*revision + 1);
+            });
+        })
+    };
+
+    let set_banned = {
+        let users = users.clone();
+        let revision = revision.clone();
+        let api = api.clone();
+
+        move |username: String, banned: bool| {
+            let users = users.clone();
+            let revision = revision.clone();
+            let api = api.clone();
+
+            Callback::from(move |_| {
+                let username = username.clone();
+                let users = users.clone();
+                let revision = revision.clone();
+                let api = api.clone();
+
+                wasm_bindgen_futures::spawn_local(async move {
+                    match api.set_banned(&username, banned).await {
+                        Ok(()) => {
+                            users.set(
+                                users
+                                    .iter()
+                                    .cloned()
+                                    .map(|mut user| {
+                                        if user.username == username {
+                                            user.banned = banned;
+                                        }
+                                        user
+                                    })
+                                    .collect(),
+                            );
+                        }
+                        Err(err) => alert(&format!("Error: {}", err)),
+                    }
+
+                    revision.set(*revision + 1);
+                });
+            })
+        }
+    };
+
+    let recompute = {
+        let revision = revision.clone();
+        let api = api.clone();
+
+        Callback::from(move |_| {
+            let revision = revision.clone();
+            let api = api.clone();
+
+            wasm_bindgen_futures::spawn_local(async move {
+                match api.recompute_leaderboards().await {
+                    Ok(()) => alert("Leaderboards recomputed"),
+                    Err(err) => alert(&format!("Error: {}", err)),
+                }
+
+                revision.set(*revision + 1);
+            });
+        })
+    };
+
+    html! {
+        <>
+            <h1>{"Admin"}</h1>
+            <div>
+                <h2>{"Games"}</h2>
+                <input type="text" name="player" placeholder="Player" ref={player_ref}/>
+                <button onclick={search_games}>{"Search"}</button>
+                <table>
+                    <thead>
+                        <tr>
+                            <th>{"Game"}</th>
+                            <th>{"Player 1"}</th>
+                            <th>{"Player 2"}</th>
+                            <th>{"Winner"}</th>
+                            <th>{"Date"}</th>
+                            <th></th>
+                        </tr>
+                    </thead>
+                    <tbody>
+                        {
+                            for games.iter().map(|record| html! {
+                                <tr>
+                                    <td>{format!("{:?}", record.game.game_type)}</td>
+                                    <td>{&record.game.player1}</td>
+                                    <td>{&record.game.player2}</td>
+                                    <td>{&record.game.winner}</td>
+                                    <td>{record.game.ended_at.date_naive().to_string()}</td>
+                                    <td><button onclick={delete_game(record.id.clone())}>{"Delete"}</button></td>
+                                </tr>
+                            })
+                        }
+                    </tbody>
+                </table>
+            </div>
+            <div>
+                <h2>{"Users"}</h2>
+                <input type="text" name="search" placeholder="Username" ref={user_search_ref}/>
+                <button onclick={search_users}>{"Search"}</button>
+                <table>
+                    <tbody>
+                        {
+                            for users.iter().map(|user| html! {
+                                <tr>
+                                    <td>{&user.username}</td>
+                                    <td>{format!("{:?}", user.role)}</td>
+                                    <td>{if user.guest { "Guest" } else { "" }}</td>
+                                    <td>
+                                        {
+                                            if user.banned {
+                                                html! { <button onclick={set_banned(user.username.clone(), false)}>{"Unban"}</button> }
+                                            } else {
+                                                html! { <button onclick={set_banned(user.username.clone(), true)}>{"Ban"}</button> }
+                                            }
+                                        }
+                                    </td>
+                                </tr>
+                            })
+                        }
+                    </tbody>
+                </table>
+            </div>
+            <div>
+                <h2>{"Leaderboards"}</h2>
+                <button onclick={recompute}>{"Recompute leaderboards"}</button>
+            </div>
+            <div>
+                <h2>{"Audit log"}</h2>
+                <table>
+                    <tbody>
+                        {
+                            for audit_log.iter().map(|entry| html! {
+                                <tr>
+                                    <td>{entry.date.format("%Y-%m-%d %H:%M").to_string()}</td>
+                                    <td>{&entry.admin}</td>
+                                    <td>{&entry.action}</td>
+                                    <td>{&entry.target}</td>
+                                </tr>
+                            })
+                        }
+                    </tbody>
+                </table>
+            </div>
+        </>
+    }
+}
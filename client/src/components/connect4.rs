@@ -1,27 +1,44 @@
-use client::{ConnectGame, GameType};
+use client::ai;
+use client::engine::{Board, Side, MAX_SIZE};
+use client::notation::{GameNotation, Ply, Position};
+use client::{ConnectGame, CpuLevel, FirstMove, GameType};
 
-use chrono::{Datelike, Utc};
-use gloo_dialogs::alert;
-use gloo_net::http::Request;
+use chrono::{DateTime, Utc};
+use gloo_dialogs::{alert, prompt};
 use gloo_timers::callback::Timeout;
 
-use rand::prelude::SliceRandom;
-
 use log::info;
 use yew::prelude::*;
 
+use crate::api::Api;
+
 pub struct Game {
-    board: Vec<Vec<char>>,
+    board: Board,
+    /// Every piece placed so far, unless the game was loaded from a position.
+    plies: Vec<Ply>,
+    from_position: bool,
     player1: String,
     player2: usize,
     game_type: GameType,
     num_rows: i32,
     num_cols: i32,
     user_turn: bool,
+    first_move: FirstMove,
+    /// Whether the user made the first move, settled from `first_move` as the game starts.
+    user_first: bool,
     winners: (bool, bool),
     user_otto_toot: String,
     t_selected: bool,
     game_started: bool,
+    started_at: DateTime<Utc>,
+    /// Every random choice in the game is drawn from `rng`, seeded with this.
+    seed: u32,
+    rng: ai::SeededRng,
+    /// Whether a CPU move has been chosen and is waiting to be played.
+    cpu_pending: bool,
+    /// Whether the finished game has been sent to the server, so rendering again doesn't
+    /// save it twice.
+    saved: bool,
 }
 
 pub enum Msg {
@@ -32,7 +49,10 @@ pub enum Msg {
     ChangeSel,
     ChangeCpu { cpu: usize },
     ChangeDim { rows: i32, cols: i32 },
+    ChangeFirst { first: FirstMove },
     StartGame,
+    CopyGame,
+    LoadPosition,
 }
 
 #[derive(Properties, PartialEq)]
@@ -47,216 +67,96 @@ pub struct Props {
 }
 
 impl Game {
-    fn alpha_beta_minmax(
-        &mut self,
-        player: bool,
-        depth: i32,
-        mut alpha: i32,
-        mut beta: i32,
-    ) -> (i32, usize, char) {
-        let max_player = true; //true is user move (R in connect4)
-
-        let options = match self.game_type {
-            GameType::Connect4 => {
-                if player {
-                    "R"
-                } else {
-                    "B"
-                }
-            }
-            GameType::TootAndOtto => "OT",
-        };
-
-        // first element in winners array is user win, second is computer win
-        let winners = self.is_win();
-        if (winners.0 || winners.1) && (winners.0 != winners.1) {
-            if winners.1 {
-                return (-1, 0, '_'); // Min player won (computer)
-            } else {
-                return (1, 0, '_'); // Max player won
-            }
-        } else if self.is_draw() || depth == 0 || (winners.0 && winners.1) {
-            return (0, 0, '_');
-        }
-
-        let mut best_score = if player == max_player {
-            i32::MIN
-        } else {
-            i32::MAX
-        };
-        let mut best_move = 0;
-        let mut best_choice = '_';
-        let rng = &mut rand::thread_rng();
-
-        let mut moves = self.get_valid_moves();
-        moves.shuffle(rng);
-
-        let mut options = options.chars().collect::<Vec<char>>();
-        options.shuffle(rng);
-
-        for m in moves {
-            for c in &options {
-                self.perform_move(m, *c);
-
-                let (score, _, _) = self.alpha_beta_minmax(!player, depth - 1, alpha, beta);
-
-                if player == max_player {
-                    if score > best_score {
-                        best_score = score;
-                        best_move = m;
-                        best_choice = *c;
-                    }
-
-                    if beta <= best_score {
-                        self.undo_move(m);
-                        return (best_score, best_move, best_choice);
-                    }
-                    alpha = std::cmp::max(alpha, best_score);
-                } else {
-                    if score < best_score {
-                        best_score = score;
-                        best_move = m;
-                        best_choice = *c;
-                    }
-                    if alpha >= best_score {
-                        self.undo_move(m);
-                        return (best_score, best_move, best_choice);
-                    }
-                    beta = std::cmp::min(beta, best_score);
-                }
-                self.undo_move(m);
-            }
-        }
+    fn is_draw(&self) -> bool {
+        self.board.is_full()
+    }
 
-        (best_score, best_move, best_choice)
+    fn get_valid_moves(&self) -> Vec<usize> {
+        self.board.valid_moves()
     }
 
-    fn is_draw(&self) -> bool {
-        self.get_valid_moves().len() == 0
+    fn perform_move(&mut self, col: usize, player: char) {
+        self.board.perform_move(col, player);
     }
 
-    fn get_valid_moves(&self) -> Vec<usize> {
-        let mut moves = vec![];
-        for i in 0..self.num_cols {
-            if self.board[0][i as usize] == '_' {
-                moves.push(i as usize);
-            }
+    /// The user plays red in Connect 4, and whichever word they were given in Toot & Otto.
+    fn user_side(&self) -> Side {
+        match self.game_type {
+            GameType::TootAndOtto if self.user_otto_toot.as_str() == "OTTO" => Side::Two,
+            _ => Side::One,
         }
-        moves
     }
 
-    fn undo_move(&mut self, col: usize) {
-        for i in 0..self.num_rows {
-            if self.board[i as usize][col as usize] != '_' {
-                self.board[i as usize][col as usize] = '_';
-                break;
-            }
+    /// The game so far, from an empty board.
+    fn notation(&self) -> GameNotation {
+        GameNotation {
+            game_type: self.game_type.clone(),
+            rows: self.num_rows as usize,
+            cols: self.num_cols as usize,
+            first: if self.user_first {
+                self.user_side()
+            } else {
+                self.user_side().other()
+            },
+            plies: self.plies.clone(),
         }
     }
 
-    fn perform_move(&mut self, col: usize, player: char) {
-        for row in (0..self.num_rows).rev() {
-            if self.board[row as usize][col as usize] == '_' {
-                self.board[row as usize][col as usize] = player;
-                break;
-            }
+    /// Draws the user's word in Toot & Otto, the first choice made from the seed.
+    fn draw_word(&mut self) {
+        if self.game_type == GameType::TootAndOtto {
+            self.user_otto_toot = ai::draw_user_side(&self.game_type, &mut self.rng)
+                .name(&self.game_type)
+                .to_string();
         }
     }
 
-    fn is_win(&self) -> (bool, bool) {
-        let sequences = match self.game_type {
-            GameType::Connect4 => vec!["RRRR", "BBBB"],
-            GameType::TootAndOtto => vec!["OTTO", "TOOT"],
-        };
-        let mut res = [false, false];
+    /// Starts drawing the game's choices from `seed` afresh.
+    fn reseed(&mut self, seed: u32) {
+        self.seed = seed;
+        self.rng = ai::seeded_rng(seed.into());
+        self.draw_word();
+    }
 
-        for row in &self.board {
-            let row_str: String = row.into_iter().collect();
-            for (i, sequence) in sequences.clone().iter().enumerate() {
-                if row_str.contains(sequence) {
-                    res[i] = true;
-                }
-            }
-        }
+    fn is_win(&self) -> (bool, bool) {
+        let (one, two) = self.board.wins(&self.game_type);
 
-        // Check columns for a winning sequence
-        let n = self.board[0].len();
-        for col_idx in 0..n {
-            let col_str: String = self.board.iter().map(|row| row[col_idx]).collect();
-            for (i, sequence) in sequences.clone().iter().enumerate() {
-                if col_str.contains(sequence) {
-                    res[i] = true;
-                }
-            }
+        // first element is user win, second is computer win
+        match self.user_side() {
+            Side::One => (one, two),
+            Side::Two => (two, one),
         }
+    }
 
-        // Check all possible diagonals for a winning sequence
-        let diagonals = get_diagonal_strings(&self.board);
+    fn position(&self) -> Position {
+        let to_move = if self.user_turn {
+            self.user_side()
+        } else {
+            self.user_side().other()
+        };
 
-        for diagonal in diagonals {
-            for (i, sequence) in sequences.clone().iter().enumerate() {
-                if diagonal.contains(sequence) {
-                    res[i] = true;
-                }
-            }
-        }
-        // No winning sequence found
-        match self.game_type {
-            GameType::TootAndOtto if self.user_otto_toot.as_str() == "TOOT" => {
-                return (res[1], res[0])
-            }
-            _ => return (res[0], res[1]),
+        Position {
+            game_type: self.game_type.clone(),
+            board: self.board.clone(),
+            to_move,
         }
     }
 }
 
-fn get_diagonal_strings(matrix: &[Vec<char>]) -> Vec<String> {
-    let rows = matrix.len();
-    let cols = matrix[0].len();
-
-    // Define a closure to extract diagonals given a starting point (r, c) and step values.
-    let extract_diag = |r: isize, c: isize, step_r: isize, step_c: isize| -> String {
-        (0..)
-            .map(move |i| {
-                let row = r + i * step_r;
-                let col = c + i * step_c;
-                (row, col)
-            })
-            .take_while(|&(row, col)| {
-                row >= 0 && row < rows as isize && col >= 0 && col < cols as isize
-            })
-            .map(|(row, col)| matrix[row as usize][col as usize])
-            .collect()
-    };
-
-    let mut diagonals = vec![];
-
-    // Extract primary diagonals starting from each element of the top row (going down-right).
-    diagonals.extend((0..cols).map(|col| extract_diag(0, col as isize, 1, 1)));
-
-    // Extract primary diagonals starting from each element of the left column (going down-right), excluding top-left corner.
-    diagonals.extend((1..rows).map(|row| extract_diag(row as isize, 0, 1, 1)));
-
-    // Extract secondary diagonals starting from each element of the top row (going down-left).
-    diagonals.extend((0..cols).map(|col| extract_diag(0, col as isize, 1, -1)));
-
-    // Extract secondary diagonals starting from each element of the right column (going down-left), excluding top-right corner.
-    diagonals.extend((1..rows).map(|row| extract_diag(row as isize, cols as isize - 1, 1, -1)));
-
-    diagonals
-}
-
 impl Component for Game {
     type Message = Msg;
     type Properties = Props;
     fn create(_ctx: &Context<Self>) -> Self {
         let props = _ctx.props().clone();
+        // Never the user's choice, or a win could be replayed from a known seed for the stats
+        let seed = rand::random();
         Self {
-            board: vec![
-                vec!['_'; props.num_cols.try_into().unwrap()];
-                props.num_rows.try_into().unwrap()
-            ],
+            board: Board::new(props.num_rows as usize, props.num_cols as usize),
+            plies: vec![],
+            from_position: false,
             user_turn: true,
+            first_move: FirstMove::default(),
+            user_first: true,
             player1: props.player1.clone(),
             player2: 0,
             game_type: props.game_type.clone(),
@@ -266,19 +166,27 @@ impl Component for Game {
             user_otto_toot: "None".to_string(),
             t_selected: false,
             game_started: false,
+            started_at: Utc::now(),
+            seed,
+            rng: ai::seeded_rng(seed.into()),
+            cpu_pending: false,
+            saved: false,
         }
     }
 
     fn update(&mut self, _ctx: &Context<Self>, msg: Self::Message) -> bool {
         match msg {
             Msg::Reset => {
-                self.board = vec![
-                    vec!['_'; self.num_cols.try_into().unwrap()];
-                    self.num_rows.try_into().unwrap()
-                ];
+                self.board = Board::new(self.num_rows as usize, self.num_cols as usize);
+                self.plies.clear();
+                self.from_position = false;
                 self.winners = (false, false);
                 self.player2 = 0;
                 self.game_started = false;
+                self.user_turn = true;
+                self.cpu_pending = false;
+                self.saved = false;
+                self.reseed(rand::random());
                 true
             }
             Msg::UserMove { col, choice } => {
@@ -289,6 +197,7 @@ impl Component for Game {
                 }
 
                 self.perform_move(col, choice);
+                self.plies.push(Ply { col, piece: choice });
                 self.winners = self.is_win();
 
                 self.user_turn = !self.user_turn;
@@ -296,7 +205,9 @@ impl Component for Game {
                 true
             }
             Msg::ComputerMove { col, choice } => {
+                self.cpu_pending = false;
                 self.perform_move(col, choice);
+                self.plies.push(Ply { col, piece: choice });
                 self.winners = self.is_win();
 
                 self.user_turn = !self.user_turn;
@@ -313,6 +224,10 @@ impl Component for Game {
                 self.player2 = cpu;
                 true
             }
+            Msg::ChangeFirst { first } => {
+                self.first_move = first;
+                true
+            }
             Msg::ChangeDim { rows, cols } => {
                 
                 self.num_rows += rows;
@@ -323,20 +238,76 @@ impl Component for Game {
                 if self.num_cols < 4 {
                     self.num_cols = 4;
                 }
-                self.board = vec![
-                    vec!['_'; self.num_cols.try_into().unwrap()];
-                    self.num_rows.try_into().unwrap()
-                ];
+                self.num_rows = self.num_rows.min(MAX_SIZE as i32);
+                self.num_cols = self.num_cols.min(MAX_SIZE as i32);
+                self.board = Board::new(self.num_rows as usize, self.num_cols as usize);
+                self.plies.clear();
+                self.from_position = false;
                 true
             }
             Msg::StartGame => {
                 if self.player2 != 0 {
                     self.game_started = true;
+                    self.started_at = Utc::now();
+                    // A loaded position already says whose turn it is
+                    if !self.from_position {
+                        self.user_first = self.first_move.user_first(&mut self.rng);
+                        self.user_turn = self.user_first;
+                    }
                 } else {
                     alert("Select a CPU to play against!")
                 }
                 true
             }
+            Msg::CopyGame => {
+                let text = if self.from_position {
+                    // There are no moves from an empty board to write down
+                    self.position().to_string()
+                } else {
+                    self.notation().to_string()
+                };
+
+                prompt("Copy this game:", Some(&text));
+                false
+            }
+            Msg::LoadPosition => {
+                let text = match prompt("Paste a position or a game:", None) {
+                    Some(text) => text,
+                    None => return false,
+                };
+
+                // A game loads as the position it ends in
+                let position = match text.parse::<Position>().or_else(|err| {
+                    text.parse::<GameNotation>()
+                        .and_then(|game| game.replay())
+                        .map_err(|_| err)
+                }) {
+                    Ok(position) => position,
+                    Err(err) => {
+                        alert(&format!("Invalid position: {}", err));
+                        return false;
+                    }
+                };
+
+                if position.game_type != self.game_type {
+                    alert(&format!("That is a {} position", position.game_type.display_name()));
+                    return false;
+                }
+
+                if position.is_over() {
+                    alert("That game is already over");
+                    return false;
+                }
+
+                self.num_rows = position.board.rows() as i32;
+                self.num_cols = position.board.cols() as i32;
+                self.user_turn = position.to_move == self.user_side();
+                self.board = position.board;
+                self.winners = self.is_win();
+                self.plies.clear();
+                self.from_position = true;
+                true
+            }
         }
     }
 
@@ -369,7 +340,7 @@ impl Component for Game {
                 };
             });
 
-            let col: Vec<char> = self.board.iter().map(|row| row[i as usize]).collect();
+            let col: Vec<char> = self.board.column(i as usize);
             board.push(html! {
                 <button {disabled} class="column" onclick={onclick}>
 
@@ -419,10 +390,7 @@ impl Component for Game {
             format!("Select CPU Difficulty")
         };
 
-        let title = match self.game_type {
-            GameType::Connect4 => "Connect 4",
-            GameType::TootAndOtto => "Toot & Otto",
-        };
+        let title = self.game_type.display_name();
 
         let mut board_classses = vec!["grid"];
 
@@ -451,6 +419,16 @@ impl Component for Game {
                     <h2 style="text-align: center; padding-top: 5px;">{format!("{} rows", self.num_rows)}</h2>
                     <button onclick={ctx.link().callback(|_| Msg::ChangeDim { rows: 1, cols: 0 })} class="button_cpu_select" style="width: 30px; height: 30px">{"+"}</button>
                     </div>
+                    <div style="display: flex; flex-direction: row; justify-content: center;">
+                        {
+                            for FirstMove::ALL.into_iter().map(|first| html! {
+                                <button onclick={ctx.link().callback(move |_| Msg::ChangeFirst { first })} disabled={self.first_move == first} class="button_cpu_select" style="font-size: small;">{first.label()}</button>
+                            })
+                        }
+                    </div>
+                    <div style="display: flex; flex-direction: row; justify-content: center;">
+                        <p style="font-size: small;">{format!("Seed {}", self.seed)}</p>
+                    </div>
                     <div style="display: flex; flex-direction: row; justify-content: center;">
                         <button onclick={ctx.link().callback(|_| Msg::StartGame)} class="button_cpu_select" style="width: 100px; height: 30px; margin-top: 10px; border-radius: 20%;">{"Start"}</button>
                     </div>
@@ -501,15 +479,15 @@ impl Component for Game {
             <h2 class="subtitle">{ subtitle }</h2>
             <div class={classes!(board_classses)}>{ board }</div>
             <button class="restart" onclick={link.callback(|_| Msg::Reset)}>{"Restart"}</button>
+            <button class="restart" onclick={link.callback(|_| Msg::CopyGame)}>{"Copy game"}</button>
+            <button class="restart" onclick={link.callback(|_| Msg::LoadPosition)}>{"Load position"}</button>
         </div>
         }
     }
 
     fn rendered(&mut self, ctx: &Context<Self>, first_render: bool) {
         if first_render && self.game_type == GameType::TootAndOtto {
-            let options = vec!["TOOT", "OTTO"];
-            self.user_otto_toot =
-                options[if rand::random::<f64>() < 0.5 { 0 } else { 1 }].to_string();
+            self.draw_word();
             let link = ctx.link().clone();
             Timeout::new(3000, move || {
                 link.send_message(Msg::RenderAgain);
@@ -517,9 +495,19 @@ impl Component for Game {
             .forget();
         }
 
-        if !self.user_turn && !self.winners.0 && !self.winners.1 {
-            let (_, col, choice) =
-                self.alpha_beta_minmax(false, (self.player2 as i32) * 2, i32::MIN, i32::MAX);
+        let cpu_level = CpuLevel::from_level(self.player2);
+        // Each move is only chosen once, so a replay draws the same choices from the seed.
+        // Once the game starts this also makes the CPU's opening move when it goes first.
+        let cpu_to_move = self.game_started
+            && !self.user_turn
+            && !self.winners.0
+            && !self.winners.1
+            && !self.cpu_pending;
+        if let Some(level) = cpu_level.filter(|_| cpu_to_move) {
+            let cpu = self.user_side().other();
+            let (col, choice) =
+                ai::cpu_move(&mut self.board, &self.game_type, cpu, level, &mut self.rng);
+            self.cpu_pending = true;
 
             let msg = Msg::ComputerMove { col, choice };
 
@@ -530,16 +518,12 @@ impl Component for Game {
             .forget();
         }
 
-        if self.winners.0 ^ self.winners.1 {
-            let date = Utc::now();
-
-            let formatted_date = format!("{}-{}-{}", date.year(), date.month(), date.day(),);
-            let cpu_name = match self.player2 {
-                1 => "CPU - Easy",
-                2 => "CPU - Medium",
-                3 => "CPU - Hard",
-                _ => panic!("Invalid CPU"),
-            };
+        // A game from a loaded position wasn't played from the start, so it isn't saved
+        if self.winners.0 ^ self.winners.1 && !self.from_position && !self.saved {
+            self.saved = true;
+            let cpu_name = CpuLevel::from_level(self.player2)
+                .expect("Invalid CPU")
+                .player_name();
 
             let connect_game = ConnectGame {
                 player1: self.player1.clone(),
@@ -550,20 +534,19 @@ impl Component for Game {
                 } else {
                     cpu_name.to_string()
                 },
-                date: formatted_date,
+                started_at: self.started_at,
+                ended_at: Utc::now(),
+                rows: Some(self.num_rows as u32),
+                cols: Some(self.num_cols as u32),
+                moves: Some(self.board.piece_count() as u32),
+                seed: Some(self.seed),
+                notation: Some(self.notation().to_string()),
+                player1_first: Some(self.user_first),
+                first_move: Some(self.first_move),
             };
             wasm_bindgen_futures::spawn_local(async move {
-                let response = Request::post("http://127.0.0.1:8000/games/create")
-                    .json(&connect_game)
-                    .unwrap()
-                    .send()
-                    .await
-                    .unwrap();
-
-                let status = response.status();
-
-                if status != 200 {
-                    log::error!("Error saving game: {}", status);
+                if let Err(err) = Api::new().create_game(&connect_game).await {
+                    log::error!("Error saving game: {}", err);
                 }
             });
         }
@@ -1,196 +1,126 @@
-use client::Leaderboard;
-use gloo_net::http::Request;
+use client::{parse_board, GameType, LeaderboardQuery, LeaderboardSort, Opponent, TimeWindow};
+use web_sys::{HtmlInputElement, HtmlSelectElement};
 use yew::prelude::*;
 
+use crate::components::leaderboard_table::LeaderboardTable;
+
 #[function_component]
 pub fn LeaderBoard() -> Html {
-    let to_users = use_state(|| vec![]);
-    {
-        let to_users = to_users.clone();
-        use_effect_with_deps(
-            move |_| {
-                let to_users = to_users.clone();
-                wasm_bindgen_futures::spawn_local(async move {
-                    let fetched_to_users: Vec<Leaderboard> =
-                        Request::get("http://127.0.0.1:8000/leaderboard/tootandotto")
-                            .send()
-                            .await
-                            .unwrap()
-                            .json()
-                            .await
-                            .unwrap();
+    // Filters shared by every table; each table keeps its own page
+    let filters = use_state(LeaderboardQuery::default);
 
-                    to_users.set(fetched_to_users);
-                });
-                || ()
-            },
-            (),
-        );
-    }
+    let update_filters = {
+        let filters = filters.clone();
+        move |update: fn(&mut LeaderboardQuery, String)| {
+            let filters = filters.clone();
+            move |value: String| {
+                let mut query = (*filters).clone();
+                update(&mut query, value);
+                filters.set(query);
+            }
+        }
+    };
 
-    let c4_users = use_state(|| vec![]);
-    {
-        let c4_users = c4_users.clone();
-        use_effect_with_deps(
-            move |_| {
-                let c4_users = c4_users.clone();
-                wasm_bindgen_futures::spawn_local(async move {
-                    let fetched_c4_users: Vec<Leaderboard> =
-                        Request::get("http://127.0.0.1:8000/leaderboard/connect4")
-                            .send()
-                            .await
-                            .unwrap()
-                            .json()
-                            .await
-                            .unwrap();
+    // Clicking the column already sorted by flips the order
+    let on_sort = {
+        let filters = filters.clone();
+        Callback::from(move |sort: LeaderboardSort| {
+            let mut query = (*filters).clone();
+            if query.sort == sort {
+                query.ascending = !query.ascending;
+            } else {
+                query.sort = sort;
+                query.ascending = false;
+            }
+            filters.set(query);
+        })
+    };
 
-                    c4_users.set(fetched_c4_users);
-                });
-                || ()
-            },
-            (),
-        );
-    }
+    let on_window = {
+        let update = update_filters(|query, value| {
+            query.window = value.parse().unwrap_or_default();
+        });
+        Callback::from(move |e: Event| {
+            update(e.target_unchecked_into::<HtmlSelectElement>().value())
+        })
+    };
+
+    let on_opponent = {
+        let update = update_filters(|query, value| {
+            query.opponent = value.parse().ok();
+        });
+        Callback::from(move |e: Event| {
+            update(e.target_unchecked_into::<HtmlSelectElement>().value())
+        })
+    };
+
+    let on_board = {
+        let update = update_filters(|query, value| {
+            query.board = parse_board(&value);
+        });
+        Callback::from(move |e: Event| {
+            update(e.target_unchecked_into::<HtmlInputElement>().value())
+        })
+    };
 
-    let to_user_col: Vec<Leaderboard> = to_users
-        .iter()
-        .map(|user| user.clone())
-        .filter(|user| !user.username.contains("CPU") && !user.username.contains("AI"))
-        .collect();
-    let c4_user_col: Vec<Leaderboard> = c4_users
-        .iter()
-        .map(|user| user.clone())
-        .filter(|user| !user.username.contains("CPU") && !user.username.contains("AI"))
-        .collect();
+    let on_min_games = {
+        let update = update_filters(|query, value| {
+            query.min_games = value.parse().unwrap_or(0);
+        });
+        Callback::from(move |e: Event| {
+            update(e.target_unchecked_into::<HtmlInputElement>().value())
+        })
+    };
 
     html! {
         <>
             <h1>{"Leaderboard"}</h1>
-            <div class = "leaderboard">
-                <div>
-                    <h2>{"Toot & Otto"}</h2>
-                    <table>
-                        <thead>
-                            <tr>
-                                <th>{"Rank"}</th>
-                                <th>{"Username"}</th>
-                                <th>{"Wins"}</th>
-                                <th>{"Losses"}</th>
-                            </tr>
-                        </thead>
-                        <tbody>
-                            {
-                                for to_user_col.iter().enumerate().map(|(i, user)| {
-                                    html! {
-                                        <tr>
-                                            {
-                                                if i == 0 {
-                                                    html! {
-                                                        <td>{"🥇"}</td>
-                                                    }
-                                                } else if i == 1 {
-                                                    html! {
-                                                        <td>{"🥈"}</td>
-                                                    }
-                                                } else if i == 2 {
-                                                    html! {
-                                                        <td>{"🥉"}</td>
-                                                    }
-                                                } else {
-                                                    html! {
-                                                        <td>{i+1}</td>
-                                                    }
-                                                }
-                                            }
-                                            {
-                                                if i == 0 {
-                                                    html! {
-                                                        <>
-                                                        <td id="first">{&user.username}</td>
-                                                        <td id="first">{&user.wins}</td>
-                                                        <td id="first">{&user.losses}</td>
-                                                        </>
-                                                    }
-                                                } else {
-                                                    html! {
-                                                        <>
-                                                        <td>{&user.username}</td>
-                                                        <td>{&user.wins}</td>
-                                                        <td>{&user.losses}</td>
-                                                        </>
-                                                    }
-                                                }
-                                            }
-                                        </tr>
+            <div class="leaderboard-filters">
+                <select onchange={on_window}>
+                    {
+                        for TimeWindow::ALL.iter().map(|window| html! {
+                            <option value={window.as_str()} selected={*window == filters.window}>
+                                {
+                                    match window {
+                                        TimeWindow::AllTime => "All time",
+                                        TimeWindow::ThisMonth => "This month",
+                                        TimeWindow::ThisWeek => "This week",
                                     }
-                                })
-                            }
-                        </tbody>
-                    </table>
-
-                </div>
-                <div>
-                    <h2>{"Connect 4"}</h2>
-                    <table>
-                        <thead>
-                            <tr>
-                                <th>{"Rank"}</th>
-                                <th>{"Username"}</th>
-                                <th>{"Wins"}</th>
-                                <th>{"Losses"}</th>
-                            </tr>
-                        </thead>
-                        <tbody>
-                            {
-                                for c4_user_col.iter().enumerate().map(|(i, user)| {
-                                    html! {
-                                        <tr>
-                                            {
-                                                if i == 0 {
-                                                    html! {
-                                                        <td>{"🥇"}</td>
-                                                    }
-                                                } else if i == 1 {
-                                                    html! {
-                                                        <td>{"🥈"}</td>
-                                                    }
-                                                } else if i == 2 {
-                                                    html! {
-                                                        <td>{"🥉"}</td>
-                                                    }
-                                                } else {
-                                                    html! {
-                                                        <td>{i+1}</td>
-                                                    }
-                                                }
-                                            }
-                                            {
-                                                if i == 0 {
-                                                    html! {
-                                                        <>
-                                                        <td id="first">{&user.username}</td>
-                                                        <td id="first">{&user.wins}</td>
-                                                        <td id="first">{&user.losses}</td>
-                                                        </>
-                                                    }
-                                                } else {
-                                                    html! {
-                                                        <>
-                                                        <td>{&user.username}</td>
-                                                        <td>{&user.wins}</td>
-                                                        <td>{&user.losses}</td>
-                                                        </>
-                                                    }
-                                                }
-                                            }
-                                        </tr>
+                                }
+                            </option>
+                        })
+                    }
+                </select>
+                <select onchange={on_opponent}>
+                    <option value="" selected={filters.opponent.is_none()}>{"Any opponent"}</option>
+                    {
+                        for Opponent::ALL.iter().map(|opponent| html! {
+                            <option value={opponent.as_str()} selected={Some(*opponent) == filters.opponent}>
+                                {
+                                    match opponent {
+                                        Opponent::Human => "Humans",
+                                        Opponent::Cpu(level) => level.player_name(),
                                     }
-                                })
-                            }
-                        </tbody>
-                    </table>
-
-                </div>
+                                }
+                            </option>
+                        })
+                    }
+                </select>
+                <input type="text" placeholder="Board, e.g. 6x7" onchange={on_board}/>
+                <input type="number" min="0" placeholder="Min. games" onchange={on_min_games}/>
+            </div>
+            <div class = "leaderboard">
+                {
+                    // Keyed on the filters so changing one starts every table over from page 1
+                    for GameType::ALL.iter().map(|game_type| html! {
+                        <LeaderboardTable
+                            key={format!("{}-{:?}", game_type.slug(), *filters)}
+                            game_type={game_type.clone()}
+                            filters={(*filters).clone()}
+                            on_sort={on_sort.clone()}
+                        />
+                    })
+                }
             </div>
         </>
     }
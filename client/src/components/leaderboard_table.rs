@@ -0,0 +1,163 @@
+use client::{CpuLevel, GameType, Leaderboard, LeaderboardPage, LeaderboardQuery, LeaderboardSort};
+use yew::prelude::*;
+use yew_router::prelude::Link;
+
+use crate::api::Api;
+use crate::MainRoute;
+
+#[derive(Clone, PartialEq, Properties)]
+pub struct Props {
+    pub game_type: GameType,
+    /// Filters to apply; the table keeps track of its own page.
+    pub filters: LeaderboardQuery,
+    pub on_sort: Callback<LeaderboardSort>,
+}
+
+/// A clickable column header, marked with an arrow while the table is sorted by it.
+fn sort_header(label: &str, sort: LeaderboardSort, props: &Props) -> Html {
+    let arrow = match (props.filters.sort == sort, props.filters.ascending) {
+        (false, _) => "",
+        (true, false) => " ▼",
+        (true, true) => " ▲",
+    };
+    let onclick = props.on_sort.reform(move |_| sort);
+
+    html! {
+        <th class="sortable" {onclick}>{format!("{}{}", label, arrow)}</th>
+    }
+}
+
+/// Wins and losses against a CPU difficulty, e.g. `3-1`.
+fn cpu_record(user: &Leaderboard, level: CpuLevel) -> String {
+    user.cpu_records
+        .iter()
+        .find(|record| record.level == level)
+        .map(|record| format!("{}-{}", record.wins, record.losses))
+        .unwrap_or_else(|| "-".to_string())
+}
+
+/// Previous/next buttons for a paginated table.
+pub fn pager(page: u32, pages: u32, set_page: &UseStateHandle<u32>) -> Html {
+    let previous = {
+        let set_page = set_page.clone();
+        Callback::from(move |_| set_page.set(page - 1))
+    };
+    let next = {
+        let set_page = set_page.clone();
+        Callback::from(move |_| set_page.set(page + 1))
+    };
+
+    html! {
+        <div class="pager">
+            <button onclick={previous} disabled={page <= 1}>{"Previous"}</button>
+            <span>{format!("Page {} of {}", page, pages)}</span>
+            <button onclick={next} disabled={page >= pages}>{"Next"}</button>
+        </div>
+    }
+}
+
+/// One game type's leaderboard, fetched a page at a time.
+#[function_component]
+pub fn LeaderboardTable(props: &Props) -> Html {
+    let page = use_state(|| 1);
+    let users = use_state(|| Option::<LeaderboardPage>::None);
+    {
+        let users = users.clone();
+        let game_type = props.game_type.clone();
+        let query = LeaderboardQuery {
+            page: *page,
+            ..props.filters.clone()
+        };
+        use_effect_with_deps(
+            move |(game_type, query)| {
+                let game_type = game_type.clone();
+                let query = query.clone();
+                wasm_bindgen_futures::spawn_local(async move {
+                    match Api::new().leaderboard(&game_type, &query).await {
+                        Ok(fetched_users) => users.set(Some(fetched_users)),
+                        Err(err) => log::error!("Error fetching leaderboard: {}", err),
+                    }
+                });
+                || ()
+            },
+            (game_type, query),
+        );
+    }
+
+    let entries = users
+        .as_ref()
+        .map(|page| page.entries.clone())
+        .unwrap_or_default();
+    let pages = users.as_ref().map_or(1, LeaderboardPage::pages);
+
+    html! {
+        <div>
+            <h2>{props.game_type.display_name()}</h2>
+            <table>
+                <thead>
+                    <tr>
+                        <th>{"Rank"}</th>
+                        { sort_header("Username", LeaderboardSort::Username, props) }
+                        { sort_header("Games", LeaderboardSort::Games, props) }
+                        { sort_header("Wins", LeaderboardSort::Wins, props) }
+                        { sort_header("Losses", LeaderboardSort::Losses, props) }
+                        { sort_header("Win %", LeaderboardSort::WinPercentage, props) }
+                        { sort_header("Streak", LeaderboardSort::CurrentStreak, props) }
+                        { sort_header("Best streak", LeaderboardSort::LongestStreak, props) }
+                        {
+                            for CpuLevel::ALL.iter().map(|level| html! {
+                                <th>{format!("vs {}", level.player_name())}</th>
+                            })
+                        }
+                        { sort_header("Avg. moves", LeaderboardSort::AverageMoves, props) }
+                        { sort_header("Last played", LeaderboardSort::LastPlayed, props) }
+                    </tr>
+                </thead>
+                <tbody>
+                    {
+                        for entries.iter().map(|user| {
+                            let medal = match user.rank {
+                                1 => "🥇".to_string(),
+                                2 => "🥈".to_string(),
+                                3 => "🥉".to_string(),
+                                rank => rank.to_string(),
+                            };
+                            let id = (user.rank == 1).then_some("first");
+
+                            html! {
+                                <tr>
+                                    <td>{medal}</td>
+                                    <td {id}>
+                                        <Link<MainRoute> to={MainRoute::Profile { username: user.username.clone() }}>
+                                            {&user.username}
+                                        </Link<MainRoute>>
+                                    </td>
+                                    <td {id}>{user.games}</td>
+                                    <td {id}>{user.wins}</td>
+                                    <td {id}>{user.losses}</td>
+                                    <td {id}>{format!("{:.1}%", user.win_percentage)}</td>
+                                    <td {id}>{user.current_streak}</td>
+                                    <td {id}>{user.longest_streak}</td>
+                                    {
+                                        for CpuLevel::ALL.iter().map(|level| html! {
+                                            <td {id}>{cpu_record(user, *level)}</td>
+                                        })
+                                    }
+                                    <td {id}>
+                                        {
+                                            user.average_moves
+                                                .map(|moves| format!("{:.1}", moves))
+                                                .unwrap_or_else(|| "-".to_string())
+                                        }
+                                    </td>
+                                    <td {id}>{user.last_played.clone().unwrap_or_else(|| "-".to_string())}</td>
+                                </tr>
+                            }
+                        })
+                    }
+                </tbody>
+            </table>
+            { pager(*page, pages, &page) }
+        </div>
+    }
+}
@@ -1,14 +1,14 @@
-use client::User;
+use client::{Session, User};
 use gloo_dialogs::alert;
-use gloo_net::http::Request;
 use yew::prelude::*;
 use yew_router::prelude::{use_navigator, Link};
 
+use crate::api::{Api, ApiError};
 use crate::{LoginRoute, MainRoute};
 
 #[derive(Clone, PartialEq, Properties)]
 pub struct Props {
-    pub set_username: Callback<Option<String>>,
+    pub set_session: Callback<Option<Session>>,
 }
 
 #[function_component]
@@ -23,13 +23,37 @@ pub fn LoginForm(props: &Props) -> Html {
 
     let navigator = use_navigator().unwrap();
 
+    let play_as_guest = {
+        let navigator = navigator.clone();
+        let set_session = props.set_session.clone();
+
+        move |_| {
+            let navigator = navigator.clone();
+            let set_session = set_session.clone();
+
+            wasm_bindgen_futures::spawn_local(async move {
+                let session = match Api::new().guest().await {
+                    Ok(session) => session,
+                    Err(err) => {
+                        alert(&format!("Error: {}", err));
+                        return;
+                    }
+                };
+
+                set_session.emit(Some(session));
+
+                navigator.push(&MainRoute::Connect4);
+            });
+        }
+    };
+
     let onclick = {
         let username_ref = username_ref.clone();
         let password_ref = password_ref.clone();
 
         move |_| {
             let navigator = navigator.clone();
-            let set_username = props.set_username.clone();
+            let set_session = props.set_session.clone();
 
             let username = username_ref.cast::<web_sys::HtmlInputElement>();
 
@@ -70,30 +94,23 @@ pub fn LoginForm(props: &Props) -> Html {
                     password: password_value.to_string(),
                 };
 
-                let result = Request::post("http://127.0.0.1:8000/users/login")
-                    .json(&user)
-                    .unwrap()
-                    .send()
-                    .await;
-                
-                let response = match result {
-                    Ok(response) => response,
+                let session = match Api::new().login(&user).await {
+                    Ok(session) => session,
+                    Err(ApiError::Status(500)) => {
+                        alert("Database not available");
+                        return;
+                    }
+                    Err(ApiError::Status(_)) => {
+                        alert("Invalid username or password");
+                        return;
+                    }
                     Err(err) => {
                         alert(&format!("Error: {}", err));
                         return;
                     }
                 };
-                let status = response.status();
-                if status == 500 {
-                    alert("Database not available");
-                    return;
-                }
-                if status != 200 {
-                    alert("Invalid username or password");
-                    return;
-                }
 
-                set_username.emit(Some(username_value.to_string()));
+                set_session.emit(Some(session));
 
                 navigator.push(&MainRoute::Connect4);
             });
@@ -113,6 +130,9 @@ pub fn LoginForm(props: &Props) -> Html {
             <div>
                 <button type="submit" {onclick} >{"Login"}</button>
             </div>
+            <div>
+                <button onclick={play_as_guest}>{"Play as guest"}</button>
+            </div>
             <Link<LoginRoute> to={LoginRoute::Register}>{ "Click here to register" }</Link<LoginRoute>>
         </>
     }
@@ -2,4 +2,8 @@ pub mod login_form;
 pub mod register_form;
 pub mod navbar;
 pub mod connect4;
-pub mod leaderboard;
\ No newline at end of file
+pub mod leaderboard;
+pub mod account;
+pub mod admin;
+pub mod leaderboard_table;
+pub mod profile;
\ No newline at end of file
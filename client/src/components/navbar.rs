@@ -3,8 +3,13 @@ use yew_router::prelude::*;
 
 use crate::MainRoute;
 
+#[derive(Clone, PartialEq, Properties)]
+pub struct Props {
+    pub admin: bool,
+}
+
 #[function_component]
-pub fn Navbar() -> Html {
+pub fn Navbar(props: &Props) -> Html {
     html! {
         <nav class="navbar">
             <div>
@@ -18,6 +23,20 @@ pub fn Navbar() -> Html {
                     <Link<MainRoute> classes={classes!("navbar-item")} to={MainRoute::Leaderboard}>
                         { "Leaderboard" }
                     </Link<MainRoute>>
+                    <Link<MainRoute> classes={classes!("navbar-item")} to={MainRoute::Account}>
+                        { "Account" }
+                    </Link<MainRoute>>
+                    {
+                        if props.admin {
+                            html! {
+                                <Link<MainRoute> classes={classes!("navbar-item")} to={MainRoute::Admin}>
+                                    { "Admin" }
+                                </Link<MainRoute>>
+                            }
+                        } else {
+                            html! {}
+                        }
+                    }
                     <Link<MainRoute> classes={classes!("navbar-item")} to={MainRoute::Logout}>
                         { "Logout" }
                     </Link<MainRoute>>
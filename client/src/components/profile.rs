@@ -0,0 +1,216 @@
+use client::{CpuLevel, GameRecord, ANONYMOUS_PLAYER};
+use yew::prelude::*;
+use yew_router::prelude::Link;
+
+use crate::api::{Api, ApiError};
+use crate::components::leaderboard_table::pager;
+use crate::MainRoute;
+
+const GAMES_PER_PAGE: u32 = 10;
+
+#[derive(Clone, PartialEq, Properties)]
+pub struct Props {
+    pub username: String,
+}
+
+/// Links to another player's profile, unless the name isn't a real account.
+fn player_link(username: &str) -> Html {
+    if username == ANONYMOUS_PLAYER || CpuLevel::from_player_name(username).is_some() {
+        return html! { username };
+    }
+
+    html! {
+        <Link<MainRoute> to={MainRoute::Profile { username: username.to_string() }}>
+            {username}
+        </Link<MainRoute>>
+    }
+}
+
+fn percentage(wins: u32, losses: u32) -> f64 {
+    if wins + losses == 0 {
+        0.0
+    } else {
+        f64::from(wins) * 100.0 / f64::from(wins + losses)
+    }
+}
+
+fn game_row(username: &str, record: &GameRecord) -> Html {
+    let game = &record.game;
+    let opponent = if game.player1 == username {
+        &game.player2
+    } else {
+        &game.player1
+    };
+    let result = if game.winner == username {
+        "Won"
+    } else {
+        "Lost"
+    };
+
+    html! {
+        <tr>
+            <td>{game.ended_at.date_naive().to_string()}</td>
+            <td>{game.game_type.display_name()}</td>
+            <td>{player_link(opponent)}</td>
+            <td>{result}</td>
+        </tr>
+    }
+}
+
+#[function_component]
+pub fn Profile(props: &Props) -> Html {
+    let page = use_state(|| 1);
+    let profile = use_state(|| Option::<client::Profile>::None);
+    let not_found = use_state(|| false);
+    {
+        let profile = profile.clone();
+        let not_found = not_found.clone();
+        use_effect_with_deps(
+            move |(username, page)| {
+                let username = username.clone();
+                let page = *page;
+                wasm_bindgen_futures::spawn_local(async move {
+                    match Api::new().profile(&username, page, GAMES_PER_PAGE).await {
+                        Ok(fetched) => {
+                            not_found.set(false);
+                            profile.set(Some(fetched));
+                        }
+                        Err(ApiError::Status(404)) => not_found.set(true),
+                        Err(err) => log::error!("Error fetching profile: {}", err),
+                    }
+                });
+                || ()
+            },
+            (props.username.clone(), *page),
+        );
+    }
+
+    if *not_found {
+        return html! { <h1>{format!("No player named {}", props.username)}</h1> };
+    }
+
+    let profile = match &*profile {
+        Some(profile) => profile,
+        None => return html! {},
+    };
+
+    let pages = (profile.total_games as u32).div_ceil(GAMES_PER_PAGE).max(1);
+
+    html! {
+        <>
+            <h1>{&profile.username}</h1>
+            <div>
+                <h2>{"Stats"}</h2>
+                <table>
+                    <thead>
+                        <tr>
+                            <th>{"Game"}</th>
+                            <th>{"Games"}</th>
+                            <th>{"Wins"}</th>
+                            <th>{"Losses"}</th>
+                            <th>{"Win %"}</th>
+                            <th>{"Streak"}</th>
+                            <th>{"Best streak"}</th>
+                            <th>{"Avg. moves"}</th>
+                            <th>{"Moving first"}</th>
+                            <th>{"Moving second"}</th>
+                            <th>{"Last played"}</th>
+                        </tr>
+                    </thead>
+                    <tbody>
+                        {
+                            for profile.stats.iter().map(|entry| {
+                                let stats = &entry.stats;
+                                html! {
+                                    <tr>
+                                        <td>{entry.game_type.display_name()}</td>
+                                        <td>{stats.games}</td>
+                                        <td>{stats.wins}</td>
+                                        <td>{stats.losses}</td>
+                                        <td>{format!("{:.1}%", stats.win_percentage)}</td>
+                                        <td>{stats.current_streak}</td>
+                                        <td>{stats.longest_streak}</td>
+                                        <td>
+                                            {
+                                                stats.average_moves
+                                                    .map(|moves| format!("{:.1}", moves))
+                                                    .unwrap_or_else(|| "-".to_string())
+                                            }
+                                        </td>
+                                        <td>{format!("{}-{}", stats.moving_first.wins, stats.moving_first.losses)}</td>
+                                        <td>{format!("{}-{}", stats.moving_second.wins, stats.moving_second.losses)}</td>
+                                        <td>{stats.last_played.clone().unwrap_or_else(|| "-".to_string())}</td>
+                                    </tr>
+                                }
+                            })
+                        }
+                    </tbody>
+                </table>
+            </div>
+            <div>
+                <h2>{"Win rate over time"}</h2>
+                <table>
+                    <tbody>
+                        {
+                            for profile.history.iter().map(|month| {
+                                let rate = percentage(month.wins, month.losses);
+                                html! {
+                                    <tr>
+                                        <td>{&month.month}</td>
+                                        <td>{format!("{} games", month.wins + month.losses)}</td>
+                                        <td>{format!("{:.1}%", rate)}</td>
+                                        <td class="win-rate">
+                                            <div class="win-rate-bar" style={format!("width: {:.1}%;", rate)}></div>
+                                        </td>
+                                    </tr>
+                                }
+                            })
+                        }
+                    </tbody>
+                </table>
+            </div>
+            <div>
+                <h2>{"Head to head"}</h2>
+                <table>
+                    <thead>
+                        <tr>
+                            <th>{"Opponent"}</th>
+                            <th>{"Wins"}</th>
+                            <th>{"Losses"}</th>
+                            <th>{"Win %"}</th>
+                        </tr>
+                    </thead>
+                    <tbody>
+                        {
+                            for profile.head_to_head.iter().map(|record| html! {
+                                <tr>
+                                    <td>{player_link(&record.opponent)}</td>
+                                    <td>{record.wins}</td>
+                                    <td>{record.losses}</td>
+                                    <td>{format!("{:.1}%", percentage(record.wins, record.losses))}</td>
+                                </tr>
+                            })
+                        }
+                    </tbody>
+                </table>
+            </div>
+            <div>
+                <h2>{"Recent games"}</h2>
+                <table>
+                    <thead>
+                        <tr>
+                            <th>{"Date"}</th>
+                            <th>{"Game"}</th>
+                            <th>{"Opponent"}</th>
+                            <th>{"Result"}</th>
+                        </tr>
+                    </thead>
+                    <tbody>
+                        { for profile.recent_games.iter().map(|record| game_row(&profile.username, record)) }
+                    </tbody>
+                </table>
+                { pager(*page, pages, &page) }
+            </div>
+        </>
+    }
+}
@@ -1,15 +1,15 @@
-use client::User;
-use gloo_net::http::Request;
+use client::{Session, User};
 use yew::prelude::*;
 
 use gloo_dialogs::alert;
 use yew_router::prelude::{use_navigator, Link};
 
+use crate::api::{Api, ApiError};
 use crate::{LoginRoute, MainRoute};
 
 #[derive(Clone, PartialEq, Properties)]
 pub struct Props {
-    pub set_username: Callback<Option<String>>,
+    pub set_session: Callback<Option<Session>>,
 }
 
 #[function_component]
@@ -29,7 +29,7 @@ pub fn RegisterForm(props: &Props) -> Html {
         let password_ref = password_ref.clone();
         move |_| {
             let navigator = navigator.clone();
-            let set_username = props.set_username.clone();
+            let set_session = props.set_session.clone();
 
             let username = username_ref.cast::<web_sys::HtmlInputElement>();
 
@@ -70,30 +70,23 @@ pub fn RegisterForm(props: &Props) -> Html {
                     password: password_value.to_string(),
                 };
 
-                let result = Request::post("http://127.0.0.1:8000/users/register")
-                    .json(&user)
-                    .unwrap()
-                    .send()
-                    .await;
-
-                let response = match result {
-                    Ok(response) => response,
+                let session = match Api::new().register(&user).await {
+                    Ok(session) => session,
+                    Err(ApiError::Status(500)) => {
+                        alert("Database not available");
+                        return;
+                    }
+                    Err(ApiError::Status(_)) => {
+                        alert("Invalid username or password");
+                        return;
+                    }
                     Err(err) => {
                         alert(&format!("Error: {}", err));
                         return;
                     }
                 };
-                let status = response.status();
-                if status == 500 {
-                    alert("Database not available");
-                    return;
-                }
-                if status != 200 {
-                    alert("Invalid username or password");
-                    return;
-                }
 
-                set_username.emit(Some(username_value.to_string()));
+                set_session.emit(Some(session));
 
                 // Redirect to the home page
                 navigator.push(&MainRoute::Connect4);
@@ -0,0 +1,411 @@
+//! The rules both games share: pieces drop to the lowest free cell of a column, and
+//! four in a row wins.
+
+use crate::GameType;
+
+/// What an empty cell holds.
+pub const EMPTY: char = '_';
+
+/// The smallest number of rows or columns a board can have, so four in a row fits.
+pub const MIN_SIZE: usize = 4;
+
+/// The largest number of rows or columns a board can have, so a pasted game or position
+/// can't ask for more memory than a board needs.
+pub const MAX_SIZE: usize = 20;
+
+/// One of the two players of a game.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Side {
+    One,
+    Two,
+}
+
+impl Side {
+    pub fn other(self) -> Side {
+        match self {
+            Side::One => Side::Two,
+            Side::Two => Side::One,
+        }
+    }
+
+    /// How the side is written down: its colour in Connect 4, or the word it spells
+    /// in Toot & Otto.
+    pub fn name(self, game_type: &GameType) -> &'static str {
+        match (game_type, self) {
+            (GameType::Connect4, Side::One) => "R",
+            (GameType::Connect4, Side::Two) => "B",
+            (GameType::TootAndOtto, Side::One) => "TOOT",
+            (GameType::TootAndOtto, Side::Two) => "OTTO",
+        }
+    }
+
+    pub fn from_name(game_type: &GameType, name: &str) -> Option<Side> {
+        [Side::One, Side::Two]
+            .into_iter()
+            .find(|side| side.name(game_type) == name)
+    }
+
+    /// The four in a row that wins the game for this side.
+    pub fn sequence(self, game_type: &GameType) -> &'static str {
+        match (game_type, self) {
+            (GameType::Connect4, Side::One) => "RRRR",
+            (GameType::Connect4, Side::Two) => "BBBB",
+            (GameType::TootAndOtto, Side::One) => "TOOT",
+            (GameType::TootAndOtto, Side::Two) => "OTTO",
+        }
+    }
+
+    /// The pieces this side may place. Both sides share the letters in Toot & Otto.
+    pub fn pieces(self, game_type: &GameType) -> &'static [char] {
+        match (game_type, self) {
+            (GameType::Connect4, Side::One) => &['R'],
+            (GameType::Connect4, Side::Two) => &['B'],
+            (GameType::TootAndOtto, _) => &['T', 'O'],
+        }
+    }
+}
+
+/// The pieces that can appear on a board of `game_type`.
+pub fn pieces(game_type: &GameType) -> &'static [char] {
+    match game_type {
+        GameType::Connect4 => &['R', 'B'],
+        GameType::TootAndOtto => &['T', 'O'],
+    }
+}
+
+/// A grid of cells, with row 0 at the top.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Board {
+    cells: Vec<Vec<char>>,
+}
+
+impl Board {
+    pub fn new(rows: usize, cols: usize) -> Board {
+        Board {
+            cells: vec![vec![EMPTY; cols]; rows],
+        }
+    }
+
+    /// A board holding `cells`, which must all be rows of the same length.
+    pub(crate) fn from_cells(cells: Vec<Vec<char>>) -> Board {
+        Board { cells }
+    }
+
+    pub fn rows(&self) -> usize {
+        self.cells.len()
+    }
+
+    pub fn cols(&self) -> usize {
+        self.cells.first().map_or(0, Vec::len)
+    }
+
+    pub fn cells(&self) -> &[Vec<char>] {
+        &self.cells
+    }
+
+    /// The cells of one column, top first.
+    pub fn column(&self, col: usize) -> Vec<char> {
+        self.cells.iter().map(|row| row[col]).collect()
+    }
+
+    /// Whether `col` exists and has room for another piece.
+    pub fn is_valid_move(&self, col: usize) -> bool {
+        col < self.cols() && self.cells[0][col] == EMPTY
+    }
+
+    /// The columns with room for another piece, left to right.
+    pub fn valid_moves(&self) -> Vec<usize> {
+        (0..self.cols())
+            .filter(|&col| self.is_valid_move(col))
+            .collect()
+    }
+
+    pub fn is_full(&self) -> bool {
+        self.valid_moves().is_empty()
+    }
+
+    /// How many pieces have been placed.
+    pub fn piece_count(&self) -> usize {
+        self.cells
+            .iter()
+            .flatten()
+            .filter(|&&cell| cell != EMPTY)
+            .count()
+    }
+
+    /// Drops `piece` into `col`, returning the row it lands in, or `None` if the column
+    /// is full or doesn't exist.
+    pub fn perform_move(&mut self, col: usize, piece: char) -> Option<usize> {
+        if col >= self.cols() {
+            return None;
+        }
+
+        let row = (0..self.rows())
+            .rev()
+            .find(|&row| self.cells[row][col] == EMPTY)?;
+        self.cells[row][col] = piece;
+
+        Some(row)
+    }
+
+    /// Takes the top piece back out of `col`, if it has one. A column that doesn't exist
+    /// has nothing to take.
+    pub fn undo_move(&mut self, col: usize) {
+        if col >= self.cols() {
+            return;
+        }
+
+        if let Some(row) = (0..self.rows()).find(|&row| self.cells[row][col] != EMPTY) {
+            self.cells[row][col] = EMPTY;
+        }
+    }
+
+    /// Whether each side has four in a row, as `(Side::One, Side::Two)`. In Toot & Otto
+    /// one piece can complete both words at once.
+    pub fn wins(&self, game_type: &GameType) -> (bool, bool) {
+        let lines = self.lines();
+        let has = |side: Side| {
+            let sequence = side.sequence(game_type);
+            lines.iter().any(|line| line.contains(sequence))
+        };
+
+        (has(Side::One), has(Side::Two))
+    }
+
+    /// Every row, column and diagonal, as strings.
+    pub(crate) fn lines(&self) -> Vec<String> {
+        let mut lines: Vec<String> = self.cells.iter().map(|row| row.iter().collect()).collect();
+
+        lines.extend((0..self.cols()).map(|col| self.column(col).into_iter().collect()));
+        lines.extend(get_diagonal_strings(&self.cells));
+
+        lines
+    }
+}
+
+fn get_diagonal_strings(matrix: &[Vec<char>]) -> Vec<String> {
+    let rows = matrix.len();
+    let cols = matrix[0].len();
+
+    // Define a closure to extract diagonals given a starting point (r, c) and step values.
+    let extract_diag = |r: isize, c: isize, step_r: isize, step_c: isize| -> String {
+        (0..)
+            .map(move |i| {
+                let row = r + i * step_r;
+                let col = c + i * step_c;
+                (row, col)
+            })
+            .take_while(|&(row, col)| {
+                row >= 0 && row < rows as isize && col >= 0 && col < cols as isize
+            })
+            .map(|(row, col)| matrix[row as usize][col as usize])
+            .collect()
+    };
+
+    let mut diagonals = vec![];
+
+    // Extract primary diagonals starting from each element of the top row (going down-right).
+    diagonals.extend((0..cols).map(|col| extract_diag(0, col as isize, 1, 1)));
+
+    // Extract primary diagonals starting from each element of the left column (going down-right), excluding top-left corner.
+    diagonals.extend((1..rows).map(|row| extract_diag(row as isize, 0, 1, 1)));
+
+    // Extract secondary diagonals starting from each element of the top row (going down-left).
+    diagonals.extend((0..cols).map(|col| extract_diag(0, col as isize, 1, -1)));
+
+    // Extract secondary diagonals starting from each element of the right column (going down-left), excluding top-right corner.
+    diagonals.extend((1..rows).map(|row| extract_diag(row as isize, cols as isize - 1, 1, -1)));
+
+    diagonals
+}
+
+#[cfg(test)]
+mod tests {
+    use proptest::prelude::*;
+
+    use super::*;
+
+    fn board(rows: &[&str]) -> Board {
+        Board::from_cells(rows.iter().map(|row| row.chars().collect()).collect())
+    }
+
+    /// Whether `side` has four in a row, checked cell by cell in every direction.
+    fn brute_force_win(board: &Board, game_type: &GameType, side: Side) -> bool {
+        let sequence: Vec<char> = side.sequence(game_type).chars().collect();
+        let (rows, cols) = (board.rows() as isize, board.cols() as isize);
+
+        (0..rows).any(|row| {
+            (0..cols).any(|col| {
+                [(0, 1), (1, 0), (1, 1), (1, -1)]
+                    .into_iter()
+                    .any(|(step_row, step_col)| {
+                        sequence.iter().enumerate().all(|(i, &wanted)| {
+                            let (r, c) = (row + i as isize * step_row, col + i as isize * step_col);
+                            r >= 0
+                                && r < rows
+                                && c >= 0
+                                && c < cols
+                                && board.cells()[r as usize][c as usize] == wanted
+                        })
+                    })
+            })
+        })
+    }
+
+    #[test]
+    fn finds_every_direction_on_non_square_boards() {
+        let cases = [
+            (
+                vec!["____", "____", "____", "____", "____", "RRRR"],
+                (true, false),
+            ),
+            (
+                vec!["B______", "B______", "B______", "B______"],
+                (false, true),
+            ),
+            (
+                vec!["_______", "___R___", "____R__", "_____R_", "______R"],
+                (true, false),
+            ),
+            (
+                vec!["___B", "__B_", "_B__", "B___", "____", "____", "____"],
+                (false, true),
+            ),
+            (vec!["______", "RRR_BB", "BBBRRR", "RRRBBB"], (false, false)),
+        ];
+
+        for (rows, wins) in cases {
+            assert_eq!(board(&rows).wins(&GameType::Connect4), wins, "{:?}", rows);
+        }
+    }
+
+    #[test]
+    fn one_letter_can_spell_both_words() {
+        // The O dropped into the second column spells TOOT across and OTTO down
+        let mut board = board(&["T_OT", "OTOO", "TTTT", "OOOO"]);
+        assert_eq!(board.wins(&GameType::TootAndOtto), (false, false));
+
+        assert_eq!(board.perform_move(1, 'O'), Some(0));
+        assert_eq!(board.wins(&GameType::TootAndOtto), (true, true));
+    }
+
+    #[test]
+    fn full_and_missing_columns_are_rejected() {
+        let mut board = Board::new(4, 5);
+        for row in (0..4).rev() {
+            assert_eq!(board.perform_move(2, 'R'), Some(row));
+        }
+
+        let full = board.clone();
+        assert!(!board.is_valid_move(2));
+        assert_eq!(board.perform_move(2, 'B'), None);
+        assert_eq!(board.perform_move(5, 'B'), None);
+        assert_eq!(board, full);
+        assert_eq!(board.valid_moves(), [0, 1, 3, 4]);
+    }
+
+    /// A board of any allowed size, with each cell empty or holding a piece of `game_type`,
+    /// whether or not it could come up in play.
+    fn any_board(game_type: GameType) -> impl Strategy<Value = Board> {
+        let cell = prop::sample::select(vec![EMPTY, pieces(&game_type)[0], pieces(&game_type)[1]]);
+
+        (MIN_SIZE..=MAX_SIZE, MIN_SIZE..=MAX_SIZE).prop_flat_map(move |(rows, cols)| {
+            prop::collection::vec(prop::collection::vec(cell.clone(), cols), rows)
+                .prop_map(Board::from_cells)
+        })
+    }
+
+    /// An empty board of any allowed size, and some moves to make on it, which may not all
+    /// be legal.
+    fn any_game() -> impl Strategy<Value = (Board, Vec<(usize, char)>)> {
+        (MIN_SIZE..=MAX_SIZE, MIN_SIZE..=MAX_SIZE).prop_flat_map(|(rows, cols)| {
+            let ply = (0..cols + 1, prop::sample::select(vec!['R', 'B', 'T', 'O']));
+            (
+                Just(Board::new(rows, cols)),
+                prop::collection::vec(ply, 0..rows * cols + 8),
+            )
+        })
+    }
+
+    proptest! {
+        #[test]
+        fn connect4_wins_match_brute_force(board in any_board(GameType::Connect4)) {
+            let game_type = GameType::Connect4;
+            prop_assert_eq!(
+                board.wins(&game_type),
+                (
+                    brute_force_win(&board, &game_type, Side::One),
+                    brute_force_win(&board, &game_type, Side::Two)
+                )
+            );
+        }
+
+        #[test]
+        fn toot_and_otto_wins_match_brute_force(board in any_board(GameType::TootAndOtto)) {
+            let game_type = GameType::TootAndOtto;
+            prop_assert_eq!(
+                board.wins(&game_type),
+                (
+                    brute_force_win(&board, &game_type, Side::One),
+                    brute_force_win(&board, &game_type, Side::Two)
+                )
+            );
+        }
+
+        #[test]
+        fn moves_undo_in_reverse((mut board, plies) in any_game()) {
+            let mut history = vec![board.clone()];
+            let mut played = vec![];
+
+            for (col, piece) in plies {
+                let before = board.clone();
+                let valid = board.is_valid_move(col);
+
+                match board.perform_move(col, piece) {
+                    Some(row) => {
+                        prop_assert!(valid);
+                        prop_assert_eq!(board.cells()[row][col], piece);
+                        prop_assert_eq!(board.piece_count(), before.piece_count() + 1);
+                        // The piece landed on the floor or on another piece
+                        prop_assert!(row + 1 == board.rows() || board.cells()[row + 1][col] != EMPTY);
+
+                        played.push(col);
+                        history.push(board.clone());
+                    }
+                    None => {
+                        prop_assert!(!valid);
+                        prop_assert_eq!(&board, &before);
+                    }
+                }
+            }
+
+            // Columns off the board have nothing to take back
+            let last = board.clone();
+            board.undo_move(board.cols());
+            prop_assert_eq!(&board, &last);
+
+            history.pop();
+            while let Some(col) = played.pop() {
+                board.undo_move(col);
+                prop_assert_eq!(&board, &history.pop().unwrap());
+            }
+            prop_assert_eq!(board.piece_count(), 0);
+        }
+
+        #[test]
+        fn full_columns_take_no_more(rows in MIN_SIZE..=MAX_SIZE, cols in MIN_SIZE..=MAX_SIZE, col in 0..MAX_SIZE) {
+            let col = col % cols;
+            let mut board = Board::new(rows, cols);
+
+            for _ in 0..rows {
+                prop_assert!(board.perform_move(col, 'T').is_some());
+            }
+
+            let full = board.clone();
+            prop_assert_eq!(board.perform_move(col, 'O'), None);
+            prop_assert_eq!(&board, &full);
+            prop_assert!(!board.valid_moves().contains(&col));
+            prop_assert!(!board.is_full());
+        }
+    }
+}
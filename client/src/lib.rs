@@ -1,29 +1,670 @@
+use std::fmt;
+use std::str::FromStr;
+
+use chrono::{DateTime, Datelike, Duration, NaiveDate, Utc};
 use serde::{Deserialize, Serialize};
 
-#[derive(Serialize, Deserialize, Debug, Clone)]
+pub mod ai;
+pub mod engine;
+pub mod notation;
+
+/// Name that replaces a deleted user in the games they leave behind.
+pub const ANONYMOUS_PLAYER: &str = "[deleted]";
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
 pub struct ConnectGame {
     pub game_type: GameType,
     pub player1: String,
     pub player2: String,
     pub winner: String,
-    pub date: String,
+    /// When the first move could be made.
+    pub started_at: DateTime<Utc>,
+    /// When the game was won; the server stamps this as it saves the game.
+    pub ended_at: DateTime<Utc>,
+    /// Board size, for games saved since it started being recorded.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub rows: Option<u32>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub cols: Option<u32>,
+    /// Pieces placed over the whole game, by both players.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub moves: Option<u32>,
+    /// The seed the CPU's choices were drawn from, for games against the CPU that can be
+    /// replayed with `ai::replay`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub seed: Option<u32>,
+    /// Every ply as a `GameNotation`, for games played from an empty board.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub notation: Option<String>,
+    /// Whether `player1` made the first move, for games saved since it started being recorded.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub player1_first: Option<bool>,
+    /// Who was picked to move first against the CPU. Games saved without it had the user
+    /// move first.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub first_move: Option<FirstMove>,
 }
 
+/// A game as stored on the server, along with the id used to refer to it.
 #[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct GameRecord {
+    pub id: String,
+    #[serde(flatten)]
+    pub game: ConnectGame,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq, Hash)]
 pub enum GameType {
     Connect4,
     TootAndOtto,
 }
 
+impl GameType {
+    pub const ALL: [GameType; 2] = [GameType::Connect4, GameType::TootAndOtto];
+
+    /// The name used for this game type in URLs, e.g. `/leaderboard/connect4`.
+    pub fn slug(&self) -> &'static str {
+        match self {
+            GameType::Connect4 => "connect4",
+            GameType::TootAndOtto => "tootandotto",
+        }
+    }
+
+    pub fn from_slug(slug: &str) -> Option<GameType> {
+        GameType::ALL
+            .into_iter()
+            .find(|game_type| game_type.slug() == slug)
+    }
+
+    pub fn display_name(&self) -> &'static str {
+        match self {
+            GameType::Connect4 => "Connect 4",
+            GameType::TootAndOtto => "Toot & Otto",
+        }
+    }
+}
+
 #[derive(Serialize, Deserialize, Debug)]
 pub struct User {
     pub username: String,
     pub password: String,
 }
 
-#[derive(Serialize, Deserialize, Debug, Clone)]
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, Default, PartialEq)]
+pub enum Role {
+    #[default]
+    Player,
+    Admin,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct Session {
+    pub username: String,
+    pub token: String,
+    #[serde(default)]
+    pub guest: bool,
+    #[serde(default)]
+    pub role: Role,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct PasswordChange {
+    pub old_password: String,
+    pub new_password: String,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct UsernameChange {
+    pub username: String,
+}
+
+/// What happens to a user's games when their account is deleted.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+pub enum GameRetention {
+    /// Keep the games but replace the user's name with `ANONYMOUS_PLAYER`.
+    Anonymize,
+    /// Remove every game the user took part in.
+    Purge,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct AccountDeletion {
+    /// Checked again before anything is deleted.
+    pub password: String,
+    pub games: GameRetention,
+}
+
+/// What the admin page shows about an account.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct UserSummary {
+    pub username: String,
+    pub role: Role,
+    pub guest: bool,
+    pub banned: bool,
+}
+
+/// One admin action, as recorded in the `audit_log` collection.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct AuditEntry {
+    pub admin: String,
+    pub action: String,
+    pub target: String,
+    pub date: DateTime<Utc>,
+}
+
+/// Wins and losses against one CPU difficulty.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+pub struct CpuRecord {
+    pub level: CpuLevel,
+    pub wins: u32,
+    pub losses: u32,
+}
+
+/// Wins and losses in the games a player moved first in, or second.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Default)]
+pub struct TurnRecord {
+    pub wins: u32,
+    pub losses: u32,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
 pub struct Leaderboard {
+    /// 1-based position on the full (unpaginated) leaderboard, in the order it was sorted by.
+    #[serde(default)]
+    pub rank: u32,
     pub username: String,
     pub wins: u32,
     pub losses: u32,
+    #[serde(default)]
+    pub games: u32,
+    /// Share of games won, from 0 to 100.
+    #[serde(default)]
+    pub win_percentage: f64,
+    /// Wins since the player's last loss.
+    #[serde(default)]
+    pub current_streak: u32,
+    #[serde(default)]
+    pub longest_streak: u32,
+    /// One entry per CPU difficulty the player has faced.
+    #[serde(default)]
+    pub cpu_records: Vec<CpuRecord>,
+    /// Average number of pieces placed per game, over games that recorded it.
+    #[serde(default)]
+    pub average_moves: Option<f64>,
+    /// Date of the player's most recent game, as `YYYY-MM-DD`.
+    #[serde(default)]
+    pub last_played: Option<String>,
+    /// Over games that recorded who moved first.
+    #[serde(default)]
+    pub moving_first: TurnRecord,
+    #[serde(default)]
+    pub moving_second: TurnRecord,
+}
+
+/// One page of a leaderboard, along with how many players it has in total.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct LeaderboardPage {
+    pub total: u64,
+    pub page: u32,
+    pub limit: u32,
+    pub entries: Vec<Leaderboard>,
+}
+
+impl LeaderboardPage {
+    pub fn pages(&self) -> u32 {
+        (self.total as u32).div_ceil(self.limit.max(1)).max(1)
+    }
+}
+
+/// A player's record in one game type, as shown on their profile.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct GameTypeStats {
+    pub game_type: GameType,
+    pub stats: Leaderboard,
+}
+
+/// A player's record against one opponent, human or CPU, across all game types.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct HeadToHead {
+    pub opponent: String,
+    pub wins: u32,
+    pub losses: u32,
+}
+
+/// Games won and lost in one calendar month.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct MonthlyRecord {
+    /// As `YYYY-MM`.
+    pub month: String,
+    pub wins: u32,
+    pub losses: u32,
+}
+
+/// Everything shown on a player's profile page.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct Profile {
+    pub username: String,
+    pub stats: Vec<GameTypeStats>,
+    /// Most played opponent first.
+    pub head_to_head: Vec<HeadToHead>,
+    /// Oldest month first.
+    pub history: Vec<MonthlyRecord>,
+    /// One page of the player's games, newest first.
+    pub recent_games: Vec<GameRecord>,
+    pub total_games: u64,
+    pub page: u32,
+    pub limit: u32,
+}
+
+/// Error for query values that don't name a known option.
+#[derive(Debug, Clone, PartialEq)]
+pub struct UnknownValue(pub String);
+
+impl fmt::Display for UnknownValue {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "unknown value: {}", self.0)
+    }
+}
+
+/// The CPU difficulties, saved as player names like `CPU - Easy`.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum CpuLevel {
+    Easy,
+    Medium,
+    Hard,
+}
+
+impl CpuLevel {
+    pub const ALL: [CpuLevel; 3] = [CpuLevel::Easy, CpuLevel::Medium, CpuLevel::Hard];
+
+    /// The level picked in the game UI, where 1 is easy and 3 is hard.
+    pub fn from_level(level: usize) -> Option<CpuLevel> {
+        match level {
+            1 => Some(CpuLevel::Easy),
+            2 => Some(CpuLevel::Medium),
+            3 => Some(CpuLevel::Hard),
+            _ => None,
+        }
+    }
+
+    pub fn player_name(&self) -> &'static str {
+        match self {
+            CpuLevel::Easy => "CPU - Easy",
+            CpuLevel::Medium => "CPU - Medium",
+            CpuLevel::Hard => "CPU - Hard",
+        }
+    }
+
+    pub fn from_player_name(name: &str) -> Option<CpuLevel> {
+        CpuLevel::ALL
+            .into_iter()
+            .find(|level| level.player_name() == name)
+    }
+
+    /// How many plies ahead the CPU looks at this level.
+    pub fn search_depth(&self) -> i32 {
+        match self {
+            CpuLevel::Easy => 2,
+            CpuLevel::Medium => 4,
+            CpuLevel::Hard => 6,
+        }
+    }
+}
+
+/// Which period of history a leaderboard covers.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub enum TimeWindow {
+    #[default]
+    AllTime,
+    ThisMonth,
+    ThisWeek,
+}
+
+impl TimeWindow {
+    pub const ALL: [TimeWindow; 3] = [
+        TimeWindow::AllTime,
+        TimeWindow::ThisMonth,
+        TimeWindow::ThisWeek,
+    ];
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            TimeWindow::AllTime => "all",
+            TimeWindow::ThisMonth => "month",
+            TimeWindow::ThisWeek => "week",
+        }
+    }
+
+    /// The first day in the window ending `today`, or `None` for all time.
+    /// Weeks start on Monday.
+    pub fn start(&self, today: NaiveDate) -> Option<NaiveDate> {
+        match self {
+            TimeWindow::AllTime => None,
+            TimeWindow::ThisMonth => today.with_day(1),
+            TimeWindow::ThisWeek => {
+                Some(today - Duration::days(today.weekday().num_days_from_monday().into()))
+            }
+        }
+    }
+}
+
+impl FromStr for TimeWindow {
+    type Err = UnknownValue;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        TimeWindow::ALL
+            .into_iter()
+            .find(|window| window.as_str() == s)
+            .ok_or_else(|| UnknownValue(s.to_string()))
+    }
+}
+
+/// Who the games on a leaderboard were played against.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Opponent {
+    Human,
+    Cpu(CpuLevel),
+}
+
+impl Opponent {
+    pub const ALL: [Opponent; 4] = [
+        Opponent::Human,
+        Opponent::Cpu(CpuLevel::Easy),
+        Opponent::Cpu(CpuLevel::Medium),
+        Opponent::Cpu(CpuLevel::Hard),
+    ];
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Opponent::Human => "human",
+            Opponent::Cpu(CpuLevel::Easy) => "easy",
+            Opponent::Cpu(CpuLevel::Medium) => "medium",
+            Opponent::Cpu(CpuLevel::Hard) => "hard",
+        }
+    }
+}
+
+impl FromStr for Opponent {
+    type Err = UnknownValue;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Opponent::ALL
+            .into_iter()
+            .find(|opponent| opponent.as_str() == s)
+            .ok_or_else(|| UnknownValue(s.to_string()))
+    }
+}
+
+/// Who makes the first move in a game against the CPU, as picked before it starts.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum FirstMove {
+    #[default]
+    User,
+    Cpu,
+    Random,
+}
+
+impl FirstMove {
+    pub const ALL: [FirstMove; 3] = [FirstMove::User, FirstMove::Cpu, FirstMove::Random];
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            FirstMove::User => "user",
+            FirstMove::Cpu => "cpu",
+            FirstMove::Random => "random",
+        }
+    }
+
+    pub fn label(&self) -> &'static str {
+        match self {
+            FirstMove::User => "I go first",
+            FirstMove::Cpu => "CPU goes first",
+            FirstMove::Random => "Random",
+        }
+    }
+
+    /// Whether the user moves first. `Random` is drawn from the game's `rng`, right after
+    /// the user's word in Toot & Otto, so a replay from the seed draws it the same way.
+    pub fn user_first<R: rand::Rng>(&self, rng: &mut R) -> bool {
+        match self {
+            FirstMove::User => true,
+            FirstMove::Cpu => false,
+            FirstMove::Random => rng.gen_bool(0.5),
+        }
+    }
+}
+
+impl FromStr for FirstMove {
+    type Err = UnknownValue;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        FirstMove::ALL
+            .into_iter()
+            .find(|first| first.as_str() == s)
+            .ok_or_else(|| UnknownValue(s.to_string()))
+    }
+}
+
+/// A column a leaderboard can be sorted by.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub enum LeaderboardSort {
+    /// Most wins, with fewer losses breaking ties.
+    #[default]
+    Wins,
+    Losses,
+    Games,
+    WinPercentage,
+    CurrentStreak,
+    LongestStreak,
+    AverageMoves,
+    LastPlayed,
+    Username,
+}
+
+impl LeaderboardSort {
+    pub const ALL: [LeaderboardSort; 9] = [
+        LeaderboardSort::Wins,
+        LeaderboardSort::Losses,
+        LeaderboardSort::Games,
+        LeaderboardSort::WinPercentage,
+        LeaderboardSort::CurrentStreak,
+        LeaderboardSort::LongestStreak,
+        LeaderboardSort::AverageMoves,
+        LeaderboardSort::LastPlayed,
+        LeaderboardSort::Username,
+    ];
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            LeaderboardSort::Wins => "wins",
+            LeaderboardSort::Losses => "losses",
+            LeaderboardSort::Games => "games",
+            LeaderboardSort::WinPercentage => "win_percentage",
+            LeaderboardSort::CurrentStreak => "current_streak",
+            LeaderboardSort::LongestStreak => "longest_streak",
+            LeaderboardSort::AverageMoves => "average_moves",
+            LeaderboardSort::LastPlayed => "last_played",
+            LeaderboardSort::Username => "username",
+        }
+    }
+}
+
+impl FromStr for LeaderboardSort {
+    type Err = UnknownValue;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        LeaderboardSort::ALL
+            .into_iter()
+            .find(|sort| sort.as_str() == s)
+            .ok_or_else(|| UnknownValue(s.to_string()))
+    }
+}
+
+/// Query parameters accepted by the leaderboard routes.
+#[derive(Debug, Clone, PartialEq)]
+pub struct LeaderboardQuery {
+    /// 1-based page number.
+    pub page: u32,
+    pub limit: u32,
+    pub window: TimeWindow,
+    /// Only count games played on a `rows` x `cols` board.
+    pub board: Option<(u32, u32)>,
+    pub opponent: Option<Opponent>,
+    /// Leave out players with fewer games than this.
+    pub min_games: u32,
+    pub sort: LeaderboardSort,
+    /// Sort smallest first instead of largest first.
+    pub ascending: bool,
+}
+
+impl Default for LeaderboardQuery {
+    fn default() -> Self {
+        LeaderboardQuery {
+            page: 1,
+            limit: 20,
+            window: TimeWindow::AllTime,
+            board: None,
+            opponent: None,
+            min_games: 0,
+            sort: LeaderboardSort::Wins,
+            ascending: false,
+        }
+    }
+}
+
+impl LeaderboardQuery {
+    /// The query as `(name, value)` pairs, leaving out anything left at its default.
+    pub fn to_pairs(&self) -> Vec<(&'static str, String)> {
+        let mut pairs = vec![
+            ("page", self.page.to_string()),
+            ("limit", self.limit.to_string()),
+        ];
+
+        if self.window != TimeWindow::AllTime {
+            pairs.push(("window", self.window.as_str().to_string()));
+        }
+        if let Some((rows, cols)) = self.board {
+            pairs.push(("board", format!("{}x{}", rows, cols)));
+        }
+        if let Some(opponent) = self.opponent {
+            pairs.push(("opponent", opponent.as_str().to_string()));
+        }
+        if self.min_games > 0 {
+            pairs.push(("min_games", self.min_games.to_string()));
+        }
+        if self.sort != LeaderboardSort::Wins {
+            pairs.push(("sort", self.sort.as_str().to_string()));
+        }
+        if self.ascending {
+            pairs.push(("order", "asc".to_string()));
+        }
+
+        pairs
+    }
+}
+
+/// Parses a board size written as `6x7` (rows by columns).
+pub fn parse_board(board: &str) -> Option<(u32, u32)> {
+    let (rows, cols) = board.split_once('x')?;
+    Some((rows.trim().parse().ok()?, cols.trim().parse().ok()?))
+}
+
+/// How a game ended for the player a game listing is filtered by.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum GameResult {
+    Win,
+    Loss,
+}
+
+impl GameResult {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            GameResult::Win => "win",
+            GameResult::Loss => "loss",
+        }
+    }
+}
+
+impl FromStr for GameResult {
+    type Err = UnknownValue;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        [GameResult::Win, GameResult::Loss]
+            .into_iter()
+            .find(|result| result.as_str() == s)
+            .ok_or_else(|| UnknownValue(s.to_string()))
+    }
+}
+
+/// Which games `/games/all` returns, and which page of them.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct GameFilter {
+    /// Games this player took part in.
+    pub player: Option<String>,
+    /// Games `player` played against this opponent. Requires `player`.
+    pub opponent: Option<String>,
+    pub game_type: Option<GameType>,
+    /// Games `player` won or lost. Requires `player`.
+    pub result: Option<GameResult>,
+    /// First day to include.
+    pub from: Option<NaiveDate>,
+    /// Last day to include.
+    pub to: Option<NaiveDate>,
+    /// Only games played on a `rows` x `cols` board.
+    pub board: Option<(u32, u32)>,
+    /// The `next_cursor` of the previous page.
+    pub cursor: Option<String>,
+    /// Page size, or the server's default when `None`.
+    pub limit: Option<u32>,
+    /// Oldest games first instead of newest first.
+    pub ascending: bool,
+}
+
+impl GameFilter {
+    /// The filter as `(name, value)` pairs, leaving out anything that isn't set.
+    pub fn to_pairs(&self) -> Vec<(&'static str, String)> {
+        let mut pairs = vec![];
+
+        if let Some(player) = &self.player {
+            pairs.push(("player", player.clone()));
+        }
+        if let Some(opponent) = &self.opponent {
+            pairs.push(("opponent", opponent.clone()));
+        }
+        if let Some(game_type) = &self.game_type {
+            pairs.push(("game_type", game_type.slug().to_string()));
+        }
+        if let Some(result) = self.result {
+            pairs.push(("result", result.as_str().to_string()));
+        }
+        if let Some(from) = self.from {
+            pairs.push(("from", from.to_string()));
+        }
+        if let Some(to) = self.to {
+            pairs.push(("to", to.to_string()));
+        }
+        if let Some((rows, cols)) = self.board {
+            pairs.push(("board", format!("{}x{}", rows, cols)));
+        }
+        if let Some(cursor) = &self.cursor {
+            pairs.push(("cursor", cursor.clone()));
+        }
+        if let Some(limit) = self.limit {
+            pairs.push(("limit", limit.to_string()));
+        }
+        if self.ascending {
+            pairs.push(("order", "asc".to_string()));
+        }
+
+        pairs
+    }
+}
+
+/// One page of `/games/all`.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct GamePage {
+    pub games: Vec<GameRecord>,
+    /// Pass back as `cursor` to get the next page; `None` on the last page.
+    pub next_cursor: Option<String>,
 }
@@ -1,11 +1,17 @@
+use client::{Role, Session};
 use yew::prelude::*;
 use yew_router::prelude::*;
 
+mod api;
 mod components;
+use crate::api::Api;
+use crate::components::account::Account;
+use crate::components::admin::Admin;
 use crate::components::connect4::{Connect4, TootOtto};
 use crate::components::leaderboard::LeaderBoard;
 use crate::components::login_form::LoginForm;
 use crate::components::navbar::Navbar;
+use crate::components::profile::Profile;
 use crate::components::register_form::RegisterForm;
 
 #[derive(Routable, PartialEq, Eq, Clone, Debug)]
@@ -16,6 +22,12 @@ pub enum MainRoute {
     TootOtto,
     #[at("/leaderboard")]
     Leaderboard,
+    #[at("/profile/:username")]
+    Profile { username: String },
+    #[at("/account")]
+    Account,
+    #[at("/admin")]
+    Admin,
     #[at("/logout")]
     Logout,
     #[not_found]
@@ -36,26 +48,27 @@ pub enum LoginRoute {
 
 #[function_component()]
 fn App() -> Html {
-    let username = use_state(|| Option::<String>::None);
+    let session = use_state(|| Option::<Session>::None);
 
-    let user_clone = username.clone();
-    let update_username = Callback::from(move |new_user: Option<String>| user_clone.set(new_user));
+    let session_clone = session.clone();
+    let update_session =
+        Callback::from(move |new_session: Option<Session>| session_clone.set(new_session));
 
     html! {
         <BrowserRouter>
             {
-                if let Some(username) = &*username {
+                if let Some(session) = &*session {
                     html! {
                         <>
-                            <Navbar/>
-                            <Switch<MainRoute> render={switch_main(username, update_username)} />
+                            <Navbar admin={session.role == Role::Admin}/>
+                            <Switch<MainRoute> render={switch_main(session, update_session)} />
                         </>
                     }
                 } else {
                     html! {
                         <>
                             <Redirect<LoginRoute> to={LoginRoute::Login}/>
-                            <Switch<LoginRoute> render={switch_login(update_username)} />
+                            <Switch<LoginRoute> render={switch_login(update_session)} />
                         </>
                     }
                 }
@@ -65,10 +78,11 @@ fn App() -> Html {
 }
 
 fn switch_main(
-    username: &String,
-    set_user: Callback<Option<String>>,
+    session: &Session,
+    set_session: Callback<Option<Session>>,
 ) -> impl Fn(MainRoute) -> Html {
-    let username = username.clone();
+    let session = session.clone();
+    let username = session.username.clone();
     move |routes: MainRoute| match routes {
         MainRoute::Connect4 => {
             html! { <div class="game-container">
@@ -88,24 +102,49 @@ fn switch_main(
                 </div>
             }
         }
-        MainRoute::NotFound => {
+        MainRoute::Profile { username } => {
+            html! { <div class="game-container">
+                    <Profile key={username.as_str()} username={username.clone()} />
+                </div>
+            }
+        }
+        MainRoute::Account => {
+            html! { <div class="game-container">
+                    <Account session={session.clone()} set_session={set_session.clone()} />
+                </div>
+            }
+        }
+        MainRoute::Admin if session.role == Role::Admin => {
+            html! { <div class="game-container">
+                    <Admin session={session.clone()} />
+                </div>
+            }
+        }
+        MainRoute::Admin | MainRoute::NotFound => {
             html! { "Page not found." }
         }
         MainRoute::Logout => {
-            set_user.emit(None);
+            let api = Api::authenticated(&session);
+            wasm_bindgen_futures::spawn_local(async move {
+                if let Err(err) = api.logout().await {
+                    log::warn!("Couldn't sign out on the server: {}", err);
+                }
+            });
+
+            set_session.emit(None);
 
             html! {<Redirect<LoginRoute> to={LoginRoute::Login}/>}
         }
     }
 }
 
-fn switch_login(on_login: Callback<Option<String>>) -> impl Fn(LoginRoute) -> Html {
+fn switch_login(on_login: Callback<Option<Session>>) -> impl Fn(LoginRoute) -> Html {
     move |routes: LoginRoute| match routes {
         LoginRoute::Login => {
-            html! { <LoginForm set_username={on_login.clone()} /> }
+            html! { <LoginForm set_session={on_login.clone()} /> }
         }
         LoginRoute::Register => {
-            html! { <RegisterForm set_username={on_login.clone()}/> }
+            html! { <RegisterForm set_session={on_login.clone()}/> }
         }
         LoginRoute::NotFound => {
             html! { "Page not found." }
@@ -114,5 +153,8 @@ fn switch_login(on_login: Callback<Option<String>>) -> impl Fn(LoginRoute) -> Ht
 }
 
 fn main() {
-    yew::Renderer::<App>::new().render();
+    wasm_bindgen_futures::spawn_local(async {
+        api::load_config().await;
+        yew::Renderer::<App>::new().render();
+    });
 }
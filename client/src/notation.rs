@@ -0,0 +1,532 @@
+//! A compact way to write down games and positions.
+//!
+//! A game is its type, board size, the side that moved first and then its plies, with
+//! columns numbered from 1 on the left:
+//!
+//! ```text
+//! connect4 6x7 R 4 4 3 5
+//! tootandotto 4x6 TOOT 3T 4O 3O
+//! ```
+//!
+//! Connect 4 plies are just a column, since each side only has one colour. Sides are
+//! written as their colour (`R` or `B`) in Connect 4 and as the word they spell (`TOOT`
+//! or `OTTO`) in Toot & Otto.
+//!
+//! A position is written like a chess FEN: type, size, then the rows from the top
+//! separated by `/`, with runs of empty cells as a count, then the side to move:
+//!
+//! ```text
+//! connect4 6x7 7/7/7/7/3B3/2RR3 B
+//! ```
+
+use std::fmt;
+use std::str::FromStr;
+
+use crate::engine::{self, Board, Side, EMPTY, MAX_SIZE, MIN_SIZE};
+use crate::{parse_board, GameType};
+
+/// Why a game or position couldn't be read, or a ply couldn't be played.
+#[derive(Debug, Clone, PartialEq)]
+pub struct NotationError(pub String);
+
+impl fmt::Display for NotationError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.0)
+    }
+}
+
+fn error<T>(message: impl Into<String>) -> Result<T, NotationError> {
+    Err(NotationError(message.into()))
+}
+
+/// One piece dropped into a column, counted from 0 on the left.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Ply {
+    pub col: usize,
+    pub piece: char,
+}
+
+/// A game from an empty board.
+#[derive(Debug, Clone, PartialEq)]
+pub struct GameNotation {
+    pub game_type: GameType,
+    pub rows: usize,
+    pub cols: usize,
+    /// The side that made the first ply.
+    pub first: Side,
+    pub plies: Vec<Ply>,
+}
+
+impl GameNotation {
+    /// Plays every ply from an empty board, checking each is legal.
+    pub fn replay(&self) -> Result<Position, NotationError> {
+        let mut position = Position::new(self.game_type.clone(), self.rows, self.cols, self.first);
+
+        for (index, ply) in self.plies.iter().enumerate() {
+            position
+                .play(*ply)
+                .map_err(|err| NotationError(format!("ply {}: {}", index + 1, err)))?;
+        }
+
+        Ok(position)
+    }
+}
+
+impl fmt::Display for GameNotation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} {}x{} {}",
+            self.game_type.slug(),
+            self.rows,
+            self.cols,
+            self.first.name(&self.game_type)
+        )?;
+
+        for ply in &self.plies {
+            match self.game_type {
+                GameType::Connect4 => write!(f, " {}", ply.col + 1)?,
+                GameType::TootAndOtto => write!(f, " {}{}", ply.col + 1, ply.piece)?,
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// Reads the type, size and side that start both notations.
+fn parse_header<'a>(
+    tokens: &mut impl Iterator<Item = &'a str>,
+) -> Result<(GameType, usize, usize), NotationError> {
+    let game_type = match tokens.next() {
+        Some(slug) => match GameType::from_slug(slug) {
+            Some(game_type) => game_type,
+            None => return error(format!("unknown game type {:?}", slug)),
+        },
+        None => return error("missing game type"),
+    };
+
+    let (rows, cols) = match tokens.next().map(parse_board) {
+        Some(Some((rows, cols))) => (rows as usize, cols as usize),
+        _ => return error("missing or invalid board size, e.g. 6x7"),
+    };
+
+    if rows < MIN_SIZE || cols < MIN_SIZE {
+        return error(format!("boards are at least {}x{}", MIN_SIZE, MIN_SIZE));
+    }
+
+    if rows > MAX_SIZE || cols > MAX_SIZE {
+        return error(format!("boards are at most {}x{}", MAX_SIZE, MAX_SIZE));
+    }
+
+    Ok((game_type, rows, cols))
+}
+
+fn parse_side(game_type: &GameType, token: Option<&str>) -> Result<Side, NotationError> {
+    match token.and_then(|name| Side::from_name(game_type, name)) {
+        Some(side) => Ok(side),
+        None => error(format!(
+            "missing or invalid side, {} or {}",
+            Side::One.name(game_type),
+            Side::Two.name(game_type)
+        )),
+    }
+}
+
+/// Reads one ply as written in a game, like `4` in Connect 4 or `4T` in Toot & Otto,
+/// for `side` to play. Columns count from 1.
+pub fn parse_ply(game_type: &GameType, side: Side, token: &str) -> Result<Ply, NotationError> {
+    let (number, piece) = match game_type {
+        GameType::Connect4 => (token, side.pieces(game_type)[0]),
+        GameType::TootAndOtto => match token.char_indices().last() {
+            Some((index, piece)) => (&token[..index], piece.to_ascii_uppercase()),
+            None => return error("empty ply"),
+        },
+    };
+
+    let col = match number.parse::<usize>() {
+        Ok(col) if col >= 1 => col - 1,
+        _ => return error(format!("invalid ply {:?}", token)),
+    };
+
+    Ok(Ply { col, piece })
+}
+
+impl FromStr for GameNotation {
+    type Err = NotationError;
+
+    /// Reads a game, and checks it could actually have been played.
+    fn from_str(text: &str) -> Result<Self, Self::Err> {
+        let mut tokens = text.split_whitespace();
+        let (game_type, rows, cols) = parse_header(&mut tokens)?;
+        let first = parse_side(&game_type, tokens.next())?;
+
+        let mut side = first;
+        let mut plies = vec![];
+
+        for token in tokens {
+            plies.push(parse_ply(&game_type, side, token)?);
+            side = side.other();
+        }
+
+        let game = GameNotation {
+            game_type,
+            rows,
+            cols,
+            first,
+            plies,
+        };
+        game.replay()?;
+
+        Ok(game)
+    }
+}
+
+/// A board part way through a game, along with whose turn it is.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Position {
+    pub game_type: GameType,
+    pub board: Board,
+    pub to_move: Side,
+}
+
+impl Position {
+    /// An empty board.
+    pub fn new(game_type: GameType, rows: usize, cols: usize, to_move: Side) -> Position {
+        Position {
+            game_type,
+            board: Board::new(rows, cols),
+            to_move,
+        }
+    }
+
+    /// Whether each side has four in a row, as `(Side::One, Side::Two)`.
+    pub fn wins(&self) -> (bool, bool) {
+        self.board.wins(&self.game_type)
+    }
+
+    /// Whether someone has won or the board is full.
+    pub fn is_over(&self) -> bool {
+        let (one, two) = self.wins();
+        one || two || self.board.is_full()
+    }
+
+    /// Plays `ply` for the side to move and passes the turn, if it's a legal move.
+    pub fn play(&mut self, ply: Ply) -> Result<(), NotationError> {
+        if self.is_over() {
+            return error("the game is already over");
+        }
+
+        if !self.to_move.pieces(&self.game_type).contains(&ply.piece) {
+            return error(format!(
+                "{} can't play {:?}",
+                self.to_move.name(&self.game_type),
+                ply.piece
+            ));
+        }
+
+        if self.board.perform_move(ply.col, ply.piece).is_none() {
+            return error(format!("column {} is full or off the board", ply.col + 1));
+        }
+
+        self.to_move = self.to_move.other();
+
+        Ok(())
+    }
+}
+
+impl fmt::Display for Position {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let ranks: Vec<String> = self
+            .board
+            .cells()
+            .iter()
+            .map(|row| {
+                let mut rank = String::new();
+                let mut empty = 0;
+
+                for &cell in row {
+                    if cell == EMPTY {
+                        empty += 1;
+                        continue;
+                    }
+
+                    if empty > 0 {
+                        rank.push_str(&empty.to_string());
+                        empty = 0;
+                    }
+                    rank.push(cell);
+                }
+
+                if empty > 0 {
+                    rank.push_str(&empty.to_string());
+                }
+
+                rank
+            })
+            .collect();
+
+        write!(
+            f,
+            "{} {}x{} {} {}",
+            self.game_type.slug(),
+            self.board.rows(),
+            self.board.cols(),
+            ranks.join("/"),
+            self.to_move.name(&self.game_type)
+        )
+    }
+}
+
+/// Adds a run of `count` empty cells to a row, as long as the row still fits in `cols`.
+fn push_run(cells: &mut Vec<char>, count: &str, cols: usize) -> Result<(), NotationError> {
+    let run = match count.parse::<usize>() {
+        Ok(run) if run > 0 => run,
+        _ => return error(format!("invalid count of empty cells {:?}", count)),
+    };
+
+    if run > cols - cells.len() {
+        return error(format!("a row has more than {} cells", cols));
+    }
+
+    cells.resize(cells.len() + run, EMPTY);
+    Ok(())
+}
+
+/// Reads one row of a position, expanding counts into empty cells.
+fn parse_rank(rank: &str, game_type: &GameType, cols: usize) -> Result<Vec<char>, NotationError> {
+    let mut cells = vec![];
+    let mut count = String::new();
+
+    for c in rank.chars() {
+        if c.is_ascii_digit() {
+            count.push(c);
+            continue;
+        }
+
+        if !count.is_empty() {
+            push_run(&mut cells, &count, cols)?;
+            count.clear();
+        }
+
+        if !engine::pieces(game_type).contains(&c) {
+            return error(format!(
+                "{:?} isn't a {} piece",
+                c,
+                game_type.display_name()
+            ));
+        }
+
+        if cells.len() == cols {
+            return error(format!("a row has more than {} cells", cols));
+        }
+        cells.push(c);
+    }
+
+    if !count.is_empty() {
+        push_run(&mut cells, &count, cols)?;
+    }
+
+    Ok(cells)
+}
+
+impl FromStr for Position {
+    type Err = NotationError;
+
+    /// Reads a position, and checks it could be reached by playing.
+    fn from_str(text: &str) -> Result<Self, Self::Err> {
+        let mut tokens = text.split_whitespace();
+        let (game_type, rows, cols) = parse_header(&mut tokens)?;
+
+        let ranks: Vec<&str> = match tokens.next() {
+            Some(ranks) => ranks.split('/').collect(),
+            None => return error("missing rows"),
+        };
+
+        if ranks.len() != rows {
+            return error(format!("expected {} rows, found {}", rows, ranks.len()));
+        }
+
+        let mut cells = vec![];
+        for (index, rank) in ranks.iter().enumerate() {
+            let row = parse_rank(rank, &game_type, cols)?;
+
+            if row.len() != cols {
+                return error(format!(
+                    "row {} has {} cells, expected {}",
+                    index + 1,
+                    row.len(),
+                    cols
+                ));
+            }
+            cells.push(row);
+        }
+
+        let to_move = parse_side(&game_type, tokens.next())?;
+
+        if let Some(extra) = tokens.next() {
+            return error(format!("unexpected {:?}", extra));
+        }
+
+        // Pieces fall, so nothing can sit above an empty cell
+        for col in 0..cols {
+            let column: Vec<char> = cells.iter().map(|row| row[col]).collect();
+            let floating = column
+                .windows(2)
+                .any(|pair| pair[0] != EMPTY && pair[1] == EMPTY);
+
+            if floating {
+                return error(format!(
+                    "column {} has a piece above an empty cell",
+                    col + 1
+                ));
+            }
+        }
+
+        // Connect 4 sides take turns with their own colour, so the counts can differ by
+        // at most one, and the side ahead has just moved
+        if game_type == GameType::Connect4 {
+            let count = |side: Side| {
+                let piece = side.pieces(&game_type)[0];
+                cells
+                    .iter()
+                    .flatten()
+                    .filter(|&&cell| cell == piece)
+                    .count()
+            };
+            let (to_move_count, moved_count) = (count(to_move), count(to_move.other()));
+
+            if to_move_count > moved_count || moved_count > to_move_count + 1 {
+                return error("the piece counts don't match the side to move");
+            }
+        }
+
+        Ok(Position {
+            game_type,
+            board: Board::from_cells(cells),
+            to_move,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn connect4_game_round_trip() {
+        let text = "connect4 6x7 R 4 4 3 5 10";
+        assert!(
+            text.parse::<GameNotation>().is_err(),
+            "column 10 is off the board"
+        );
+
+        let text = "connect4 6x7 R 4 4 3 5";
+        let game: GameNotation = text.parse().unwrap();
+
+        assert_eq!(game.first, Side::One);
+        assert_eq!(
+            game.plies,
+            [
+                Ply { col: 3, piece: 'R' },
+                Ply { col: 3, piece: 'B' },
+                Ply { col: 2, piece: 'R' },
+                Ply { col: 4, piece: 'B' },
+            ]
+        );
+        assert_eq!(game.to_string(), text);
+    }
+
+    #[test]
+    fn toot_and_otto_game_round_trip() {
+        let text = "tootandotto 4x6 OTTO 3T 4O 3O";
+        let game: GameNotation = text.parse().unwrap();
+
+        assert_eq!(game.first, Side::Two);
+        assert_eq!(game.plies[1], Ply { col: 3, piece: 'O' });
+        assert_eq!(game.to_string(), text);
+
+        // Letters are read in either case but written in upper case
+        let lower: GameNotation = "tootandotto 4x6 OTTO 3t 4o 3o".parse().unwrap();
+        assert_eq!(lower, game);
+    }
+
+    #[test]
+    fn replay_gives_the_position() {
+        let game: GameNotation = "connect4 6x7 R 4 4 3 5".parse().unwrap();
+        let position = game.replay().unwrap();
+
+        assert_eq!(position.to_move, Side::One);
+        assert_eq!(position.to_string(), "connect4 6x7 7/7/7/7/3B3/2RRB2 R");
+        assert_eq!(position.to_string().parse::<Position>().unwrap(), position);
+    }
+
+    #[test]
+    fn position_round_trip() {
+        for text in [
+            "connect4 6x7 7/7/7/7/7/7 R",
+            "connect4 6x7 7/7/7/7/3B3/2RR3 B",
+            "tootandotto 4x6 6/6/2O3/2TO2 TOOT",
+            "connect4 4x12 12/12/12/R10B B",
+        ] {
+            let position: Position = text.parse().unwrap();
+            assert_eq!(position.to_string(), text);
+        }
+    }
+
+    #[test]
+    fn rejects_impossible_games_and_positions() {
+        for text in [
+            "",
+            "chess 6x7 R",
+            "connect4 3x7 R",
+            "connect4 6x7 TOOT",
+            "connect4 4x4 R 1 1 1 1 1",
+            "tootandotto 4x4 TOOT 1X",
+            // Red has already won
+            "connect4 6x7 R 1 2 1 2 1 2 1 2",
+        ] {
+            assert!(text.parse::<GameNotation>().is_err(), "{:?}", text);
+        }
+
+        for text in [
+            "connect4 6x7 7/7/7/7/7 R",
+            "connect4 6x7 7/7/7/7/7/6 R",
+            "connect4 6x7 7/7/7/7/R6/7 B",
+            "connect4 6x7 7/7/7/7/7/RR5 B",
+            "connect4 6x7 7/7/7/7/7/R6 R",
+            "connect4 6x7 7/7/7/7/7/T6 B",
+            "tootandotto 4x4 4/4/4/R3 TOOT",
+            "connect4 6x7 7/7/7/7/7/7 R extra",
+        ] {
+            assert!(text.parse::<Position>().is_err(), "{:?}", text);
+        }
+    }
+
+    #[test]
+    fn rejects_oversized_boards_and_runs() {
+        assert!("connect4 21x7 R".parse::<GameNotation>().is_err());
+        assert!("connect4 6x4000000000 R".parse::<GameNotation>().is_err());
+        assert!("connect4 20x20 R 1".parse::<GameNotation>().is_ok());
+
+        for text in [
+            "connect4 6x7 7/7/7/7/7/4000000000 R",
+            "connect4 6x7 7/7/7/7/7/99999999999999999999999 R",
+            "connect4 6x7 7/7/7/7/7/0R6 B",
+            "connect4 6x7 7/7/7/7/7/R7 B",
+            "connect4 6x7 7/7/7/7/7/RBRBRBRB R",
+        ] {
+            assert!(text.parse::<Position>().is_err(), "{:?}", text);
+        }
+    }
+
+    #[test]
+    fn play_checks_pieces_and_turns() {
+        let mut position = Position::new(GameType::Connect4, 6, 7, Side::One);
+
+        assert!(position.play(Ply { col: 0, piece: 'B' }).is_err());
+        assert!(position.play(Ply { col: 0, piece: 'R' }).is_ok());
+        assert_eq!(position.to_move, Side::Two);
+        assert!(position.play(Ply { col: 7, piece: 'B' }).is_err());
+    }
+}
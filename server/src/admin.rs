@@ -0,0 +1,189 @@
+use std::collections::HashMap;
+
+use chrono::Utc;
+use client::{AuditEntry, GameRecord, GameType, LeaderboardPage, LeaderboardQuery, UserSummary};
+use rocket::{http::Status, serde::json::Json, State};
+
+use crate::auth::AdminUser;
+use crate::leaderboard;
+use crate::repository::Storage;
+
+/// How many results a listing returns when it isn't given a `limit`.
+const DEFAULT_LIMIT: i64 = 100;
+/// The most results a single listing can return.
+const MAX_LIMIT: i64 = 500;
+
+/// Checks a listing's `limit`, which has to be positive, and caps it at `MAX_LIMIT`.
+fn parse_limit(limit: Option<i64>) -> Result<i64, Status> {
+    match limit {
+        None => Ok(DEFAULT_LIMIT),
+        Some(limit) if limit < 1 => Err(Status::BadRequest),
+        Some(limit) => Ok(limit.min(MAX_LIMIT)),
+    }
+}
+
+/// Records a change an admin made. Reads aren't recorded.
+async fn audit(
+    storage: &Storage,
+    admin: &AdminUser,
+    action: &str,
+    target: &str,
+) -> Result<(), Status> {
+    let entry = AuditEntry {
+        admin: admin.0.username.clone(),
+        action: action.to_string(),
+        target: target.to_string(),
+        date: Utc::now(),
+    };
+
+    storage.users.insert_audit_entry(entry).await
+}
+
+#[get("/games?<player>&<game_type>&<limit>")]
+pub async fn search_games(
+    storage: &State<Storage>,
+    _admin: AdminUser,
+    player: Option<&str>,
+    game_type: Option<&str>,
+    limit: Option<i64>,
+) -> Result<Json<Vec<GameRecord>>, Status> {
+    let limit = parse_limit(limit)?;
+
+    let game_type = match game_type.filter(|game_type| !game_type.is_empty()) {
+        Some(slug) => Some(GameType::from_slug(slug).ok_or(Status::BadRequest)?),
+        None => None,
+    };
+
+    let games = storage
+        .games
+        .search_games(
+            player.filter(|player| !player.is_empty()),
+            game_type.as_ref(),
+            limit,
+        )
+        .await?;
+
+    Ok(Json(games))
+}
+
+#[delete("/games/<id>")]
+pub async fn delete_game(
+    storage: &State<Storage>,
+    admin: AdminUser,
+    id: &str,
+) -> Result<(), Status> {
+    let deleted = storage
+        .games
+        .delete_game(id)
+        .await?
+        .ok_or(Status::NotFound)?;
+
+    leaderboard::forget_games(storage, &[deleted]).await?;
+
+    audit(storage, &admin, "delete_game", id).await
+}
+
+#[get("/users?<search>")]
+pub async fn search_users(
+    storage: &State<Storage>,
+    _admin: AdminUser,
+    search: Option<&str>,
+) -> Result<Json<Vec<UserSummary>>, Status> {
+    let summaries = storage
+        .users
+        .search_users(search.filter(|search| !search.is_empty()), DEFAULT_LIMIT)
+        .await?
+        .into_iter()
+        .map(|user| UserSummary {
+            username: user.username,
+            role: user.role,
+            guest: user.guest,
+            banned: user.banned,
+        })
+        .collect();
+
+    Ok(Json(summaries))
+}
+
+async fn set_banned(
+    storage: &Storage,
+    admin: &AdminUser,
+    username: &str,
+    banned: bool,
+) -> Result<(), Status> {
+    if username == admin.0.username {
+        return Err(Status::BadRequest);
+    }
+
+    if !storage.users.set_banned(username, banned).await? {
+        return Err(Status::NotFound);
+    }
+
+    if banned {
+        storage.users.delete_sessions(username, None).await?;
+    }
+
+    leaderboard::update_visibility(storage, username).await?;
+
+    audit(
+        storage,
+        admin,
+        if banned { "ban" } else { "unban" },
+        username,
+    )
+    .await
+}
+
+#[post("/users/<username>/ban")]
+pub async fn ban_user(
+    storage: &State<Storage>,
+    admin: AdminUser,
+    username: &str,
+) -> Result<(), Status> {
+    set_banned(storage, &admin, username, true).await
+}
+
+#[post("/users/<username>/unban")]
+pub async fn unban_user(
+    storage: &State<Storage>,
+    admin: AdminUser,
+    username: &str,
+) -> Result<(), Status> {
+    set_banned(storage, &admin, username, false).await
+}
+
+/// Rebuilds the materialized leaderboard stats from the games collection.
+#[post("/leaderboard/recompute")]
+pub async fn recompute_leaderboards(
+    storage: &State<Storage>,
+    admin: AdminUser,
+) -> Result<Json<HashMap<String, LeaderboardPage>>, Status> {
+    leaderboard::rebuild(storage).await?;
+
+    let mut leaderboards = HashMap::new();
+
+    for game_type in GameType::ALL {
+        let leaderboard = leaderboard::fetch_leaderboard(
+            storage,
+            game_type.clone(),
+            &LeaderboardQuery::default(),
+        )
+        .await?;
+        leaderboards.insert(game_type.slug().to_string(), leaderboard);
+    }
+
+    audit(storage, &admin, "recompute_leaderboards", "").await?;
+
+    Ok(Json(leaderboards))
+}
+
+#[get("/audit?<limit>")]
+pub async fn audit_log(
+    storage: &State<Storage>,
+    _admin: AdminUser,
+    limit: Option<i64>,
+) -> Result<Json<Vec<AuditEntry>>, Status> {
+    let limit = parse_limit(limit)?;
+
+    Ok(Json(storage.users.audit_log(limit).await?))
+}
@@ -0,0 +1,117 @@
+use argon2::{hash_encoded, verify_encoded};
+use chrono::{Duration, Utc};
+use client::{Role, Session};
+use rand::{distributions::Alphanumeric, Rng};
+use rocket::{
+    http::Status,
+    request::{FromRequest, Outcome},
+    Request, State,
+};
+
+use crate::repository::Storage;
+
+const SALT: &[u8] = b"supercalifragilisticexpialidocious";
+
+pub fn hash_password(password: &str) -> Result<String, Status> {
+    hash_encoded(password.as_bytes(), SALT, &argon2::Config::default())
+        .map_err(|_| Status::InternalServerError)
+}
+
+pub fn verify_password(hash: &str, password: &str) -> Result<bool, Status> {
+    verify_encoded(hash, password.as_bytes()).map_err(|_| Status::InternalServerError)
+}
+
+pub fn random_string(len: usize) -> String {
+    rand::thread_rng()
+        .sample_iter(&Alphanumeric)
+        .take(len)
+        .map(char::from)
+        .collect()
+}
+
+/// How long a session stays signed in before its user has to log in again.
+pub const SESSION_LIFETIME_DAYS: i64 = 30;
+
+pub async fn create_session(
+    storage: &Storage,
+    username: &str,
+    guest: bool,
+    role: Role,
+) -> Result<Session, Status> {
+    let session = Session {
+        username: username.to_string(),
+        token: random_string(32),
+        guest,
+        role,
+    };
+
+    let expires_at = Utc::now() + Duration::days(SESSION_LIFETIME_DAYS);
+    storage.users.insert_session(&session, expires_at).await?;
+
+    Ok(session)
+}
+
+/// The user behind the `Authorization: Bearer <token>` header of a request.
+pub struct AuthUser {
+    pub username: String,
+    pub token: String,
+    pub guest: bool,
+    pub role: Role,
+}
+
+#[rocket::async_trait]
+impl<'r> FromRequest<'r> for AuthUser {
+    type Error = ();
+
+    async fn from_request(request: &'r Request<'_>) -> Outcome<Self, Self::Error> {
+        let token = match request
+            .headers()
+            .get_one("Authorization")
+            .and_then(|header| header.strip_prefix("Bearer "))
+        {
+            Some(token) => token,
+            None => return Outcome::Error((Status::Unauthorized, ())),
+        };
+
+        let storage = match request.guard::<&State<Storage>>().await {
+            Outcome::Success(storage) => storage,
+            _ => return Outcome::Error((Status::InternalServerError, ())),
+        };
+
+        let session = match storage.users.find_session(token).await {
+            Ok(Some(session)) => session,
+            Ok(None) => return Outcome::Error((Status::Unauthorized, ())),
+            Err(_) => return Outcome::Error((Status::InternalServerError, ())),
+        };
+
+        // Roles and bans are read from the account so they apply to existing sessions
+        match storage.users.find_user(&session.username).await {
+            Ok(Some(user)) if user.banned => Outcome::Error((Status::Forbidden, ())),
+            Ok(Some(user)) => Outcome::Success(AuthUser {
+                username: session.username,
+                token: session.token,
+                guest: user.guest,
+                role: user.role,
+            }),
+            Ok(None) => Outcome::Error((Status::Unauthorized, ())),
+            Err(_) => Outcome::Error((Status::InternalServerError, ())),
+        }
+    }
+}
+
+/// A logged in user with the `Admin` role.
+pub struct AdminUser(pub AuthUser);
+
+#[rocket::async_trait]
+impl<'r> FromRequest<'r> for AdminUser {
+    type Error = ();
+
+    async fn from_request(request: &'r Request<'_>) -> Outcome<Self, Self::Error> {
+        match request.guard::<AuthUser>().await {
+            Outcome::Success(user) if user.role == Role::Admin => Outcome::Success(AdminUser(user)),
+            Outcome::Success(_) => Outcome::Error((Status::Forbidden, ())),
+            Outcome::Error(failure) => Outcome::Error(failure),
+            Outcome::Forward(forward) => Outcome::Forward(forward),
+        }
+    }
+}
@@ -0,0 +1,881 @@
+use std::fmt;
+use std::fs::{self, File};
+use std::io::{self, Write};
+use std::path::PathBuf;
+
+use chrono::{Duration, Utc};
+use clap::{Args, Parser, Subcommand, ValueEnum};
+use client::ai;
+use client::notation::{GameNotation, NotationError};
+use client::{CpuLevel, GameRecord, GameRetention, GameType, Role, UserSummary};
+use mongodb::{bson, Client, Database};
+use rocket::{
+    http::Status,
+    serde::{json::serde_json, Serialize},
+};
+
+use crate::auth::hash_password;
+use crate::games::GameFilterParams;
+use crate::leaderboard::{self, LeaderboardParams};
+use crate::migrations::{self, USERS_SCHEMA_VERSION};
+use crate::play::{self, PlayArgs};
+use crate::repository::{self, Storage};
+use crate::tournament::{MatchupResult, Rate, TournamentArgs};
+use crate::transfer::{self, ImportReport};
+use crate::users::{self, UserDocument};
+
+/// Runs the Connect 4 server, or administers its database.
+#[derive(Parser)]
+#[command(name = "server")]
+pub struct Cli {
+    /// MongoDB connection string. Defaults to `databases.mongodb_main.url` in Rocket.toml.
+    #[arg(long, env = "CONNECT4_DB_URI", global = true)]
+    db_uri: Option<String>,
+
+    /// How to print results.
+    #[arg(long, value_enum, default_value_t = Format::Table, global = true)]
+    format: Format,
+
+    #[command(subcommand)]
+    pub command: Option<Command>,
+}
+
+#[derive(Subcommand)]
+pub enum Command {
+    /// Start the server. This is what runs when no command is given.
+    Serve,
+    /// Manage accounts.
+    #[command(subcommand)]
+    Users(UsersCommand),
+    /// Manage saved games.
+    #[command(subcommand)]
+    Games(GamesCommand),
+    /// Read and maintain leaderboard stats.
+    #[command(subcommand)]
+    Leaderboard(LeaderboardCommand),
+    /// Maintain the database itself.
+    #[command(subcommand)]
+    Db(DbCommand),
+    /// Play the CPU in the terminal.
+    Play(PlayArgs),
+    /// Play two engines against each other and report how the first one did.
+    Tournament(TournamentArgs),
+}
+
+#[derive(Subcommand)]
+pub enum UsersCommand {
+    /// List accounts in alphabetical order.
+    List {
+        /// Only list usernames containing this, ignoring case.
+        #[arg(long)]
+        search: Option<String>,
+        #[arg(long, default_value_t = 100)]
+        limit: u32,
+    },
+    /// Create an account.
+    Create {
+        username: String,
+        /// Read from stdin when not given.
+        #[arg(long)]
+        password: Option<String>,
+        #[arg(long)]
+        admin: bool,
+    },
+    /// Delete an account, anonymizing its games unless told to delete them too.
+    Delete {
+        username: String,
+        #[arg(long)]
+        purge_games: bool,
+    },
+    /// Make an account an admin, or a player again.
+    SetRole {
+        username: String,
+        #[arg(value_enum)]
+        role: RoleArg,
+    },
+    /// Check a password the way `/users/login` does, without opening a session.
+    Login {
+        username: String,
+        /// Read from stdin when not given.
+        #[arg(long)]
+        password: Option<String>,
+    },
+    /// Set a new password and sign the account out everywhere.
+    ResetPassword {
+        username: String,
+        /// Read from stdin when not given.
+        #[arg(long)]
+        password: Option<String>,
+    },
+    /// Delete guests that never registered, anonymizing their games.
+    ExpireGuests {
+        /// Only guests created more than this many days ago.
+        #[arg(long, default_value_t = 30)]
+        days: u32,
+    },
+    /// Create the users in a JSON Lines file, skipping invalid lines and taken usernames.
+    ///
+    /// Users without a `password_hash` get a random password, to be reset before they can
+    /// sign in. Everyone is imported as a player unless `--keep-roles` is given.
+    Import {
+        file: PathBuf,
+        /// Keep the roles in the file, admins included.
+        #[arg(long)]
+        keep_roles: bool,
+    },
+    /// Write every user as JSON Lines, in alphabetical order.
+    Export {
+        /// Where to write the users. Defaults to stdout.
+        #[arg(long, short)]
+        output: Option<PathBuf>,
+        /// Include password hashes, so the users can sign in wherever they are imported.
+        #[arg(long)]
+        with_passwords: bool,
+    },
+}
+
+#[derive(ValueEnum, Clone, Copy)]
+pub enum RoleArg {
+    Player,
+    Admin,
+}
+
+impl From<RoleArg> for Role {
+    fn from(role: RoleArg) -> Self {
+        match role {
+            RoleArg::Player => Role::Player,
+            RoleArg::Admin => Role::Admin,
+        }
+    }
+}
+
+#[derive(Subcommand)]
+pub enum GamesCommand {
+    /// List games, newest first, with the same filters as `/games/all`.
+    List(GameListArgs),
+    /// Save the games in a JSON Lines file, skipping invalid lines and games already saved.
+    Import { file: PathBuf },
+    /// Write every game as JSON Lines, oldest first.
+    Export {
+        /// Where to write the games. Defaults to stdout.
+        #[arg(long, short)]
+        output: Option<PathBuf>,
+    },
+    /// Delete one game by its id.
+    Delete { id: String },
+    /// Play a game against the CPU again from its seed, checking the CPU makes the same
+    /// moves, and print how it ended.
+    Replay { id: String },
+}
+
+#[derive(Args)]
+pub struct GameListArgs {
+    #[arg(long)]
+    player: Option<String>,
+    /// Needs `--player`.
+    #[arg(long)]
+    opponent: Option<String>,
+    /// `connect4` or `tootandotto`.
+    #[arg(long)]
+    game_type: Option<String>,
+    /// `win` or `loss`, from the player's side. Needs `--player`.
+    #[arg(long)]
+    result: Option<String>,
+    /// As `YYYY-MM-DD`, inclusive.
+    #[arg(long)]
+    from: Option<String>,
+    /// As `YYYY-MM-DD`, inclusive.
+    #[arg(long)]
+    to: Option<String>,
+    /// For example `6x7`.
+    #[arg(long)]
+    board: Option<String>,
+    /// The `next_cursor` of the previous page.
+    #[arg(long)]
+    cursor: Option<String>,
+    #[arg(long)]
+    limit: Option<u32>,
+    /// `desc` or `asc`.
+    #[arg(long)]
+    order: Option<String>,
+}
+
+#[derive(Subcommand)]
+pub enum LeaderboardCommand {
+    /// Print one page of a leaderboard, with the same options as `/leaderboard/<game_type>`.
+    Show(LeaderboardArgs),
+    /// Recompute every player's stats from the saved games.
+    Rebuild,
+}
+
+#[derive(Args)]
+pub struct LeaderboardArgs {
+    /// `connect4` or `tootandotto`.
+    game_type: String,
+    #[arg(long)]
+    page: Option<u32>,
+    #[arg(long)]
+    limit: Option<u32>,
+    /// `all`, `month` or `week`.
+    #[arg(long)]
+    window: Option<String>,
+    /// For example `6x7`.
+    #[arg(long)]
+    board: Option<String>,
+    /// `human`, `easy`, `medium` or `hard`.
+    #[arg(long)]
+    opponent: Option<String>,
+    #[arg(long)]
+    min_games: Option<u32>,
+    #[arg(long)]
+    sort: Option<String>,
+    /// `desc` or `asc`.
+    #[arg(long)]
+    order: Option<String>,
+}
+
+#[derive(Subcommand)]
+pub enum DbCommand {
+    /// Create the indexes queries rely on.
+    Indexes,
+    /// Apply pending migrations.
+    Migrate {
+        /// Count what each pending migration would change without changing it.
+        #[arg(long)]
+        dry_run: bool,
+    },
+}
+
+#[derive(ValueEnum, Clone, Copy, PartialEq)]
+enum Format {
+    /// Aligned columns, for reading.
+    Table,
+    /// JSON, for scripts.
+    Json,
+}
+
+/// Why a command failed, printed before exiting with a failure status.
+pub struct CliError(String);
+
+impl fmt::Display for CliError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.0)
+    }
+}
+
+impl From<Status> for CliError {
+    fn from(status: Status) -> Self {
+        CliError(status.to_string())
+    }
+}
+
+impl From<io::Error> for CliError {
+    fn from(err: io::Error) -> Self {
+        CliError(err.to_string())
+    }
+}
+
+impl From<mongodb::error::Error> for CliError {
+    fn from(err: mongodb::error::Error) -> Self {
+        CliError(err.to_string())
+    }
+}
+
+pub(crate) fn fail<T>(message: impl Into<String>) -> Result<T, CliError> {
+    Err(CliError(message.into()))
+}
+
+/// Prints `items` as a table with one row each, or as a JSON array.
+fn print_list<T: Serialize>(
+    format: Format,
+    items: &[T],
+    headers: &[&str],
+    row: impl Fn(&T) -> Vec<String>,
+) -> Result<(), CliError> {
+    if format == Format::Json {
+        return print_json(items);
+    }
+
+    let rows: Vec<Vec<String>> = items.iter().map(row).collect();
+    let mut widths: Vec<usize> = headers.iter().map(|header| header.len()).collect();
+
+    for row in &rows {
+        for (width, cell) in widths.iter_mut().zip(row) {
+            *width = (*width).max(cell.chars().count());
+        }
+    }
+
+    let headers: Vec<String> = headers.iter().map(|header| header.to_string()).collect();
+
+    for row in std::iter::once(&headers).chain(&rows) {
+        let line: Vec<String> = row
+            .iter()
+            .zip(&widths)
+            .map(|(cell, width)| format!("{:width$}", cell, width = width))
+            .collect();
+
+        println!("{}", line.join("  ").trim_end());
+    }
+
+    Ok(())
+}
+
+fn print_json<T: Serialize + ?Sized>(value: &T) -> Result<(), CliError> {
+    let json = serde_json::to_string_pretty(value).map_err(|err| CliError(err.to_string()))?;
+    println!("{}", json);
+
+    Ok(())
+}
+
+/// Writes `items` as JSON Lines to `output`, or to stdout.
+fn write_lines<T: Serialize>(output: Option<PathBuf>, items: &[T]) -> Result<(), CliError> {
+    let mut out: Box<dyn Write> = match output {
+        Some(path) => Box::new(File::create(path)?),
+        None => Box::new(io::stdout().lock()),
+    };
+
+    for item in items {
+        let line = serde_json::to_string(item).map_err(|err| CliError(err.to_string()))?;
+        writeln!(out, "{}", line)?;
+    }
+
+    out.flush()?;
+
+    Ok(())
+}
+
+/// Prints what an import did, failing if any line was skipped as invalid.
+fn print_import_report(format: Format, report: &ImportReport, what: &str) -> Result<(), CliError> {
+    if format == Format::Json {
+        print_json(report)?;
+    } else {
+        for error in &report.errors {
+            println!("Line {}: {}", error.line, error.message);
+        }
+
+        println!(
+            "Imported {} {}, skipped {} duplicates",
+            report.imported, what, report.duplicates
+        );
+    }
+
+    if !report.errors.is_empty() {
+        return fail(format!(
+            "{} lines could not be imported",
+            report.errors.len()
+        ));
+    }
+
+    Ok(())
+}
+
+/// The password given on the command line, or else a line read from stdin.
+fn password_or_prompt(password: Option<String>) -> Result<String, CliError> {
+    let password = match password {
+        Some(password) => password,
+        None => {
+            eprint!("Password: ");
+            io::stderr().flush()?;
+
+            let mut input = String::new();
+            io::stdin().read_line(&mut input)?;
+            input.trim_end_matches(['\r', '\n']).to_string()
+        }
+    };
+
+    if password.is_empty() {
+        return fail("The password can't be empty");
+    }
+
+    Ok(password)
+}
+
+fn user_row(user: &UserSummary) -> Vec<String> {
+    vec![
+        user.username.clone(),
+        format!("{:?}", user.role),
+        user.guest.to_string(),
+        user.banned.to_string(),
+    ]
+}
+
+fn game_row(record: &GameRecord) -> Vec<String> {
+    let game = &record.game;
+
+    vec![
+        record.id.clone(),
+        game.game_type.slug().to_string(),
+        game.player1.clone(),
+        game.player2.clone(),
+        game.winner.clone(),
+        game.ended_at.format("%Y-%m-%d %H:%M").to_string(),
+        match (game.rows, game.cols) {
+            (Some(rows), Some(cols)) => format!("{}x{}", rows, cols),
+            _ => String::new(),
+        },
+    ]
+}
+
+async fn database(db_uri: Option<String>) -> Result<Database, CliError> {
+    let url = match db_uri {
+        Some(url) => url,
+        None => rocket::Config::figment()
+            .extract_inner::<String>("databases.mongodb_main.url")
+            .or_else(|_| fail("No database given; pass --db-uri or set CONNECT4_DB_URI"))?,
+    };
+
+    let client = Client::with_uri_str(&url).await?;
+
+    Ok(client.database("mongodb_main"))
+}
+
+/// Runs every command but `serve`.
+pub async fn run(cli: Cli) -> Result<(), CliError> {
+    let format = cli.format;
+
+    // The commands that don't need the database
+    let command = match cli.command {
+        Some(Command::Play(args)) => return play::run(args),
+        Some(Command::Tournament(args)) => return run_tournament(format, args),
+        command => command,
+    };
+
+    let db = database(cli.db_uri).await?;
+    let storage = Storage::mongo(&db);
+
+    // Like the server, bring the database up to date before touching it. `db` commands
+    // are how that is done by hand, so they're left to it.
+    if !matches!(command, Some(Command::Db(_))) {
+        repository::prepare(&db).await?;
+    }
+
+    match command {
+        None | Some(Command::Serve) => unreachable!("the server is launched by main"),
+        Some(Command::Users(command)) => run_users(&storage, format, command).await,
+        Some(Command::Games(command)) => run_games(&storage, format, command).await,
+        Some(Command::Leaderboard(command)) => run_leaderboard(&storage, format, command).await,
+        Some(Command::Db(command)) => run_db(&db, format, command).await,
+        Some(Command::Play(_) | Command::Tournament(_)) => {
+            unreachable!("run before connecting")
+        }
+    }
+}
+
+async fn run_users(
+    storage: &Storage,
+    format: Format,
+    command: UsersCommand,
+) -> Result<(), CliError> {
+    match command {
+        UsersCommand::List { search, limit } => {
+            let users: Vec<UserSummary> = storage
+                .users
+                .search_users(
+                    search.as_deref().filter(|search| !search.is_empty()),
+                    i64::from(limit),
+                )
+                .await?
+                .into_iter()
+                .map(|user| UserSummary {
+                    username: user.username,
+                    role: user.role,
+                    guest: user.guest,
+                    banned: user.banned,
+                })
+                .collect();
+
+            print_list(
+                format,
+                &users,
+                &["USERNAME", "ROLE", "GUEST", "BANNED"],
+                user_row,
+            )
+        }
+        UsersCommand::Create {
+            username,
+            password,
+            admin,
+        } => {
+            if users::is_reserved_for_accounts(&username) {
+                return fail(format!("{:?} can't be used as a username", username));
+            }
+
+            if storage.users.find_user(&username).await?.is_some() {
+                return fail(format!("There is already a user named {}", username));
+            }
+
+            let password = password_or_prompt(password)?;
+
+            storage
+                .users
+                .insert_user(UserDocument {
+                    schema_version: USERS_SCHEMA_VERSION,
+                    username: username.clone(),
+                    password: hash_password(&password)?,
+                    guest: false,
+                    role: if admin { Role::Admin } else { Role::Player },
+                    banned: false,
+                    created_at: Some(bson::DateTime::now()),
+                })
+                .await?;
+
+            eprintln!("Created {}", username);
+            Ok(())
+        }
+        UsersCommand::Delete {
+            username,
+            purge_games,
+        } => {
+            if storage.users.find_user(&username).await?.is_none() {
+                return fail(format!("There is no user named {}", username));
+            }
+
+            let retention = if purge_games {
+                GameRetention::Purge
+            } else {
+                GameRetention::Anonymize
+            };
+
+            users::delete_user(storage, &username, retention).await?;
+
+            eprintln!("Deleted {}", username);
+            Ok(())
+        }
+        UsersCommand::SetRole { username, role } => {
+            if !storage.users.set_role(&username, role.into()).await? {
+                return fail(format!("There is no user named {}", username));
+            }
+
+            eprintln!("Updated the role of {}", username);
+            Ok(())
+        }
+        UsersCommand::Login { username, password } => {
+            let password = password_or_prompt(password)?;
+
+            let user = match users::authenticate(storage, &username, &password).await {
+                Ok(user) => user,
+                Err(status) => {
+                    let reason = match status.code {
+                        404 => "there is no such user",
+                        401 => "wrong password, or a guest account",
+                        403 => "the account is banned",
+                        _ => "the check itself failed",
+                    };
+
+                    return fail(format!("Login would fail with {}: {}", status, reason));
+                }
+            };
+
+            print_list(
+                format,
+                &[UserSummary {
+                    username: user.username,
+                    role: user.role,
+                    guest: user.guest,
+                    banned: user.banned,
+                }],
+                &["USERNAME", "ROLE", "GUEST", "BANNED"],
+                user_row,
+            )
+        }
+        UsersCommand::ResetPassword { username, password } => {
+            if storage.users.find_user(&username).await?.is_none() {
+                return fail(format!("There is no user named {}", username));
+            }
+
+            let password = password_or_prompt(password)?;
+
+            storage
+                .users
+                .set_password(&username, &hash_password(&password)?)
+                .await?;
+            storage.users.delete_sessions(&username, None).await?;
+
+            eprintln!("Reset the password of {}", username);
+            Ok(())
+        }
+        UsersCommand::ExpireGuests { days } => {
+            let before = Utc::now() - Duration::days(days.into());
+            let expired = users::expire_guests(storage, before).await?;
+
+            eprintln!("Deleted {} guests", expired.len());
+            Ok(())
+        }
+        UsersCommand::Import { file, keep_roles } => {
+            let text = fs::read_to_string(file)?;
+            let report = transfer::import_users(storage, &text, keep_roles).await?;
+
+            print_import_report(format, &report, "users")
+        }
+        UsersCommand::Export {
+            output,
+            with_passwords,
+        } => {
+            let users = transfer::export_users(storage, with_passwords).await?;
+            write_lines(output, &users)?;
+
+            eprintln!("Exported {} users", users.len());
+            Ok(())
+        }
+    }
+}
+
+async fn run_games(
+    storage: &Storage,
+    format: Format,
+    command: GamesCommand,
+) -> Result<(), CliError> {
+    match command {
+        GamesCommand::List(args) => {
+            let params = GameFilterParams {
+                player: args.player.as_deref(),
+                opponent: args.opponent.as_deref(),
+                game_type: args.game_type.as_deref(),
+                result: args.result.as_deref(),
+                from: args.from.as_deref(),
+                to: args.to.as_deref(),
+                board: args.board.as_deref(),
+                cursor: args.cursor.as_deref(),
+                limit: args.limit,
+                order: args.order.as_deref(),
+            };
+
+            let filter = params
+                .parse()
+                .or_else(|_| fail("Invalid filter; see `games list --help`"))?;
+            let page = storage.games.list_games(&filter).await?;
+
+            if format == Format::Json {
+                return print_json(&page);
+            }
+
+            print_list(
+                format,
+                &page.games,
+                &[
+                    "ID", "TYPE", "PLAYER 1", "PLAYER 2", "WINNER", "ENDED", "BOARD",
+                ],
+                game_row,
+            )?;
+
+            if let Some(cursor) = page.next_cursor {
+                eprintln!("More games: --cursor {}", cursor);
+            }
+
+            Ok(())
+        }
+        GamesCommand::Import { file } => {
+            let report = transfer::import_games(storage, &fs::read_to_string(file)?).await?;
+
+            print_import_report(format, &report, "games")
+        }
+        GamesCommand::Export { output } => {
+            let games = transfer::export_games(storage).await?;
+            write_lines(output, &games)?;
+
+            eprintln!("Exported {} games", games.len());
+            Ok(())
+        }
+        GamesCommand::Delete { id } => {
+            let deleted = match storage.games.delete_game(&id).await? {
+                Some(deleted) => deleted,
+                None => return fail(format!("There is no game with the id {}", id)),
+            };
+
+            leaderboard::forget_games(storage, &[deleted]).await?;
+
+            eprintln!("Deleted game {}", id);
+            Ok(())
+        }
+        GamesCommand::Replay { id } => {
+            let game = match storage.games.find_game(&id).await? {
+                Some(game) => game,
+                None => return fail(format!("There is no game with the id {}", id)),
+            };
+
+            let level = match CpuLevel::from_player_name(&game.player2)
+                .or_else(|| CpuLevel::from_player_name(&game.player1))
+            {
+                Some(level) => level,
+                None => return fail("Only games against the CPU can be replayed"),
+            };
+
+            let (seed, notation) = match (game.seed, &game.notation) {
+                (Some(seed), Some(notation)) => (seed, notation),
+                _ => return fail("The game was saved without its seed and moves"),
+            };
+
+            let notation: GameNotation = notation
+                .parse()
+                .map_err(|err: NotationError| CliError(err.to_string()))?;
+            let first_move = game.first_move.unwrap_or_default();
+            let position = ai::replay(&notation, level, first_move, seed)
+                .map_err(|err| CliError(format!("The replay went differently: {}", err)))?;
+
+            play::print_board(&position.board);
+            println!("{}", notation);
+            println!(
+                "Replayed {} plies from seed {}, with the CPU choosing the same moves.",
+                notation.plies.len(),
+                seed
+            );
+            Ok(())
+        }
+    }
+}
+
+async fn run_leaderboard(
+    storage: &Storage,
+    format: Format,
+    command: LeaderboardCommand,
+) -> Result<(), CliError> {
+    match command {
+        LeaderboardCommand::Show(args) => {
+            let game_type = match GameType::from_slug(&args.game_type) {
+                Some(game_type) => game_type,
+                None => return fail(format!("Unknown game type {}", args.game_type)),
+            };
+
+            let params = LeaderboardParams {
+                page: args.page,
+                limit: args.limit,
+                window: args.window.as_deref(),
+                board: args.board.as_deref(),
+                opponent: args.opponent.as_deref(),
+                min_games: args.min_games,
+                sort: args.sort.as_deref(),
+                order: args.order.as_deref(),
+            };
+
+            let query = params
+                .parse()
+                .or_else(|_| fail("Invalid options; see `leaderboard show --help`"))?;
+            let page = leaderboard::fetch_leaderboard(storage, game_type, &query).await?;
+
+            if format == Format::Json {
+                return print_json(&page);
+            }
+
+            print_list(
+                format,
+                &page.entries,
+                &["RANK", "USERNAME", "WINS", "LOSSES", "GAMES", "WIN %"],
+                |entry| {
+                    vec![
+                        entry.rank.to_string(),
+                        entry.username.clone(),
+                        entry.wins.to_string(),
+                        entry.losses.to_string(),
+                        entry.games.to_string(),
+                        format!("{:.1}", entry.win_percentage),
+                    ]
+                },
+            )?;
+
+            eprintln!(
+                "Page {} of {}, {} players",
+                page.page,
+                page.pages(),
+                page.total
+            );
+            Ok(())
+        }
+        LeaderboardCommand::Rebuild => {
+            leaderboard::rebuild(storage).await?;
+
+            eprintln!("Leaderboard stats rebuilt");
+            Ok(())
+        }
+    }
+}
+
+async fn run_db(db: &Database, format: Format, command: DbCommand) -> Result<(), CliError> {
+    match command {
+        DbCommand::Indexes => {
+            repository::create_indexes(db).await?;
+
+            eprintln!("Indexes created");
+            Ok(())
+        }
+        DbCommand::Migrate { dry_run } => {
+            let reports = migrations::run(db, dry_run).await?;
+
+            print_list(
+                format,
+                &reports,
+                &["MIGRATION", "DOCUMENTS", "STATUS"],
+                |report| {
+                    vec![
+                        report.name.to_string(),
+                        report.documents.to_string(),
+                        if report.already_applied {
+                            "already applied"
+                        } else if dry_run {
+                            "pending"
+                        } else {
+                            "applied"
+                        }
+                        .to_string(),
+                    ]
+                },
+            )
+        }
+    }
+}
+
+/// A rate and its confidence interval as percentages.
+fn percent(rate: &Rate) -> String {
+    format!(
+        "{:.1} ({:.1}-{:.1})",
+        rate.rate * 100.0,
+        rate.low * 100.0,
+        rate.high * 100.0
+    )
+}
+
+fn matchup_row(result: &MatchupResult) -> Vec<String> {
+    vec![
+        result.game_type.clone(),
+        result.board.clone(),
+        result.games.to_string(),
+        result.wins.to_string(),
+        result.draws.to_string(),
+        result.losses.to_string(),
+        percent(&result.win_rate),
+        percent(&result.draw_rate),
+        percent(&result.loss_rate),
+        format!("{:.2}", result.engine_ms_per_move),
+        format!("{:.2}", result.against_ms_per_move),
+    ]
+}
+
+fn run_tournament(format: Format, args: TournamentArgs) -> Result<(), CliError> {
+    let tournament = args.parse()?;
+    let results = tournament.run();
+
+    if format == Format::Table {
+        println!(
+            "{} against {}, {} games per matchup from seed {}. Rates are percentages with 95% confidence intervals.",
+            tournament.engine, tournament.against, tournament.games, tournament.seed
+        );
+    }
+
+    print_list(
+        format,
+        &results,
+        &[
+            "GAME",
+            "BOARD",
+            "GAMES",
+            "WINS",
+            "DRAWS",
+            "LOSSES",
+            "WIN %",
+            "DRAW %",
+            "LOSS %",
+            "MS/MOVE",
+            "AGAINST MS/MOVE",
+        ],
+        matchup_row,
+    )
+}
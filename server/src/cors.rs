@@ -0,0 +1,164 @@
+use rocket::{
+    fairing::{self, Fairing, Info, Kind},
+    http::{Header, Method, Status},
+    serde::Deserialize,
+    Build, Request, Response, Rocket,
+};
+
+/// The `[cors]` table of `Rocket.toml`, also settable through `ROCKET_CORS`.
+#[derive(Deserialize, Debug, Clone, PartialEq)]
+#[serde(crate = "rocket::serde")]
+pub struct CorsConfig {
+    /// Origins allowed to call the API, or `"*"` for any origin.
+    #[serde(default)]
+    pub allowed_origins: Vec<String>,
+    #[serde(default = "default_methods")]
+    pub allowed_methods: Vec<String>,
+    #[serde(default = "default_headers")]
+    pub allowed_headers: Vec<String>,
+}
+
+fn default_methods() -> Vec<String> {
+    ["GET", "POST", "PUT", "DELETE", "OPTIONS"]
+        .map(String::from)
+        .to_vec()
+}
+
+fn default_headers() -> Vec<String> {
+    ["Authorization", "Content-Type"].map(String::from).to_vec()
+}
+
+impl Default for CorsConfig {
+    fn default() -> Self {
+        CorsConfig {
+            allowed_origins: vec![],
+            allowed_methods: default_methods(),
+            allowed_headers: default_headers(),
+        }
+    }
+}
+
+impl CorsConfig {
+    fn allows_any_origin(&self) -> bool {
+        self.allowed_origins.iter().any(|allowed| allowed == "*")
+    }
+
+    fn allows_origin(&self, origin: &str) -> bool {
+        self.allowed_origins
+            .iter()
+            .any(|allowed| allowed == "*" || allowed == origin)
+    }
+
+    fn allows_method(&self, method: &str) -> bool {
+        self.allowed_methods
+            .iter()
+            .any(|allowed| allowed.eq_ignore_ascii_case(method))
+    }
+
+    /// Checks every header in a comma separated `Access-Control-Request-Headers` value.
+    fn allows_headers(&self, headers: &str) -> bool {
+        headers
+            .split(',')
+            .map(str::trim)
+            .filter(|header| !header.is_empty())
+            .all(|header| {
+                self.allowed_headers
+                    .iter()
+                    .any(|allowed| allowed.eq_ignore_ascii_case(header))
+            })
+    }
+}
+
+/// Answers any `OPTIONS` request so the fairing can turn it into a preflight response.
+#[options("/<_..>")]
+pub async fn options() -> Result<(), Status> {
+    Ok(())
+}
+
+pub struct Cors;
+
+#[rocket::async_trait]
+impl Fairing for Cors {
+    fn info(&self) -> Info {
+        Info {
+            name: "Add CORS headers to responses",
+            kind: Kind::Ignite | Kind::Response,
+        }
+    }
+
+    async fn on_ignite(&self, rocket: Rocket<Build>) -> fairing::Result {
+        let config = match rocket.figment().find_value("cors") {
+            Ok(_) => match rocket.figment().extract_inner::<CorsConfig>("cors") {
+                Ok(config) => config,
+                Err(err) => {
+                    error!("Invalid CORS configuration: {}", err);
+                    return Err(rocket);
+                }
+            },
+            Err(_) => CorsConfig::default(),
+        };
+
+        Ok(rocket.manage(config))
+    }
+
+    async fn on_response<'r>(&self, request: &'r Request<'_>, response: &mut Response<'r>) {
+        let config = match request.rocket().state::<CorsConfig>() {
+            Some(config) => config,
+            None => return,
+        };
+
+        // Caches have to keep responses to different origins apart, refusals included
+        response.set_header(Header::new("Vary", "Origin"));
+
+        let preflight = request.method() == Method::Options;
+
+        let origin = match request.headers().get_one("Origin") {
+            Some(origin) if config.allows_origin(origin) => origin,
+            Some(_) => {
+                if preflight {
+                    response.set_status(Status::Forbidden);
+                }
+                return;
+            }
+            None => return,
+        };
+
+        if preflight {
+            let method = request
+                .headers()
+                .get_one("Access-Control-Request-Method")
+                .unwrap_or_default();
+            let headers = request
+                .headers()
+                .get_one("Access-Control-Request-Headers")
+                .unwrap_or_default();
+
+            if !config.allows_method(method) || !config.allows_headers(headers) {
+                response.set_status(Status::Forbidden);
+                return;
+            }
+
+            response.set_status(Status::NoContent);
+            response.set_header(Header::new(
+                "Access-Control-Allow-Methods",
+                config.allowed_methods.join(", "),
+            ));
+            response.set_header(Header::new(
+                "Access-Control-Allow-Headers",
+                config.allowed_headers.join(", "),
+            ));
+            response.set_header(Header::new("Access-Control-Max-Age", "86400"));
+        }
+
+        // Browsers refuse credentials alongside a wildcard, so any origin gets neither
+        if config.allows_any_origin() {
+            response.set_header(Header::new("Access-Control-Allow-Origin", "*"));
+        } else {
+            response.set_header(Header::new(
+                "Access-Control-Allow-Origin",
+                origin.to_string(),
+            ));
+            response.set_header(Header::new("Access-Control-Allow-Credentials", "true"));
+        }
+    }
+}
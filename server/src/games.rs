@@ -0,0 +1,83 @@
+use chrono::NaiveDate;
+use client::{parse_board, ConnectGame, GameFilter, GameType};
+use rocket::http::Status;
+
+use crate::leaderboard;
+use crate::repository::Storage;
+
+/// Page size when a game listing doesn't ask for one.
+pub const DEFAULT_LIMIT: u32 = 50;
+/// The most games a single page can hold.
+const MAX_LIMIT: u32 = 200;
+
+/// Saves a finished game and counts it towards its players' stats.
+pub async fn save_game(storage: &Storage, game: &ConnectGame) -> Result<(), Status> {
+    storage.games.insert_game(game).await?;
+
+    leaderboard::record_game(storage, game).await
+}
+
+/// The query string of `/games/all`, before validation.
+#[derive(FromForm)]
+pub struct GameFilterParams<'r> {
+    pub player: Option<&'r str>,
+    pub opponent: Option<&'r str>,
+    pub game_type: Option<&'r str>,
+    pub result: Option<&'r str>,
+    pub from: Option<&'r str>,
+    pub to: Option<&'r str>,
+    pub board: Option<&'r str>,
+    pub cursor: Option<&'r str>,
+    pub limit: Option<u32>,
+    pub order: Option<&'r str>,
+}
+
+fn parse_date(date: &str) -> Result<NaiveDate, Status> {
+    NaiveDate::parse_from_str(date, "%Y-%m-%d").map_err(|_| Status::BadRequest)
+}
+
+/// Treats a blank query parameter the same as a missing one.
+fn non_empty(value: Option<&str>) -> Option<&str> {
+    value.filter(|value| !value.is_empty())
+}
+
+impl GameFilterParams<'_> {
+    pub fn parse(&self) -> Result<GameFilter, Status> {
+        let filter = GameFilter {
+            player: non_empty(self.player).map(str::to_string),
+            opponent: non_empty(self.opponent).map(str::to_string),
+            game_type: match non_empty(self.game_type) {
+                Some(game_type) => Some(GameType::from_slug(game_type).ok_or(Status::BadRequest)?),
+                None => None,
+            },
+            result: match non_empty(self.result) {
+                Some(result) => Some(result.parse().map_err(|_| Status::BadRequest)?),
+                None => None,
+            },
+            from: non_empty(self.from).map(parse_date).transpose()?,
+            to: match non_empty(self.to).map(parse_date).transpose()? {
+                // Ranges end at the start of the next day, so there has to be one
+                Some(to) if to.succ_opt().is_none() => return Err(Status::BadRequest),
+                to => to,
+            },
+            board: match non_empty(self.board) {
+                Some(board) => Some(parse_board(board).ok_or(Status::BadRequest)?),
+                None => None,
+            },
+            cursor: non_empty(self.cursor).map(str::to_string),
+            limit: Some(self.limit.unwrap_or(DEFAULT_LIMIT).clamp(1, MAX_LIMIT)),
+            ascending: match non_empty(self.order) {
+                Some("asc") => true,
+                Some("desc") | None => false,
+                Some(_) => return Err(Status::BadRequest),
+            },
+        };
+
+        // Opponents and results are relative to the player
+        if filter.player.is_none() && (filter.opponent.is_some() || filter.result.is_some()) {
+            return Err(Status::BadRequest);
+        }
+
+        Ok(filter)
+    }
+}
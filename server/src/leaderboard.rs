@@ -0,0 +1,454 @@
+use std::cmp::Ordering;
+use std::collections::{HashMap, HashSet};
+
+use chrono::Utc;
+use client::{
+    parse_board, ConnectGame, CpuLevel, CpuRecord, GameRecord, GameType, Leaderboard,
+    LeaderboardPage, LeaderboardQuery, LeaderboardSort, TimeWindow, TurnRecord,
+};
+use rocket::{
+    http::Status,
+    serde::{Deserialize, Serialize},
+};
+
+use crate::repository::{GameSelection, Storage, UserRepository};
+use crate::users::is_reserved_username;
+
+/// The most entries a single leaderboard page can hold.
+const MAX_LIMIT: u32 = 100;
+
+/// A document in the `leaderboard` collection: one player's record in one game type.
+///
+/// These are kept up to date as games are created, renamed and deleted, so reading
+/// a leaderboard never has to scan `games`. `rebuild` recomputes them from scratch.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(crate = "rocket::serde")]
+pub struct PlayerStats {
+    pub username: String,
+    pub game_type: GameType,
+    pub wins: u32,
+    pub losses: u32,
+    #[serde(default)]
+    pub current_streak: u32,
+    #[serde(default)]
+    pub longest_streak: u32,
+    #[serde(default)]
+    pub cpu_records: Vec<CpuRecord>,
+    /// Sum of `ConnectGame.moves` over the games that recorded it.
+    #[serde(default)]
+    pub total_moves: u64,
+    #[serde(default)]
+    pub games_with_moves: u32,
+    /// As `YYYY-MM-DD`, so it sorts the same as a string and as a date.
+    #[serde(default)]
+    pub last_played: Option<String>,
+    /// Over the games that recorded `ConnectGame.player1_first`.
+    #[serde(default)]
+    pub moving_first: TurnRecord,
+    #[serde(default)]
+    pub moving_second: TurnRecord,
+    /// Kept off leaderboards, for the reasons `is_hidden` gives.
+    #[serde(default)]
+    pub hidden: bool,
+    /// Bumped by every `GameRepository::update_stats`, so an update can tell another one
+    /// got in first.
+    #[serde(default)]
+    pub revision: u32,
+}
+
+impl PlayerStats {
+    fn new(username: &str, game_type: &GameType) -> Self {
+        PlayerStats {
+            username: username.to_string(),
+            game_type: game_type.clone(),
+            wins: 0,
+            losses: 0,
+            current_streak: 0,
+            longest_streak: 0,
+            cpu_records: vec![],
+            total_moves: 0,
+            games_with_moves: 0,
+            last_played: None,
+            moving_first: TurnRecord::default(),
+            moving_second: TurnRecord::default(),
+            hidden: is_reserved_username(username),
+            revision: 0,
+        }
+    }
+
+    /// Adds a game this player took part in. Games must be applied in the order they were played.
+    fn apply(&mut self, game: &ConnectGame) {
+        let (winner, loser) = outcome(game);
+        let won = winner == self.username;
+
+        if won {
+            self.wins += 1;
+            self.current_streak += 1;
+            self.longest_streak = self.longest_streak.max(self.current_streak);
+        } else {
+            self.losses += 1;
+            self.current_streak = 0;
+        }
+
+        let opponent = if won { loser } else { winner };
+        if let Some(level) = CpuLevel::from_player_name(opponent) {
+            if !self.cpu_records.iter().any(|record| record.level == level) {
+                self.cpu_records.push(CpuRecord {
+                    level,
+                    wins: 0,
+                    losses: 0,
+                });
+                self.cpu_records.sort_by_key(|record| record.level);
+            }
+
+            let record = self
+                .cpu_records
+                .iter_mut()
+                .find(|record| record.level == level)
+                .unwrap();
+
+            if won {
+                record.wins += 1;
+            } else {
+                record.losses += 1;
+            }
+        }
+
+        if let Some(moves) = game.moves {
+            self.total_moves += u64::from(moves);
+            self.games_with_moves += 1;
+        }
+
+        if let Some(player1_first) = game.player1_first {
+            let record = if player1_first == (game.player1 == self.username) {
+                &mut self.moving_first
+            } else {
+                &mut self.moving_second
+            };
+
+            if won {
+                record.wins += 1;
+            } else {
+                record.losses += 1;
+            }
+        }
+
+        let date = game.ended_at.date_naive().to_string();
+        self.last_played = self.last_played.clone().max(Some(date));
+    }
+
+    fn games(&self) -> u32 {
+        self.wins + self.losses
+    }
+
+    pub fn to_leaderboard(&self) -> Leaderboard {
+        let games = self.games();
+
+        Leaderboard {
+            rank: 0,
+            username: self.username.clone(),
+            wins: self.wins,
+            losses: self.losses,
+            games,
+            win_percentage: if games == 0 {
+                0.0
+            } else {
+                f64::from(self.wins) * 100.0 / f64::from(games)
+            },
+            current_streak: self.current_streak,
+            longest_streak: self.longest_streak,
+            cpu_records: self.cpu_records.clone(),
+            average_moves: (self.games_with_moves > 0)
+                .then(|| self.total_moves as f64 / f64::from(self.games_with_moves)),
+            last_played: self.last_played.clone(),
+            moving_first: self.moving_first,
+            moving_second: self.moving_second,
+        }
+    }
+}
+
+/// The winner and loser of a game, by username.
+pub fn outcome(game: &ConnectGame) -> (&str, &str) {
+    let loser = if game.player1 == game.winner {
+        &game.player2
+    } else {
+        &game.player1
+    };
+
+    (&game.winner, loser)
+}
+
+/// Folds games, oldest first, into per-player stats.
+fn accumulate(games: &[GameRecord]) -> HashMap<(GameType, String), PlayerStats> {
+    let mut totals: HashMap<(GameType, String), PlayerStats> = HashMap::new();
+
+    for GameRecord { game, .. } in games {
+        let (winner, loser) = outcome(game);
+
+        for username in [winner, loser] {
+            totals
+                .entry((game.game_type.clone(), username.to_string()))
+                .or_insert_with(|| PlayerStats::new(username, &game.game_type))
+                .apply(game);
+        }
+    }
+
+    totals
+}
+
+/// Whether `username` is kept off leaderboards. CPU players and deleted users always are,
+/// guests play off the record until they register and claim their games, and banned users
+/// are hidden altogether.
+async fn is_hidden(users: &dyn UserRepository, username: &str) -> Result<bool, Status> {
+    if is_reserved_username(username) {
+        return Ok(true);
+    }
+
+    Ok(users
+        .find_user(username)
+        .await?
+        .is_some_and(|user| user.guest || user.banned))
+}
+
+/// Shows or hides `username`'s stats once their account has been banned or unbanned.
+pub async fn update_visibility(storage: &Storage, username: &str) -> Result<(), Status> {
+    let hidden = is_hidden(storage.users.as_ref(), username).await?;
+
+    storage.games.set_stats_hidden(username, hidden).await
+}
+
+/// Counts a newly created game towards its players' stats.
+pub async fn record_game(storage: &Storage, game: &ConnectGame) -> Result<(), Status> {
+    let games = storage.games.as_ref();
+    let (winner, loser) = outcome(game);
+
+    for username in [winner, loser] {
+        let new = PlayerStats {
+            hidden: is_hidden(storage.users.as_ref(), username).await?,
+            ..PlayerStats::new(username, &game.game_type)
+        };
+
+        games
+            .update_stats(new, &|record: &mut PlayerStats| record.apply(game))
+            .await?;
+    }
+
+    Ok(())
+}
+
+/// Recomputes one player's stats in one game type from their games.
+async fn refresh_player(
+    storage: &Storage,
+    game_type: &GameType,
+    username: &str,
+) -> Result<(), Status> {
+    let games = storage.games.as_ref();
+    let played = games
+        .select_games(&GameSelection {
+            game_type: Some(game_type),
+            player: Some(username),
+            ..GameSelection::default()
+        })
+        .await?;
+
+    match accumulate(&played).remove(&(game_type.clone(), username.to_string())) {
+        Some(record) => {
+            let hidden = is_hidden(storage.users.as_ref(), username).await?;
+            games.save_stats(&PlayerStats { hidden, ..record }).await
+        }
+        None => games.delete_stats(game_type, username).await,
+    }
+}
+
+/// Takes games that have been deleted back out of their players' stats.
+///
+/// Streaks can't be undone one game at a time, so each affected player is recomputed.
+pub async fn forget_games(storage: &Storage, deleted: &[ConnectGame]) -> Result<(), Status> {
+    let mut players = HashSet::new();
+
+    for game in deleted {
+        let (winner, loser) = outcome(game);
+        players.insert((game.game_type.clone(), winner.to_string()));
+        players.insert((game.game_type.clone(), loser.to_string()));
+    }
+
+    for (game_type, username) in players {
+        refresh_player(storage, &game_type, &username).await?;
+    }
+
+    Ok(())
+}
+
+/// Moves `from`'s stats over to `to`, once the games themselves have been renamed.
+pub async fn rename_player(storage: &Storage, from: &str, to: &str) -> Result<(), Status> {
+    for game_type in GameType::ALL {
+        storage.games.delete_stats(&game_type, from).await?;
+        refresh_player(storage, &game_type, to).await?;
+    }
+
+    Ok(())
+}
+
+/// Recomputes every player's stats from the saved games.
+pub async fn rebuild(storage: &Storage) -> Result<(), Status> {
+    let all = storage
+        .games
+        .select_games(&GameSelection::default())
+        .await?;
+    let hidden = hidden_users(storage.users.as_ref()).await?;
+
+    let stats = accumulate(&all)
+        .into_values()
+        .map(|record| PlayerStats {
+            hidden: record.hidden || hidden.contains(&record.username),
+            ..record
+        })
+        .collect();
+
+    storage.games.replace_all_stats(stats).await
+}
+
+/// The query string of a leaderboard route, before validation.
+#[derive(FromForm)]
+pub struct LeaderboardParams<'r> {
+    pub page: Option<u32>,
+    pub limit: Option<u32>,
+    pub window: Option<&'r str>,
+    pub board: Option<&'r str>,
+    pub opponent: Option<&'r str>,
+    pub min_games: Option<u32>,
+    pub sort: Option<&'r str>,
+    pub order: Option<&'r str>,
+}
+
+impl LeaderboardParams<'_> {
+    pub fn parse(&self) -> Result<LeaderboardQuery, Status> {
+        let defaults = LeaderboardQuery::default();
+
+        Ok(LeaderboardQuery {
+            page: self.page.unwrap_or(defaults.page).max(1),
+            limit: self.limit.unwrap_or(defaults.limit).clamp(1, MAX_LIMIT),
+            window: match self.window {
+                Some(window) => window.parse().map_err(|_| Status::BadRequest)?,
+                None => defaults.window,
+            },
+            board: match self.board {
+                Some(board) => Some(parse_board(board).ok_or(Status::BadRequest)?),
+                None => None,
+            },
+            opponent: match self.opponent {
+                Some(opponent) => Some(opponent.parse().map_err(|_| Status::BadRequest)?),
+                None => None,
+            },
+            min_games: self.min_games.unwrap_or(defaults.min_games),
+            sort: match self.sort {
+                Some(sort) => sort.parse().map_err(|_| Status::BadRequest)?,
+                None => defaults.sort,
+            },
+            ascending: match self.order {
+                Some("asc") => true,
+                Some("desc") | None => false,
+                Some(_) => return Err(Status::BadRequest),
+            },
+        })
+    }
+}
+
+/// The guests and banned users, whose stats `is_hidden` keeps off leaderboards.
+async fn hidden_users(users: &dyn UserRepository) -> Result<HashSet<String>, Status> {
+    Ok(users.hidden_users().await?.into_iter().collect())
+}
+
+/// Orders two rows by the column `sort` names, largest first.
+fn compare(a: &Leaderboard, b: &Leaderboard, sort: LeaderboardSort) -> Ordering {
+    match sort {
+        LeaderboardSort::Wins => a.wins.cmp(&b.wins).then(b.losses.cmp(&a.losses)),
+        LeaderboardSort::Losses => a.losses.cmp(&b.losses),
+        LeaderboardSort::Games => a.games.cmp(&b.games),
+        LeaderboardSort::WinPercentage => a.win_percentage.total_cmp(&b.win_percentage),
+        LeaderboardSort::CurrentStreak => a.current_streak.cmp(&b.current_streak),
+        LeaderboardSort::LongestStreak => a.longest_streak.cmp(&b.longest_streak),
+        LeaderboardSort::AverageMoves => a
+            .average_moves
+            .unwrap_or(0.0)
+            .total_cmp(&b.average_moves.unwrap_or(0.0)),
+        LeaderboardSort::LastPlayed => a.last_played.cmp(&b.last_played),
+        // Alphabetical order reads as "smallest first"
+        LeaderboardSort::Username => b.username.cmp(&a.username),
+    }
+}
+
+/// Reads one page of the leaderboard for `game_type`, sorted as the query asks.
+///
+/// Unfiltered leaderboards come straight from the materialized stats, leaving hidden
+/// players out in the query. Filtering by time window, board size or opponent aggregates
+/// the matching games instead, which reads every one of them, along with the list of
+/// guests and banned users, on each request, so those get slower as games pile up.
+pub async fn fetch_leaderboard(
+    storage: &Storage,
+    game_type: GameType,
+    query: &LeaderboardQuery,
+) -> Result<LeaderboardPage, Status> {
+    let filtered =
+        query.window != TimeWindow::AllTime || query.board.is_some() || query.opponent.is_some();
+
+    let records: Vec<PlayerStats> = if filtered {
+        let games = storage
+            .games
+            .select_games(&GameSelection {
+                game_type: Some(&game_type),
+                since: query.window.start(Utc::now().date_naive()),
+                board: query.board,
+                opponent: query.opponent,
+                ..GameSelection::default()
+            })
+            .await?;
+
+        let hidden = hidden_users(storage.users.as_ref()).await?;
+
+        accumulate(&games)
+            .into_values()
+            .filter(|record| !record.hidden && !hidden.contains(&record.username))
+            .collect()
+    } else {
+        storage.games.visible_stats(&game_type).await?
+    };
+
+    let min_games = query.min_games.max(1);
+
+    let mut rows: Vec<Leaderboard> = records
+        .iter()
+        .filter(|record| record.games() >= min_games)
+        .map(PlayerStats::to_leaderboard)
+        .collect();
+
+    rows.sort_by(|a, b| {
+        let order = compare(a, b, query.sort)
+            .then_with(|| compare(a, b, LeaderboardSort::Wins))
+            .then_with(|| compare(a, b, LeaderboardSort::Username));
+
+        if query.ascending {
+            order
+        } else {
+            order.reverse()
+        }
+    });
+
+    let total = rows.len() as u64;
+    let skip = (query.page - 1)
+        .checked_mul(query.limit)
+        .ok_or(Status::BadRequest)? as usize;
+
+    Ok(LeaderboardPage {
+        total,
+        page: query.page,
+        limit: query.limit,
+        entries: rows
+            .into_iter()
+            .zip(1..)
+            .skip(skip)
+            .take(query.limit as usize)
+            .map(|(row, rank)| Leaderboard { rank, ..row })
+            .collect(),
+    })
+}
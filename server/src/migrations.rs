@@ -0,0 +1,243 @@
+use chrono::Utc;
+use client::{CpuLevel, ANONYMOUS_PLAYER};
+use mongodb::{
+    bson::{self, doc, Document},
+    options::UpdateOptions,
+    Database,
+};
+use rocket::{http::Status, serde::Serialize};
+
+use crate::repository::Storage;
+
+/// The `schema_version` that newly written `games` documents carry.
+pub const GAMES_SCHEMA_VERSION: u32 = 1;
+/// The `schema_version` that newly written `users` documents carry.
+pub const USERS_SCHEMA_VERSION: u32 = 1;
+
+/// One change to the shape of the documents in a collection.
+///
+/// `filter` has to select only the documents the migration hasn't been applied to yet,
+/// which is what makes running it again harmless.
+pub(crate) struct Migration {
+    /// Recorded in the `migrations` collection once applied, so it must never change.
+    pub name: &'static str,
+    pub collection: &'static str,
+    pub filter: fn() -> Document,
+    /// An aggregation pipeline, so updates can be computed from the document's own fields.
+    pub update: fn() -> Vec<Document>,
+}
+
+/// Sets `hidden` on leaderboard stats, which takes a second step for guests and banned users.
+const LEADERBOARD_HIDDEN: &str = "0004-leaderboard-hidden";
+
+/// Every migration, in the order they are applied. Only ever append to this.
+pub(crate) const MIGRATIONS: &[Migration] = &[
+    Migration {
+        name: "0001-game-timestamps",
+        collection: "games",
+        filter: || doc! {"date": {"$type": "string"}},
+        update: || {
+            // `date` was written as an unpadded `Y-M-D`, e.g. `2023-4-9`
+            let part = |index: i32| {
+                doc! {"$toInt": {"$arrayElemAt": [{"$split": ["$date", "-"]}, index]}}
+            };
+            let date = doc! {"$dateFromParts": {"year": part(0), "month": part(1), "day": part(2)}};
+
+            vec![
+                doc! {"$set": {"started_at": date.clone(), "ended_at": date}},
+                doc! {"$unset": "date"},
+            ]
+        },
+    },
+    Migration {
+        name: "0002-games-schema-version",
+        collection: "games",
+        filter: || doc! {"schema_version": {"$exists": false}},
+        update: || vec![doc! {"$set": {"schema_version": 1}}],
+    },
+    Migration {
+        name: "0003-users-schema-version",
+        collection: "users",
+        filter: || doc! {"schema_version": {"$exists": false}},
+        update: || vec![doc! {"$set": {"schema_version": 1}}],
+    },
+    Migration {
+        name: LEADERBOARD_HIDDEN,
+        collection: "leaderboard",
+        filter: || doc! {"hidden": {"$exists": false}},
+        update: || {
+            // Guests and banned users can't be told apart from here; `run` hides those after
+            let mut reserved = vec![ANONYMOUS_PLAYER];
+            reserved.extend(CpuLevel::ALL.map(|level| level.player_name()));
+
+            vec![doc! {"$set": {"hidden": {"$in": ["$username", reserved]}}}]
+        },
+    },
+    Migration {
+        name: "0005-audit-log-dates",
+        collection: "audit_log",
+        // Written as RFC 3339 strings before
+        filter: || doc! {"date": {"$type": "string"}},
+        update: || vec![doc! {"$set": {"date": {"$dateFromString": {"dateString": "$date"}}}}],
+    },
+];
+
+/// What running a migration did, or would do on a dry run.
+#[derive(Serialize)]
+#[serde(crate = "rocket::serde")]
+pub struct MigrationReport {
+    pub name: &'static str,
+    /// Documents changed, or on a dry run, the documents that would be.
+    pub documents: u64,
+    /// Set when the migration was recorded as applied by an earlier run and skipped.
+    pub already_applied: bool,
+}
+
+/// Where migrations are applied and recorded, so the runner can be tried out without
+/// a database.
+#[rocket::async_trait]
+pub(crate) trait MigrationTarget: Send + Sync {
+    /// Whether `name` is recorded in the `migrations` collection.
+    async fn is_applied(&self, name: &str) -> Result<bool, Status>;
+
+    async fn record_applied(&self, name: &str, documents: u64) -> Result<(), Status>;
+
+    async fn count(&self, collection: &str, filter: Document) -> Result<u64, Status>;
+
+    /// Returns how many documents changed.
+    async fn update(
+        &self,
+        collection: &str,
+        filter: Document,
+        update: Vec<Document>,
+    ) -> Result<u64, Status>;
+}
+
+#[rocket::async_trait]
+impl MigrationTarget for Database {
+    async fn is_applied(&self, name: &str) -> Result<bool, Status> {
+        Ok(self
+            .collection::<Document>("migrations")
+            .find_one(doc! {"_id": name}, None)
+            .await
+            .map_err(|_| Status::InternalServerError)?
+            .is_some())
+    }
+
+    async fn record_applied(&self, name: &str, documents: u64) -> Result<(), Status> {
+        // Upserted so that two servers starting at once don't trip over each other
+        self.collection::<Document>("migrations")
+            .update_one(
+                doc! {"_id": name},
+                doc! {"$setOnInsert": {
+                    "applied_at": bson::DateTime::from_chrono(Utc::now()),
+                    "documents": documents as i64,
+                }},
+                UpdateOptions::builder().upsert(true).build(),
+            )
+            .await
+            .map_err(|_| Status::InternalServerError)?;
+
+        Ok(())
+    }
+
+    async fn count(&self, collection: &str, filter: Document) -> Result<u64, Status> {
+        self.collection::<Document>(collection)
+            .count_documents(filter, None)
+            .await
+            .map_err(|_| Status::InternalServerError)
+    }
+
+    async fn update(
+        &self,
+        collection: &str,
+        filter: Document,
+        update: Vec<Document>,
+    ) -> Result<u64, Status> {
+        let result = self
+            .collection::<Document>(collection)
+            .update_many(filter, update, None)
+            .await
+            .map_err(|_| Status::InternalServerError)?;
+
+        Ok(result.modified_count)
+    }
+}
+
+/// Applies every migration that hasn't been applied yet, in order.
+///
+/// A dry run only counts the documents each pending migration would change. Since
+/// nothing is written, a migration that depends on an earlier pending one may count
+/// differently than it would once the earlier one has run.
+pub async fn run(db: &Database, dry_run: bool) -> Result<Vec<MigrationReport>, Status> {
+    let reports = apply(db, MIGRATIONS, dry_run).await?;
+
+    let hid_stats = reports
+        .iter()
+        .any(|report| report.name == LEADERBOARD_HIDDEN && !report.already_applied);
+    if hid_stats && !dry_run {
+        hide_guests_and_banned(db).await?;
+    }
+
+    Ok(reports)
+}
+
+/// Finishes what `0004-leaderboard-hidden` started, hiding the stats of the users it
+/// couldn't see were guests or banned.
+async fn hide_guests_and_banned(db: &Database) -> Result<(), Status> {
+    let storage = Storage::mongo(db);
+
+    for username in storage.users.hidden_users().await? {
+        storage.games.set_stats_hidden(&username, true).await?;
+    }
+
+    Ok(())
+}
+
+/// Runs `migrations` against `target` the way `run` runs them against the database.
+pub(crate) async fn apply(
+    target: &dyn MigrationTarget,
+    migrations: &[Migration],
+    dry_run: bool,
+) -> Result<Vec<MigrationReport>, Status> {
+    let mut reports = vec![];
+
+    for migration in migrations {
+        let already_applied = target.is_applied(migration.name).await?;
+
+        if already_applied {
+            reports.push(MigrationReport {
+                name: migration.name,
+                documents: 0,
+                already_applied,
+            });
+            continue;
+        }
+
+        let documents = if dry_run {
+            target
+                .count(migration.collection, (migration.filter)())
+                .await?
+        } else {
+            let documents = target
+                .update(
+                    migration.collection,
+                    (migration.filter)(),
+                    (migration.update)(),
+                )
+                .await?;
+
+            target.record_applied(migration.name, documents).await?;
+
+            documents
+        };
+
+        reports.push(MigrationReport {
+            name: migration.name,
+            documents,
+            already_applied,
+        });
+    }
+
+    Ok(reports)
+}
@@ -0,0 +1,231 @@
+//! Playing the CPU in a terminal, with the same rules and levels as the browser game.
+
+use std::io::{self, BufRead, Write};
+
+use chrono::Utc;
+use clap::Args;
+use client::engine::{Board, Side, EMPTY, MAX_SIZE, MIN_SIZE};
+use client::notation::{self, GameNotation, Ply, Position};
+use client::{ai, ConnectGame, FirstMove, GameType, Opponent};
+
+use crate::cli::{fail, CliError};
+
+#[derive(Args)]
+pub struct PlayArgs {
+    /// `connect4` or `tootandotto`.
+    #[arg(long, default_value = "connect4")]
+    game_type: String,
+    /// `easy`, `medium` or `hard`.
+    #[arg(long, default_value = "easy")]
+    cpu: String,
+    #[arg(long, default_value_t = 7)]
+    rows: usize,
+    #[arg(long, default_value_t = 6)]
+    cols: usize,
+    /// Draws the CPU's choices, and your word in Toot & Otto, from this seed. Picked at
+    /// random when not given. Games played from a chosen seed aren't submitted, as a known
+    /// win could be replayed for the stats.
+    #[arg(long, conflicts_with = "submit")]
+    seed: Option<u32>,
+    /// Who moves first: `user`, `cpu` or `random`.
+    #[arg(long, default_value = "user")]
+    first: String,
+    /// Who you play as, and who the game is saved under.
+    #[arg(long, default_value = "Player")]
+    username: String,
+    /// Save the game to a server once someone wins, as the browser game does.
+    #[arg(long)]
+    submit: bool,
+    /// The server `--submit` saves to.
+    #[arg(long, default_value = "http://127.0.0.1:8000")]
+    server_url: String,
+}
+
+/// Prints the board with column numbers above it and `.` for empty cells.
+pub(crate) fn print_board(board: &Board) {
+    let numbers: Vec<String> = (1..=board.cols())
+        .map(|col| format!("{:>2}", col))
+        .collect();
+    println!();
+    println!("{}", numbers.concat());
+
+    for row in board.cells() {
+        let cells: Vec<String> = row
+            .iter()
+            .map(|&cell| format!("{:>2}", if cell == EMPTY { '.' } else { cell }))
+            .collect();
+        println!("{}", cells.concat());
+    }
+
+    println!();
+}
+
+/// Asks for the user's next ply until one parses, or `None` if they quit.
+fn read_ply(game_type: &GameType, side: Side, cols: usize) -> Result<Option<Ply>, CliError> {
+    let prompt = match game_type {
+        GameType::Connect4 => format!("Your move (1-{}, q to quit): ", cols),
+        GameType::TootAndOtto => format!("Your move (1-{} then T or O, q to quit): ", cols),
+    };
+    let mut lines = io::stdin().lock().lines();
+
+    loop {
+        print!("{}", prompt);
+        io::stdout().flush()?;
+
+        let line = match lines.next() {
+            Some(line) => line?,
+            None => return Ok(None),
+        };
+        let token = line.trim();
+
+        if token.eq_ignore_ascii_case("q") {
+            return Ok(None);
+        }
+
+        match notation::parse_ply(game_type, side, token) {
+            Ok(ply) => return Ok(Some(ply)),
+            Err(err) => println!("{}", err),
+        }
+    }
+}
+
+fn submit(server_url: &str, game: &ConnectGame) -> Result<(), CliError> {
+    let url = format!("{}/games/create", server_url.trim_end_matches('/'));
+
+    match ureq::post(&url).send_json(game) {
+        Ok(_) => Ok(()),
+        Err(err) => fail(format!("Couldn't save the game: {}", err)),
+    }
+}
+
+/// Plays one game against the CPU.
+pub fn run(args: PlayArgs) -> Result<(), CliError> {
+    let game_type = match GameType::from_slug(&args.game_type) {
+        Some(game_type) => game_type,
+        None => return fail(format!("Unknown game type {:?}", args.game_type)),
+    };
+
+    let level = match args.cpu.parse::<Opponent>() {
+        Ok(Opponent::Cpu(level)) => level,
+        _ => return fail(format!("Unknown CPU level {:?}", args.cpu)),
+    };
+
+    let first_move = match args.first.parse::<FirstMove>() {
+        Ok(first_move) => first_move,
+        Err(_) => return fail(format!("Unknown first move {:?}", args.first)),
+    };
+
+    if args.rows < MIN_SIZE || args.cols < MIN_SIZE {
+        return fail(format!(
+            "The board needs at least {} rows and columns",
+            MIN_SIZE
+        ));
+    }
+
+    if args.rows > MAX_SIZE || args.cols > MAX_SIZE {
+        return fail(format!(
+            "The board can have at most {} rows and columns",
+            MAX_SIZE
+        ));
+    }
+
+    let seed = args.seed.unwrap_or_else(rand::random);
+    let mut rng = ai::seeded_rng(seed.into());
+    let user = ai::draw_user_side(&game_type, &mut rng);
+    let cpu = user.other();
+    let user_first = first_move.user_first(&mut rng);
+    let first = if user_first { user } else { cpu };
+
+    println!(
+        "{} against {} with seed {}. You play {} and move {}.",
+        game_type.display_name(),
+        level.player_name(),
+        seed,
+        user.name(&game_type),
+        if user_first { "first" } else { "second" }
+    );
+
+    let started_at = Utc::now();
+    let mut position = Position::new(game_type.clone(), args.rows, args.cols, first);
+    let mut plies = vec![];
+
+    while !position.is_over() {
+        let ply = if position.to_move == user {
+            print_board(&position.board);
+
+            match read_ply(&game_type, user, args.cols)? {
+                Some(ply) => ply,
+                None => return Ok(()),
+            }
+        } else {
+            let (col, piece) = ai::cpu_move(&mut position.board, &game_type, cpu, level, &mut rng);
+
+            match game_type {
+                GameType::Connect4 => println!("{} plays {}", level.player_name(), col + 1),
+                GameType::TootAndOtto => {
+                    println!("{} plays {}{}", level.player_name(), col + 1, piece)
+                }
+            }
+
+            Ply { col, piece }
+        };
+
+        match position.play(ply) {
+            Ok(()) => plies.push(ply),
+            Err(err) => println!("{}", err),
+        }
+    }
+
+    print_board(&position.board);
+
+    let winner = match position.wins() {
+        (true, false) => Some(Side::One),
+        (false, true) => Some(Side::Two),
+        // A full board, or one letter finishing both words
+        _ => None,
+    };
+
+    match winner {
+        Some(side) if side == user => println!("You win!"),
+        Some(_) => println!("{} wins.", level.player_name()),
+        None => println!("It's a draw."),
+    }
+
+    let notation = GameNotation {
+        game_type: game_type.clone(),
+        rows: args.rows,
+        cols: args.cols,
+        first,
+        plies,
+    };
+    println!("{}", notation);
+
+    // Draws aren't saved, in the browser either
+    if let (true, Some(side)) = (args.submit, winner) {
+        let cpu_name = level.player_name().to_string();
+        let game = ConnectGame {
+            game_type,
+            winner: if side == user {
+                args.username.clone()
+            } else {
+                cpu_name.clone()
+            },
+            player1: args.username,
+            player2: cpu_name,
+            started_at,
+            ended_at: Utc::now(),
+            rows: Some(args.rows as u32),
+            cols: Some(args.cols as u32),
+            moves: Some(position.board.piece_count() as u32),
+            seed: Some(seed),
+            notation: Some(notation.to_string()),
+            player1_first: Some(user_first),
+            first_move: Some(first_move),
+        };
+
+        submit(&args.server_url, &game)?;
+        println!("Saved the game to {}", args.server_url);
+    }
+
+    Ok(())
+}
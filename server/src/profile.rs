@@ -0,0 +1,113 @@
+use std::collections::{BTreeMap, HashMap};
+
+use client::{GameRecord, GameTypeStats, HeadToHead, MonthlyRecord, Profile};
+use rocket::{http::Status, serde::json::Json, State};
+
+use crate::leaderboard::outcome;
+use crate::repository::Storage;
+
+/// The most games a single page of a profile can hold.
+const MAX_LIMIT: u32 = 100;
+
+/// `username`'s wins and losses against each opponent in `games`, in no particular order.
+pub(crate) fn head_to_head(games: &[GameRecord], username: &str) -> Vec<HeadToHead> {
+    let mut records: HashMap<String, HeadToHead> = HashMap::new();
+
+    for GameRecord { game, .. } in games {
+        let (winner, loser) = outcome(game);
+        let won = winner == username;
+        let opponent = if won { loser } else { winner };
+
+        let record = records
+            .entry(opponent.to_string())
+            .or_insert_with(|| HeadToHead {
+                opponent: opponent.to_string(),
+                wins: 0,
+                losses: 0,
+            });
+        if won {
+            record.wins += 1;
+        } else {
+            record.losses += 1;
+        }
+    }
+
+    records.into_values().collect()
+}
+
+/// `username`'s wins and losses in each month of `games`, oldest first.
+pub(crate) fn monthly_records(games: &[GameRecord], username: &str) -> Vec<MonthlyRecord> {
+    let mut records: BTreeMap<String, MonthlyRecord> = BTreeMap::new();
+
+    for GameRecord { game, .. } in games {
+        let month = game.ended_at.format("%Y-%m").to_string();
+        let record = records
+            .entry(month.clone())
+            .or_insert_with(|| MonthlyRecord {
+                month,
+                wins: 0,
+                losses: 0,
+            });
+        if game.winner == username {
+            record.wins += 1;
+        } else {
+            record.losses += 1;
+        }
+    }
+
+    records.into_values().collect()
+}
+
+#[get("/<username>/profile?<page>&<limit>")]
+pub async fn profile(
+    storage: &State<Storage>,
+    username: &str,
+    page: Option<u32>,
+    limit: Option<u32>,
+) -> Result<Json<Profile>, Status> {
+    let page = page.unwrap_or(1).max(1);
+    let limit = limit.unwrap_or(10).clamp(1, MAX_LIMIT);
+    let skip = (page - 1).checked_mul(limit).ok_or(Status::BadRequest)?;
+
+    // Guests and banned users are kept off the leaderboard, and off profiles with it
+    match storage.users.find_user(username).await? {
+        Some(user) if !user.banned && !user.guest => {}
+        _ => return Err(Status::NotFound),
+    }
+
+    let mut stats: Vec<GameTypeStats> = storage
+        .games
+        .player_stats(username)
+        .await?
+        .into_iter()
+        .filter(|record| record.wins + record.losses > 0)
+        .map(|record| GameTypeStats {
+            game_type: record.game_type.clone(),
+            stats: record.to_leaderboard(),
+        })
+        .collect();
+    stats.sort_by_key(|stats| stats.game_type.slug());
+
+    let mut head_to_head = storage.games.head_to_head(username).await?;
+    head_to_head.sort_by(|a, b| {
+        (b.wins + b.losses)
+            .cmp(&(a.wins + a.losses))
+            .then_with(|| a.opponent.cmp(&b.opponent))
+    });
+
+    let (recent_games, total_games) = storage
+        .games
+        .player_games(username, u64::from(skip), limit)
+        .await?;
+
+    Ok(Json(Profile {
+        username: username.to_string(),
+        stats,
+        head_to_head,
+        history: storage.games.monthly_records(username).await?,
+        recent_games,
+        total_games,
+        page,
+        limit,
+    }))
+}
@@ -0,0 +1,525 @@
+use std::sync::Mutex;
+
+use chrono::{DateTime, Utc};
+use client::{
+    AuditEntry, ConnectGame, GameFilter, GamePage, GameRecord, GameResult, GameType, HeadToHead,
+    MonthlyRecord, Role, Session,
+};
+use mongodb::bson::{self, oid::ObjectId};
+use rocket::http::Status;
+
+use super::{GameRepository, GameSelection, UserRepository};
+use crate::games::DEFAULT_LIMIT;
+use crate::leaderboard::PlayerStats;
+use crate::profile;
+use crate::users::UserDocument;
+
+fn contains_ignoring_case(text: &str, search: &str) -> bool {
+    text.to_lowercase().contains(&search.to_lowercase())
+}
+
+#[derive(Default)]
+struct Accounts {
+    users: Vec<UserDocument>,
+    /// Along with when each one expires.
+    sessions: Vec<(Session, DateTime<Utc>)>,
+    /// Oldest first.
+    audit_log: Vec<AuditEntry>,
+}
+
+#[derive(Default)]
+pub struct MemoryUsers {
+    accounts: Mutex<Accounts>,
+}
+
+impl MemoryUsers {
+    fn accounts(&self) -> std::sync::MutexGuard<'_, Accounts> {
+        self.accounts
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+
+    /// Applies `update` to the user named `username`, returning whether there was one.
+    fn update_user(&self, username: &str, update: impl FnOnce(&mut UserDocument)) -> bool {
+        match self
+            .accounts()
+            .users
+            .iter_mut()
+            .find(|user| user.username == username)
+        {
+            Some(user) => {
+                update(user);
+                true
+            }
+            None => false,
+        }
+    }
+}
+
+#[rocket::async_trait]
+impl UserRepository for MemoryUsers {
+    async fn find_user(&self, username: &str) -> Result<Option<UserDocument>, Status> {
+        Ok(self
+            .accounts()
+            .users
+            .iter()
+            .find(|user| user.username == username)
+            .cloned())
+    }
+
+    async fn insert_user(&self, user: UserDocument) -> Result<(), Status> {
+        let mut accounts = self.accounts();
+
+        if accounts
+            .users
+            .iter()
+            .any(|existing| existing.username == user.username)
+        {
+            return Err(Status::Conflict);
+        }
+
+        accounts.users.push(user);
+        Ok(())
+    }
+
+    async fn search_users(
+        &self,
+        search: Option<&str>,
+        limit: i64,
+    ) -> Result<Vec<UserDocument>, Status> {
+        let mut users: Vec<UserDocument> = self
+            .accounts()
+            .users
+            .iter()
+            .filter(|user| {
+                search.is_none_or(|search| contains_ignoring_case(&user.username, search))
+            })
+            .cloned()
+            .collect();
+
+        users.sort_by(|a, b| a.username.cmp(&b.username));
+        users.truncate(limit.max(0) as usize);
+
+        Ok(users)
+    }
+
+    async fn hidden_users(&self) -> Result<Vec<String>, Status> {
+        Ok(self
+            .accounts()
+            .users
+            .iter()
+            .filter(|user| user.guest || user.banned)
+            .map(|user| user.username.clone())
+            .collect())
+    }
+
+    async fn guests_created_before(&self, before: DateTime<Utc>) -> Result<Vec<String>, Status> {
+        let before = bson::DateTime::from_chrono(before);
+
+        Ok(self
+            .accounts()
+            .users
+            .iter()
+            .filter(|user| user.guest && user.created_at.is_none_or(|created| created < before))
+            .map(|user| user.username.clone())
+            .collect())
+    }
+
+    async fn set_password(&self, username: &str, password: &str) -> Result<(), Status> {
+        self.update_user(username, |user| user.password = password.to_string());
+        Ok(())
+    }
+
+    async fn set_role(&self, username: &str, role: Role) -> Result<bool, Status> {
+        Ok(self.update_user(username, |user| user.role = role))
+    }
+
+    async fn set_banned(&self, username: &str, banned: bool) -> Result<bool, Status> {
+        Ok(self.update_user(username, |user| user.banned = banned))
+    }
+
+    async fn rename_user(&self, from: &str, to: &str) -> Result<(), Status> {
+        let mut accounts = self.accounts();
+
+        for user in accounts
+            .users
+            .iter_mut()
+            .filter(|user| user.username == from)
+        {
+            user.username = to.to_string();
+        }
+
+        for (session, _) in accounts
+            .sessions
+            .iter_mut()
+            .filter(|(session, _)| session.username == from)
+        {
+            session.username = to.to_string();
+        }
+
+        Ok(())
+    }
+
+    async fn delete_user(&self, username: &str) -> Result<(), Status> {
+        let mut accounts = self.accounts();
+
+        accounts.users.retain(|user| user.username != username);
+        accounts
+            .sessions
+            .retain(|(session, _)| session.username != username);
+
+        Ok(())
+    }
+
+    async fn insert_session(
+        &self,
+        session: &Session,
+        expires_at: DateTime<Utc>,
+    ) -> Result<(), Status> {
+        self.accounts().sessions.push((session.clone(), expires_at));
+        Ok(())
+    }
+
+    async fn find_session(&self, token: &str) -> Result<Option<Session>, Status> {
+        let now = Utc::now();
+
+        Ok(self
+            .accounts()
+            .sessions
+            .iter()
+            .find(|(session, expires_at)| session.token == token && *expires_at > now)
+            .map(|(session, _)| session.clone()))
+    }
+
+    async fn delete_session(&self, token: &str) -> Result<(), Status> {
+        self.accounts()
+            .sessions
+            .retain(|(session, _)| session.token != token);
+
+        Ok(())
+    }
+
+    async fn delete_sessions(&self, username: &str, keep: Option<&str>) -> Result<(), Status> {
+        self.accounts().sessions.retain(|(session, _)| {
+            session.username != username || Some(session.token.as_str()) == keep
+        });
+
+        Ok(())
+    }
+
+    async fn insert_audit_entry(&self, entry: AuditEntry) -> Result<(), Status> {
+        self.accounts().audit_log.push(entry);
+        Ok(())
+    }
+
+    async fn audit_log(&self, limit: i64) -> Result<Vec<AuditEntry>, Status> {
+        Ok(self
+            .accounts()
+            .audit_log
+            .iter()
+            .rev()
+            .take(limit.max(0) as usize)
+            .cloned()
+            .collect())
+    }
+}
+
+/// Whether `game` is one `/games/all` would list for `filter`, ignoring its cursor.
+fn filter_matches(filter: &GameFilter, game: &ConnectGame) -> bool {
+    let player = filter.player.as_deref();
+    let opponent = filter.opponent.as_deref();
+    let plays = |name: &str| game.player1 == name || game.player2 == name;
+
+    player.is_none_or(plays)
+        && opponent.is_none_or(plays)
+        && match (player, filter.result) {
+            (Some(player), Some(GameResult::Win)) => game.winner == player,
+            (Some(player), Some(GameResult::Loss)) => game.winner != player,
+            _ => true,
+        }
+        && filter
+            .game_type
+            .as_ref()
+            .is_none_or(|game_type| game.game_type == *game_type)
+        && filter
+            .from
+            .is_none_or(|from| game.ended_at.date_naive() >= from)
+        && filter.to.is_none_or(|to| game.ended_at.date_naive() <= to)
+        && filter
+            .board
+            .is_none_or(|board| (game.rows, game.cols) == (Some(board.0), Some(board.1)))
+}
+
+#[derive(Default)]
+struct Games {
+    /// In the order they were saved, which stands in for Mongo's id order.
+    games: Vec<GameRecord>,
+    stats: Vec<PlayerStats>,
+}
+
+#[derive(Default)]
+pub struct MemoryGames {
+    games: Mutex<Games>,
+}
+
+impl MemoryGames {
+    fn games(&self) -> std::sync::MutexGuard<'_, Games> {
+        self.games
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+
+    fn stats_where(&self, predicate: impl Fn(&PlayerStats) -> bool) -> Vec<PlayerStats> {
+        self.games()
+            .stats
+            .iter()
+            .filter(|stats| predicate(stats))
+            .cloned()
+            .collect()
+    }
+}
+
+#[rocket::async_trait]
+impl GameRepository for MemoryGames {
+    async fn insert_game(&self, game: &ConnectGame) -> Result<(), Status> {
+        self.games().games.push(GameRecord {
+            id: ObjectId::new().to_hex(),
+            game: game.clone(),
+        });
+
+        Ok(())
+    }
+
+    async fn list_games(&self, filter: &GameFilter) -> Result<GamePage, Status> {
+        let limit = filter.limit.unwrap_or(DEFAULT_LIMIT) as usize;
+        let state = self.games();
+
+        let ordered: Box<dyn Iterator<Item = &GameRecord>> = if filter.ascending {
+            Box::new(state.games.iter())
+        } else {
+            Box::new(state.games.iter().rev())
+        };
+
+        // Everything up to and including the cursor was on an earlier page
+        let ordered: Box<dyn Iterator<Item = &GameRecord>> = match &filter.cursor {
+            Some(cursor) => {
+                if !state.games.iter().any(|record| record.id == *cursor) {
+                    return Err(Status::BadRequest);
+                }
+
+                Box::new(
+                    ordered
+                        .skip_while(move |record| record.id != *cursor)
+                        .skip(1),
+                )
+            }
+            None => ordered,
+        };
+
+        let mut games: Vec<GameRecord> = ordered
+            .filter(|record| filter_matches(filter, &record.game))
+            .take(limit + 1)
+            .cloned()
+            .collect();
+
+        let next_cursor = if games.len() > limit {
+            games.truncate(limit);
+            games.last().map(|game| game.id.clone())
+        } else {
+            None
+        };
+
+        Ok(GamePage { games, next_cursor })
+    }
+
+    async fn search_games(
+        &self,
+        player: Option<&str>,
+        game_type: Option<&GameType>,
+        limit: i64,
+    ) -> Result<Vec<GameRecord>, Status> {
+        Ok(self
+            .games()
+            .games
+            .iter()
+            .rev()
+            .filter(|record| {
+                player.is_none_or(|player| {
+                    contains_ignoring_case(&record.game.player1, player)
+                        || contains_ignoring_case(&record.game.player2, player)
+                }) && game_type.is_none_or(|game_type| record.game.game_type == *game_type)
+            })
+            .take(limit.max(0) as usize)
+            .cloned()
+            .collect())
+    }
+
+    async fn select_games(&self, selection: &GameSelection<'_>) -> Result<Vec<GameRecord>, Status> {
+        let mut games: Vec<GameRecord> = self
+            .games()
+            .games
+            .iter()
+            .filter(|record| selection.matches(&record.game))
+            .cloned()
+            .collect();
+
+        // Stable, so games that ended at the same instant stay in the order they were saved
+        games.sort_by_key(|record| record.game.ended_at);
+
+        Ok(games)
+    }
+
+    async fn player_games(
+        &self,
+        username: &str,
+        skip: u64,
+        limit: u32,
+    ) -> Result<(Vec<GameRecord>, u64), Status> {
+        let games = self
+            .select_games(&GameSelection::played_by(username))
+            .await?;
+        let total = games.len() as u64;
+
+        let page = games
+            .into_iter()
+            .rev()
+            .skip(usize::try_from(skip).unwrap_or(usize::MAX))
+            .take(limit as usize)
+            .collect();
+
+        Ok((page, total))
+    }
+
+    async fn head_to_head(&self, username: &str) -> Result<Vec<HeadToHead>, Status> {
+        let games = self
+            .select_games(&GameSelection::played_by(username))
+            .await?;
+
+        Ok(profile::head_to_head(&games, username))
+    }
+
+    async fn monthly_records(&self, username: &str) -> Result<Vec<MonthlyRecord>, Status> {
+        let games = self
+            .select_games(&GameSelection::played_by(username))
+            .await?;
+
+        Ok(profile::monthly_records(&games, username))
+    }
+
+    async fn find_game(&self, id: &str) -> Result<Option<ConnectGame>, Status> {
+        Ok(self
+            .games()
+            .games
+            .iter()
+            .find(|record| record.id == id)
+            .map(|record| record.game.clone()))
+    }
+
+    async fn delete_game(&self, id: &str) -> Result<Option<ConnectGame>, Status> {
+        let mut state = self.games();
+
+        Ok(state
+            .games
+            .iter()
+            .position(|record| record.id == id)
+            .map(|index| state.games.remove(index).game))
+    }
+
+    async fn delete_player_games(&self, username: &str) -> Result<Vec<ConnectGame>, Status> {
+        let mut state = self.games();
+        let plays = |game: &ConnectGame| game.player1 == username || game.player2 == username;
+
+        let deleted = state
+            .games
+            .iter()
+            .filter(|record| plays(&record.game))
+            .map(|record| record.game.clone())
+            .collect();
+        state.games.retain(|record| !plays(&record.game));
+
+        Ok(deleted)
+    }
+
+    async fn rename_player(&self, from: &str, to: &str) -> Result<(), Status> {
+        for record in self.games().games.iter_mut() {
+            let game = &mut record.game;
+
+            for name in [&mut game.player1, &mut game.player2, &mut game.winner] {
+                if name == from {
+                    *name = to.to_string();
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    async fn visible_stats(&self, game_type: &GameType) -> Result<Vec<PlayerStats>, Status> {
+        Ok(self.stats_where(|stats| stats.game_type == *game_type && !stats.hidden))
+    }
+
+    async fn player_stats(&self, username: &str) -> Result<Vec<PlayerStats>, Status> {
+        Ok(self.stats_where(|stats| stats.username == username))
+    }
+
+    async fn update_stats(
+        &self,
+        new: PlayerStats,
+        update: &(dyn for<'s> Fn(&'s mut PlayerStats) + Send + Sync),
+    ) -> Result<(), Status> {
+        // Held until the update is saved
+        let mut state = self.games();
+
+        match state.stats.iter_mut().find(|existing| {
+            existing.game_type == new.game_type && existing.username == new.username
+        }) {
+            Some(existing) => update(existing),
+            None => {
+                let mut stats = new;
+                update(&mut stats);
+                state.stats.push(stats);
+            }
+        }
+
+        Ok(())
+    }
+
+    async fn save_stats(&self, stats: &PlayerStats) -> Result<(), Status> {
+        let mut state = self.games();
+
+        match state.stats.iter_mut().find(|existing| {
+            existing.game_type == stats.game_type && existing.username == stats.username
+        }) {
+            Some(existing) => *existing = stats.clone(),
+            None => state.stats.push(stats.clone()),
+        }
+
+        Ok(())
+    }
+
+    async fn delete_stats(&self, game_type: &GameType, username: &str) -> Result<(), Status> {
+        self.games()
+            .stats
+            .retain(|stats| stats.game_type != *game_type || stats.username != username);
+
+        Ok(())
+    }
+
+    async fn set_stats_hidden(&self, username: &str, hidden: bool) -> Result<(), Status> {
+        for stats in self
+            .games()
+            .stats
+            .iter_mut()
+            .filter(|stats| stats.username == username)
+        {
+            stats.hidden = hidden;
+        }
+
+        Ok(())
+    }
+
+    async fn replace_all_stats(&self, stats: Vec<PlayerStats>) -> Result<(), Status> {
+        self.games().stats = stats;
+        Ok(())
+    }
+}
@@ -0,0 +1,274 @@
+use chrono::{DateTime, NaiveDate, Utc};
+use client::{
+    AuditEntry, ConnectGame, CpuLevel, GameFilter, GamePage, GameRecord, GameType, HeadToHead,
+    MonthlyRecord, Opponent, Role, Session,
+};
+use rocket::{fairing, http::Status, serde::Deserialize, Build, Rocket};
+
+use crate::leaderboard::PlayerStats;
+use crate::users::UserDocument;
+
+mod memory;
+mod mongo;
+
+pub use memory::{MemoryGames, MemoryUsers};
+pub use mongo::{create_indexes, prepare, MongoGames, MongoUsers};
+
+/// Accounts, along with the sessions signed in to them and the admin audit log.
+#[rocket::async_trait]
+pub trait UserRepository: Send + Sync {
+    async fn find_user(&self, username: &str) -> Result<Option<UserDocument>, Status>;
+
+    /// Callers check that the username is free first. Should it be taken in the meantime,
+    /// this fails with `Conflict`.
+    async fn insert_user(&self, user: UserDocument) -> Result<(), Status>;
+
+    /// Up to `limit` users whose name contains `search`, ignoring case, in alphabetical order.
+    async fn search_users(
+        &self,
+        search: Option<&str>,
+        limit: i64,
+    ) -> Result<Vec<UserDocument>, Status>;
+
+    /// The usernames of guests and banned users.
+    async fn hidden_users(&self) -> Result<Vec<String>, Status>;
+
+    /// The usernames of guests created before `before`, or with no `created_at`.
+    async fn guests_created_before(&self, before: DateTime<Utc>) -> Result<Vec<String>, Status>;
+
+    async fn set_password(&self, username: &str, password: &str) -> Result<(), Status>;
+
+    /// Returns whether there was a user to update.
+    async fn set_role(&self, username: &str, role: Role) -> Result<bool, Status>;
+
+    /// Returns whether there was a user to update.
+    async fn set_banned(&self, username: &str, banned: bool) -> Result<bool, Status>;
+
+    /// Renames a user, keeping them signed in to their sessions.
+    async fn rename_user(&self, from: &str, to: &str) -> Result<(), Status>;
+
+    /// Deletes a user and signs them out everywhere.
+    async fn delete_user(&self, username: &str) -> Result<(), Status>;
+
+    async fn insert_session(
+        &self,
+        session: &Session,
+        expires_at: DateTime<Utc>,
+    ) -> Result<(), Status>;
+
+    /// `None` if there is no session with that token, or it has expired.
+    async fn find_session(&self, token: &str) -> Result<Option<Session>, Status>;
+
+    /// Signs out of the session with the token `token`.
+    async fn delete_session(&self, token: &str) -> Result<(), Status>;
+
+    /// Signs `username` out of every session, apart from the one with the token `keep`.
+    async fn delete_sessions(&self, username: &str, keep: Option<&str>) -> Result<(), Status>;
+
+    async fn insert_audit_entry(&self, entry: AuditEntry) -> Result<(), Status>;
+
+    /// Up to `limit` audit entries, newest first.
+    async fn audit_log(&self, limit: i64) -> Result<Vec<AuditEntry>, Status>;
+}
+
+/// Finished games, along with the per-player stats maintained from them.
+#[rocket::async_trait]
+pub trait GameRepository: Send + Sync {
+    async fn insert_game(&self, game: &ConnectGame) -> Result<(), Status>;
+
+    /// One page of the games matching `filter`, which `GameFilterParams::parse` has validated.
+    async fn list_games(&self, filter: &GameFilter) -> Result<GamePage, Status>;
+
+    /// Up to `limit` games, newest first, with a player whose name contains `player`,
+    /// ignoring case.
+    async fn search_games(
+        &self,
+        player: Option<&str>,
+        game_type: Option<&GameType>,
+        limit: i64,
+    ) -> Result<Vec<GameRecord>, Status>;
+
+    /// Every game `selection` matches, oldest first.
+    async fn select_games(&self, selection: &GameSelection<'_>) -> Result<Vec<GameRecord>, Status>;
+
+    /// Up to `limit` of the games `username` played, newest first, after skipping `skip` of
+    /// them, along with how many they played in all.
+    async fn player_games(
+        &self,
+        username: &str,
+        skip: u64,
+        limit: u32,
+    ) -> Result<(Vec<GameRecord>, u64), Status>;
+
+    /// `username`'s wins and losses against each opponent, in no particular order.
+    async fn head_to_head(&self, username: &str) -> Result<Vec<HeadToHead>, Status>;
+
+    /// `username`'s wins and losses in each month they played, oldest first.
+    async fn monthly_records(&self, username: &str) -> Result<Vec<MonthlyRecord>, Status>;
+
+    /// `None` if there is no game with that id.
+    async fn find_game(&self, id: &str) -> Result<Option<ConnectGame>, Status>;
+
+    /// Returns the deleted game, or `None` if there was no game with that id.
+    async fn delete_game(&self, id: &str) -> Result<Option<ConnectGame>, Status>;
+
+    /// Deletes every game `username` played, returning them.
+    async fn delete_player_games(&self, username: &str) -> Result<Vec<ConnectGame>, Status>;
+
+    /// Replaces `from` with `to` wherever they appear as a player or the winner.
+    async fn rename_player(&self, from: &str, to: &str) -> Result<(), Status>;
+
+    /// Every player's stats in one game type, apart from those marked `hidden`.
+    async fn visible_stats(&self, game_type: &GameType) -> Result<Vec<PlayerStats>, Status>;
+
+    /// One player's stats in every game type they have played.
+    async fn player_stats(&self, username: &str) -> Result<Vec<PlayerStats>, Status>;
+
+    /// Applies `update` to the stats for the player and game type in `new`, or to `new` if
+    /// there are none yet, without losing an update made at the same time.
+    async fn update_stats(
+        &self,
+        new: PlayerStats,
+        update: &(dyn for<'s> Fn(&'s mut PlayerStats) + Send + Sync),
+    ) -> Result<(), Status>;
+
+    /// Inserts or replaces the stats for the player and game type in `stats`.
+    async fn save_stats(&self, stats: &PlayerStats) -> Result<(), Status>;
+
+    async fn delete_stats(&self, game_type: &GameType, username: &str) -> Result<(), Status>;
+
+    /// Sets `hidden` on `username`'s stats in every game type.
+    async fn set_stats_hidden(&self, username: &str, hidden: bool) -> Result<(), Status>;
+
+    /// Throws away every player's stats in favour of `stats`.
+    async fn replace_all_stats(&self, stats: Vec<PlayerStats>) -> Result<(), Status>;
+}
+
+/// The games stats are computed from. Fields left unset match every game.
+#[derive(Default)]
+pub struct GameSelection<'a> {
+    pub game_type: Option<&'a GameType>,
+    pub player: Option<&'a str>,
+    /// Games that ended on or after this day, UTC.
+    pub since: Option<NaiveDate>,
+    pub board: Option<(u32, u32)>,
+    pub opponent: Option<Opponent>,
+}
+
+impl<'a> GameSelection<'a> {
+    /// Every game `username` played.
+    pub fn played_by(username: &'a str) -> Self {
+        GameSelection {
+            player: Some(username),
+            ..GameSelection::default()
+        }
+    }
+
+    pub fn matches(&self, game: &ConnectGame) -> bool {
+        let is_cpu = |name: &str| CpuLevel::from_player_name(name).is_some();
+
+        self.game_type
+            .is_none_or(|game_type| game.game_type == *game_type)
+            && self
+                .player
+                .is_none_or(|player| game.player1 == player || game.player2 == player)
+            && self
+                .since
+                .is_none_or(|since| game.ended_at.date_naive() >= since)
+            && self
+                .board
+                .is_none_or(|board| (game.rows, game.cols) == (Some(board.0), Some(board.1)))
+            && match self.opponent {
+                None => true,
+                Some(Opponent::Human) => !is_cpu(&game.player1) && !is_cpu(&game.player2),
+                Some(Opponent::Cpu(level)) => {
+                    let name = level.player_name();
+                    game.player1 == name || game.player2 == name
+                }
+            }
+    }
+}
+
+/// Where the server keeps its data, picked by the `storage` config value.
+pub struct Storage {
+    pub users: Box<dyn UserRepository>,
+    pub games: Box<dyn GameRepository>,
+}
+
+impl Storage {
+    pub fn mongo(db: &mongodb::Database) -> Self {
+        Storage {
+            users: Box::new(MongoUsers::new(db)),
+            games: Box::new(MongoGames::new(db)),
+        }
+    }
+
+    /// Storage that starts out empty and is lost on shutdown, for tests and local demos.
+    pub fn memory() -> Self {
+        Storage {
+            users: Box::<MemoryUsers>::default(),
+            games: Box::<MemoryGames>::default(),
+        }
+    }
+}
+
+/// The values the `storage` config key accepts.
+#[derive(Deserialize, Debug, Clone, Copy, Default, PartialEq)]
+#[serde(crate = "rocket::serde", rename_all = "lowercase")]
+enum Backend {
+    /// The database at `databases.mongodb_main.url`.
+    #[default]
+    MongoDb,
+    Memory,
+}
+
+/// Puts the `Storage` picked by the `storage` config value, `mongodb` unless set, in
+/// managed state.
+pub async fn init(rocket: Rocket<Build>) -> fairing::Result {
+    let backend = match rocket.figment().find_value("storage") {
+        Ok(_) => match rocket.figment().extract_inner::<Backend>("storage") {
+            Ok(backend) => backend,
+            Err(err) => {
+                error!("Invalid storage backend: {}", err);
+                return Err(rocket);
+            }
+        },
+        Err(_) => Backend::default(),
+    };
+
+    let storage = match backend {
+        Backend::Memory => Storage::memory(),
+        Backend::MongoDb => {
+            let url = match rocket
+                .figment()
+                .extract_inner::<String>("databases.mongodb_main.url")
+            {
+                Ok(url) => url,
+                Err(err) => {
+                    error!("Missing MongoDB url: {}", err);
+                    return Err(rocket);
+                }
+            };
+
+            let client = match mongodb::Client::with_uri_str(&url).await {
+                Ok(client) => client,
+                Err(err) => {
+                    error!("Invalid MongoDB url: {}", err);
+                    return Err(rocket);
+                }
+            };
+
+            let db = client.database("mongodb_main");
+
+            // Old documents can't be read until they are migrated, so launch waits for it
+            if prepare(&db).await.is_err() {
+                error!("Could not migrate and index the database");
+                return Err(rocket);
+            }
+
+            Storage::mongo(&db)
+        }
+    };
+
+    Ok(rocket.manage(storage))
+}
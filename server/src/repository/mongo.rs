@@ -0,0 +1,1007 @@
+use chrono::{DateTime, NaiveDate, Utc};
+use client::{
+    AuditEntry, ConnectGame, CpuLevel, FirstMove, GameFilter, GamePage, GameRecord, GameResult,
+    GameType, HeadToHead, MonthlyRecord, Opponent, Role, Session,
+};
+use mongodb::{
+    bson::{self, doc, oid::ObjectId, to_bson, Bson, Document, Regex},
+    error::{ErrorKind, WriteFailure},
+    options::{FindOptions, IndexOptions, ReplaceOptions},
+    Collection, Database, IndexModel,
+};
+use rocket::{
+    futures::TryStreamExt,
+    http::Status,
+    serde::{de::DeserializeOwned, Deserialize, Serialize},
+};
+
+use super::{GameRepository, GameSelection, UserRepository};
+use crate::games::DEFAULT_LIMIT;
+use crate::leaderboard::PlayerStats;
+use crate::migrations::{self, GAMES_SCHEMA_VERSION};
+use crate::users::UserDocument;
+
+/// A `ConnectGame` as the `games` collection holds it, with its timestamps as BSON
+/// datetimes so Mongo can sort and compare them.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(crate = "rocket::serde")]
+pub struct StoredGame {
+    /// Zero for games saved before documents were versioned.
+    #[serde(default)]
+    schema_version: u32,
+    game_type: GameType,
+    player1: String,
+    player2: String,
+    winner: String,
+    #[serde(with = "bson::serde_helpers::chrono_datetime_as_bson_datetime")]
+    started_at: DateTime<Utc>,
+    #[serde(with = "bson::serde_helpers::chrono_datetime_as_bson_datetime")]
+    ended_at: DateTime<Utc>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    rows: Option<u32>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    cols: Option<u32>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    moves: Option<u32>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    seed: Option<u32>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    notation: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    player1_first: Option<bool>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    first_move: Option<FirstMove>,
+}
+
+/// A `Session` as the `sessions` collection holds it, along with when it expires. A TTL
+/// index clears expired sessions out eventually, so reads check `expires_at` as well.
+#[derive(Serialize, Deserialize, Debug)]
+#[serde(crate = "rocket::serde")]
+struct StoredSession {
+    #[serde(flatten)]
+    session: Session,
+    #[serde(with = "bson::serde_helpers::chrono_datetime_as_bson_datetime")]
+    expires_at: DateTime<Utc>,
+}
+
+/// An `AuditEntry` as the `audit_log` collection holds it, its date a BSON datetime.
+#[derive(Serialize, Deserialize, Debug)]
+#[serde(crate = "rocket::serde")]
+struct StoredAuditEntry {
+    admin: String,
+    action: String,
+    target: String,
+    #[serde(with = "bson::serde_helpers::chrono_datetime_as_bson_datetime")]
+    date: DateTime<Utc>,
+}
+
+impl From<AuditEntry> for StoredAuditEntry {
+    fn from(entry: AuditEntry) -> Self {
+        StoredAuditEntry {
+            admin: entry.admin,
+            action: entry.action,
+            target: entry.target,
+            date: entry.date,
+        }
+    }
+}
+
+impl From<StoredAuditEntry> for AuditEntry {
+    fn from(entry: StoredAuditEntry) -> Self {
+        AuditEntry {
+            admin: entry.admin,
+            action: entry.action,
+            target: entry.target,
+            date: entry.date,
+        }
+    }
+}
+
+impl From<ConnectGame> for StoredGame {
+    fn from(game: ConnectGame) -> Self {
+        StoredGame {
+            schema_version: GAMES_SCHEMA_VERSION,
+            game_type: game.game_type,
+            player1: game.player1,
+            player2: game.player2,
+            winner: game.winner,
+            started_at: game.started_at,
+            ended_at: game.ended_at,
+            rows: game.rows,
+            cols: game.cols,
+            moves: game.moves,
+            seed: game.seed,
+            notation: game.notation,
+            player1_first: game.player1_first,
+            first_move: game.first_move,
+        }
+    }
+}
+
+impl From<StoredGame> for ConnectGame {
+    fn from(game: StoredGame) -> Self {
+        ConnectGame {
+            game_type: game.game_type,
+            player1: game.player1,
+            player2: game.player2,
+            winner: game.winner,
+            started_at: game.started_at,
+            ended_at: game.ended_at,
+            rows: game.rows,
+            cols: game.cols,
+            moves: game.moves,
+            seed: game.seed,
+            notation: game.notation,
+            player1_first: game.player1_first,
+            first_move: game.first_move,
+        }
+    }
+}
+
+/// A game read back from the `games` collection, with the id Mongo gave it.
+#[derive(Deserialize)]
+#[serde(crate = "rocket::serde")]
+struct GameDocument {
+    #[serde(rename = "_id")]
+    id: ObjectId,
+    #[serde(flatten)]
+    game: StoredGame,
+}
+
+impl From<GameDocument> for GameRecord {
+    fn from(document: GameDocument) -> Self {
+        GameRecord {
+            id: document.id.to_hex(),
+            game: document.game.into(),
+        }
+    }
+}
+
+fn game_type_bson(game_type: &GameType) -> Bson {
+    to_bson(game_type).expect("game types serialize to strings")
+}
+
+fn start_of_day(date: NaiveDate) -> bson::DateTime {
+    bson::DateTime::from_chrono(date.and_hms_opt(0, 0, 0).unwrap().and_utc())
+}
+
+/// A case-insensitive "contains" match on `text`.
+fn contains(text: &str) -> Regex {
+    let pattern = text
+        .chars()
+        .map(|c| {
+            if "\\^$.|?*+()[]{}".contains(c) {
+                format!("\\{}", c)
+            } else {
+                c.to_string()
+            }
+        })
+        .collect();
+
+    Regex {
+        pattern,
+        options: "i".to_string(),
+    }
+}
+
+/// Both players' fields matching `username`.
+fn played_by(username: &str) -> Document {
+    doc! {"$or": [{"player1": username}, {"player2": username}]}
+}
+
+/// Turns a `GameFilter` into a Mongo filter on the `games` collection.
+fn filter_document(filter: &GameFilter) -> Result<Document, Status> {
+    let mut conditions = vec![];
+
+    match (&filter.player, &filter.opponent) {
+        (Some(player), Some(opponent)) => conditions.push(doc! {"$or": [
+            {"player1": player, "player2": opponent},
+            {"player1": opponent, "player2": player},
+        ]}),
+        (Some(player), None) => conditions.push(played_by(player)),
+        (None, _) => {}
+    }
+
+    match (&filter.player, filter.result) {
+        (Some(player), Some(GameResult::Win)) => conditions.push(doc! {"winner": player}),
+        (Some(player), Some(GameResult::Loss)) => conditions.push(doc! {"winner": {"$ne": player}}),
+        _ => {}
+    }
+
+    if let Some(game_type) = &filter.game_type {
+        conditions.push(doc! {"game_type": game_type_bson(game_type)});
+    }
+
+    if let Some(from) = filter.from {
+        conditions.push(doc! {"ended_at": {"$gte": start_of_day(from)}});
+    }
+
+    if let Some(to) = filter.to {
+        let end = start_of_day(to.succ_opt().ok_or(Status::BadRequest)?);
+        conditions.push(doc! {"ended_at": {"$lt": end}});
+    }
+
+    if let Some((rows, cols)) = filter.board {
+        conditions.push(doc! {"rows": rows, "cols": cols});
+    }
+
+    if let Some(cursor) = &filter.cursor {
+        let cursor = ObjectId::parse_str(cursor).map_err(|_| Status::BadRequest)?;
+        let direction = if filter.ascending { "$gt" } else { "$lt" };
+        conditions.push(doc! {"_id": {direction: cursor}});
+    }
+
+    Ok(match conditions.len() {
+        0 => Document::new(),
+        1 => conditions.remove(0),
+        _ => doc! {"$and": conditions},
+    })
+}
+
+/// Turns a `GameSelection` into a Mongo filter on the `games` collection.
+fn selection_document(selection: &GameSelection<'_>) -> Document {
+    let mut conditions = vec![];
+
+    if let Some(game_type) = selection.game_type {
+        conditions.push(doc! {"game_type": game_type_bson(game_type)});
+    }
+
+    if let Some(player) = selection.player {
+        conditions.push(played_by(player));
+    }
+
+    if let Some(since) = selection.since {
+        conditions.push(doc! {"ended_at": {"$gte": start_of_day(since)}});
+    }
+
+    if let Some((rows, cols)) = selection.board {
+        conditions.push(doc! {"rows": rows, "cols": cols});
+    }
+
+    match selection.opponent {
+        Some(Opponent::Human) => {
+            let cpus: Vec<&str> = CpuLevel::ALL.iter().map(CpuLevel::player_name).collect();
+            conditions.push(doc! {"player1": {"$nin": &cpus}, "player2": {"$nin": &cpus}});
+        }
+        Some(Opponent::Cpu(level)) => conditions.push(played_by(level.player_name())),
+        None => {}
+    }
+
+    match conditions.len() {
+        0 => Document::new(),
+        1 => conditions.remove(0),
+        _ => doc! {"$and": conditions},
+    }
+}
+
+/// Oldest first, which is the order streaks have to be counted in.
+fn oldest_first() -> FindOptions {
+    FindOptions::builder()
+        .sort(doc! {"ended_at": 1, "_id": 1})
+        .build()
+}
+
+/// Whether `err` is a unique index turning a write away.
+fn is_duplicate_key(err: &mongodb::error::Error) -> bool {
+    matches!(
+        *err.kind,
+        ErrorKind::Write(WriteFailure::WriteError(ref err)) if err.code == 11000
+    )
+}
+
+/// The indexes queries rely on. Creating an index that already exists is a no-op.
+pub async fn create_indexes(db: &Database) -> Result<(), mongodb::error::Error> {
+    let games = db.collection::<Document>("games");
+
+    // Game listings, newest first
+    for field in ["player1", "player2", "game_type", "ended_at"] {
+        games
+            .create_index(
+                IndexModel::builder()
+                    .keys(doc! {field: 1, "_id": -1})
+                    .build(),
+                None,
+            )
+            .await?;
+    }
+
+    // Used when a single player's stats are recomputed
+    for player in ["player1", "player2"] {
+        games
+            .create_index(
+                IndexModel::builder()
+                    .keys(doc! {"game_type": 1, player: 1})
+                    .build(),
+                None,
+            )
+            .await?;
+    }
+
+    let users = db.collection::<Document>("users");
+
+    // Two users can't claim one name, even when both register at once
+    users
+        .create_index(
+            IndexModel::builder()
+                .keys(doc! {"username": 1})
+                .options(IndexOptions::builder().unique(true).build())
+                .build(),
+            None,
+        )
+        .await?;
+
+    let sessions = db.collection::<Document>("sessions");
+
+    // Every authenticated request looks its session up by token
+    sessions
+        .create_index(
+            IndexModel::builder()
+                .keys(doc! {"token": 1})
+                .options(IndexOptions::builder().unique(true).build())
+                .build(),
+            None,
+        )
+        .await?;
+
+    // Signing a user out everywhere
+    sessions
+        .create_index(
+            IndexModel::builder().keys(doc! {"username": 1}).build(),
+            None,
+        )
+        .await?;
+
+    sessions
+        .create_index(
+            IndexModel::builder()
+                .keys(doc! {"expires_at": 1})
+                .options(
+                    IndexOptions::builder()
+                        .expire_after(std::time::Duration::ZERO)
+                        .build(),
+                )
+                .build(),
+            None,
+        )
+        .await?;
+
+    db.collection::<Document>("leaderboard")
+        .create_index(
+            IndexModel::builder()
+                .keys(doc! {"game_type": 1, "username": 1})
+                .options(IndexOptions::builder().unique(true).build())
+                .build(),
+            None,
+        )
+        .await?;
+
+    Ok(())
+}
+
+/// Brings a database up to date: applies pending migrations, then creates indexes.
+pub async fn prepare(db: &Database) -> Result<(), Status> {
+    migrations::run(db, false).await?;
+
+    create_indexes(db)
+        .await
+        .map_err(|_| Status::InternalServerError)
+}
+
+pub struct MongoUsers {
+    users: Collection<UserDocument>,
+    sessions: Collection<StoredSession>,
+    audit_log: Collection<StoredAuditEntry>,
+}
+
+impl MongoUsers {
+    pub fn new(db: &Database) -> Self {
+        MongoUsers {
+            users: db.collection("users"),
+            sessions: db.collection("sessions"),
+            audit_log: db.collection("audit_log"),
+        }
+    }
+}
+
+#[rocket::async_trait]
+impl UserRepository for MongoUsers {
+    async fn find_user(&self, username: &str) -> Result<Option<UserDocument>, Status> {
+        self.users
+            .find_one(doc! {"username": username}, None)
+            .await
+            .map_err(|_| Status::InternalServerError)
+    }
+
+    async fn insert_user(&self, user: UserDocument) -> Result<(), Status> {
+        self.users
+            .insert_one(user, None)
+            .await
+            .map_err(|err| match is_duplicate_key(&err) {
+                // The unique index on `username`
+                true => Status::Conflict,
+                false => Status::InternalServerError,
+            })?;
+
+        Ok(())
+    }
+
+    async fn search_users(
+        &self,
+        search: Option<&str>,
+        limit: i64,
+    ) -> Result<Vec<UserDocument>, Status> {
+        let filter = search.map(|search| doc! {"username": contains(search)});
+
+        let options = FindOptions::builder()
+            .sort(doc! {"username": 1})
+            .limit(limit)
+            .build();
+
+        self.users
+            .find(filter, options)
+            .await
+            .map_err(|_| Status::InternalServerError)?
+            .try_collect()
+            .await
+            .map_err(|_| Status::InternalServerError)
+    }
+
+    async fn hidden_users(&self) -> Result<Vec<String>, Status> {
+        let users: Vec<UserDocument> = self
+            .users
+            .find(doc! {"$or": [{"guest": true}, {"banned": true}]}, None)
+            .await
+            .map_err(|_| Status::InternalServerError)?
+            .try_collect()
+            .await
+            .map_err(|_| Status::InternalServerError)?;
+
+        Ok(users.into_iter().map(|user| user.username).collect())
+    }
+
+    async fn guests_created_before(&self, before: DateTime<Utc>) -> Result<Vec<String>, Status> {
+        let filter = doc! {
+            "guest": true,
+            // Matches a missing `created_at` too
+            "created_at": {"$not": {"$gte": bson::DateTime::from_chrono(before)}},
+        };
+
+        let users: Vec<UserDocument> = self
+            .users
+            .find(filter, None)
+            .await
+            .map_err(|_| Status::InternalServerError)?
+            .try_collect()
+            .await
+            .map_err(|_| Status::InternalServerError)?;
+
+        Ok(users.into_iter().map(|user| user.username).collect())
+    }
+
+    async fn set_password(&self, username: &str, password: &str) -> Result<(), Status> {
+        self.users
+            .update_one(
+                doc! {"username": username},
+                doc! {"$set": {"password": password}},
+                None,
+            )
+            .await
+            .map_err(|_| Status::InternalServerError)?;
+
+        Ok(())
+    }
+
+    async fn set_role(&self, username: &str, role: Role) -> Result<bool, Status> {
+        let role = to_bson(&role).map_err(|_| Status::InternalServerError)?;
+
+        let result = self
+            .users
+            .update_one(
+                doc! {"username": username},
+                doc! {"$set": {"role": role}},
+                None,
+            )
+            .await
+            .map_err(|_| Status::InternalServerError)?;
+
+        Ok(result.matched_count > 0)
+    }
+
+    async fn set_banned(&self, username: &str, banned: bool) -> Result<bool, Status> {
+        let result = self
+            .users
+            .update_one(
+                doc! {"username": username},
+                doc! {"$set": {"banned": banned}},
+                None,
+            )
+            .await
+            .map_err(|_| Status::InternalServerError)?;
+
+        Ok(result.matched_count > 0)
+    }
+
+    async fn rename_user(&self, from: &str, to: &str) -> Result<(), Status> {
+        self.users
+            .update_one(
+                doc! {"username": from},
+                doc! {"$set": {"username": to}},
+                None,
+            )
+            .await
+            .map_err(|_| Status::InternalServerError)?;
+
+        self.sessions
+            .update_many(
+                doc! {"username": from},
+                doc! {"$set": {"username": to}},
+                None,
+            )
+            .await
+            .map_err(|_| Status::InternalServerError)?;
+
+        Ok(())
+    }
+
+    async fn delete_user(&self, username: &str) -> Result<(), Status> {
+        self.users
+            .delete_one(doc! {"username": username}, None)
+            .await
+            .map_err(|_| Status::InternalServerError)?;
+
+        self.delete_sessions(username, None).await
+    }
+
+    async fn insert_session(
+        &self,
+        session: &Session,
+        expires_at: DateTime<Utc>,
+    ) -> Result<(), Status> {
+        let stored = StoredSession {
+            session: session.clone(),
+            expires_at,
+        };
+
+        self.sessions
+            .insert_one(stored, None)
+            .await
+            .map_err(|_| Status::InternalServerError)?;
+
+        Ok(())
+    }
+
+    async fn find_session(&self, token: &str) -> Result<Option<Session>, Status> {
+        // Sessions saved before they expired have no `expires_at`, so they don't match either
+        let filter = doc! {
+            "token": token,
+            "expires_at": {"$gt": bson::DateTime::from_chrono(Utc::now())},
+        };
+
+        let stored = self
+            .sessions
+            .find_one(filter, None)
+            .await
+            .map_err(|_| Status::InternalServerError)?;
+
+        Ok(stored.map(|stored| stored.session))
+    }
+
+    async fn delete_session(&self, token: &str) -> Result<(), Status> {
+        self.sessions
+            .delete_one(doc! {"token": token}, None)
+            .await
+            .map_err(|_| Status::InternalServerError)?;
+
+        Ok(())
+    }
+
+    async fn delete_sessions(&self, username: &str, keep: Option<&str>) -> Result<(), Status> {
+        let mut filter = doc! {"username": username};
+
+        if let Some(keep) = keep {
+            filter.insert("token", doc! {"$ne": keep});
+        }
+
+        self.sessions
+            .delete_many(filter, None)
+            .await
+            .map_err(|_| Status::InternalServerError)?;
+
+        Ok(())
+    }
+
+    async fn insert_audit_entry(&self, entry: AuditEntry) -> Result<(), Status> {
+        self.audit_log
+            .insert_one(StoredAuditEntry::from(entry), None)
+            .await
+            .map_err(|_| Status::InternalServerError)?;
+
+        Ok(())
+    }
+
+    async fn audit_log(&self, limit: i64) -> Result<Vec<AuditEntry>, Status> {
+        let options = FindOptions::builder()
+            .sort(doc! {"_id": -1})
+            .limit(limit)
+            .build();
+
+        let entries: Vec<StoredAuditEntry> = self
+            .audit_log
+            .find(None, options)
+            .await
+            .map_err(|_| Status::InternalServerError)?
+            .try_collect()
+            .await
+            .map_err(|_| Status::InternalServerError)?;
+
+        Ok(entries.into_iter().map(AuditEntry::from).collect())
+    }
+}
+
+pub struct MongoGames {
+    games: Collection<StoredGame>,
+    stats: Collection<PlayerStats>,
+}
+
+impl MongoGames {
+    pub fn new(db: &Database) -> Self {
+        MongoGames {
+            games: db.collection("games"),
+            stats: db.collection("leaderboard"),
+        }
+    }
+
+    fn documents(&self) -> Collection<GameDocument> {
+        self.games.clone_with_type()
+    }
+
+    async fn find_games(
+        &self,
+        filter: Document,
+        options: FindOptions,
+    ) -> Result<Vec<GameRecord>, Status> {
+        let documents: Vec<GameDocument> = self
+            .documents()
+            .find(filter, options)
+            .await
+            .map_err(|_| Status::InternalServerError)?
+            .try_collect()
+            .await
+            .map_err(|_| Status::InternalServerError)?;
+
+        Ok(documents.into_iter().map(GameRecord::from).collect())
+    }
+
+    /// Runs an aggregation on the `games` collection, reading each result as a `T`.
+    async fn aggregate<T: DeserializeOwned>(
+        &self,
+        pipeline: Vec<Document>,
+    ) -> Result<Vec<T>, Status> {
+        let documents: Vec<Document> = self
+            .games
+            .aggregate(pipeline, None)
+            .await
+            .map_err(|_| Status::InternalServerError)?
+            .try_collect()
+            .await
+            .map_err(|_| Status::InternalServerError)?;
+
+        documents
+            .into_iter()
+            .map(|document| bson::from_document(document).map_err(|_| Status::InternalServerError))
+            .collect()
+    }
+
+    async fn find_stats_where(&self, filter: Document) -> Result<Vec<PlayerStats>, Status> {
+        self.stats
+            .find(filter, None)
+            .await
+            .map_err(|_| Status::InternalServerError)?
+            .try_collect()
+            .await
+            .map_err(|_| Status::InternalServerError)
+    }
+}
+
+#[rocket::async_trait]
+impl GameRepository for MongoGames {
+    async fn insert_game(&self, game: &ConnectGame) -> Result<(), Status> {
+        self.games
+            .insert_one(StoredGame::from(game.clone()), None)
+            .await
+            .map_err(|_| Status::InternalServerError)?;
+
+        Ok(())
+    }
+
+    async fn list_games(&self, filter: &GameFilter) -> Result<GamePage, Status> {
+        let limit = filter.limit.unwrap_or(DEFAULT_LIMIT);
+
+        // A cursor has to be a game that exists, as it does for the memory backend
+        if let Some(cursor) = &filter.cursor {
+            let id = ObjectId::parse_str(cursor).map_err(|_| Status::BadRequest)?;
+            let found = self
+                .games
+                .count_documents(doc! {"_id": id}, None)
+                .await
+                .map_err(|_| Status::InternalServerError)?;
+
+            if found == 0 {
+                return Err(Status::BadRequest);
+            }
+        }
+
+        let options = FindOptions::builder()
+            .sort(doc! {"_id": if filter.ascending { 1 } else { -1 }})
+            // One extra game tells us whether there is another page
+            .limit(i64::from(limit) + 1)
+            .build();
+
+        let mut games = self.find_games(filter_document(filter)?, options).await?;
+
+        let next_cursor = if games.len() > limit as usize {
+            games.truncate(limit as usize);
+            games.last().map(|game| game.id.clone())
+        } else {
+            None
+        };
+
+        Ok(GamePage { games, next_cursor })
+    }
+
+    async fn search_games(
+        &self,
+        player: Option<&str>,
+        game_type: Option<&GameType>,
+        limit: i64,
+    ) -> Result<Vec<GameRecord>, Status> {
+        let mut filter = Document::new();
+
+        if let Some(player) = player {
+            let regex = contains(player);
+            filter.insert(
+                "$or",
+                vec![doc! {"player1": &regex}, doc! {"player2": &regex}],
+            );
+        }
+
+        if let Some(game_type) = game_type {
+            filter.insert("game_type", game_type_bson(game_type));
+        }
+
+        let options = FindOptions::builder()
+            .sort(doc! {"_id": -1})
+            .limit(limit)
+            .build();
+
+        self.find_games(filter, options).await
+    }
+
+    async fn select_games(&self, selection: &GameSelection<'_>) -> Result<Vec<GameRecord>, Status> {
+        self.find_games(selection_document(selection), oldest_first())
+            .await
+    }
+
+    async fn player_games(
+        &self,
+        username: &str,
+        skip: u64,
+        limit: u32,
+    ) -> Result<(Vec<GameRecord>, u64), Status> {
+        let total = self
+            .games
+            .count_documents(played_by(username), None)
+            .await
+            .map_err(|_| Status::InternalServerError)?;
+
+        let options = FindOptions::builder()
+            .sort(doc! {"ended_at": -1, "_id": -1})
+            .skip(skip)
+            .limit(i64::from(limit))
+            .build();
+
+        Ok((self.find_games(played_by(username), options).await?, total))
+    }
+
+    async fn head_to_head(&self, username: &str) -> Result<Vec<HeadToHead>, Status> {
+        let won = doc! {"$eq": ["$winner", username]};
+
+        self.aggregate(vec![
+            doc! {"$match": played_by(username)},
+            doc! {"$group": {
+                "_id": {"$cond": [{"$eq": ["$player1", username]}, "$player2", "$player1"]},
+                "wins": {"$sum": {"$cond": [&won, 1, 0]}},
+                "losses": {"$sum": {"$cond": [&won, 0, 1]}},
+            }},
+            doc! {"$project": {"_id": 0, "opponent": "$_id", "wins": 1, "losses": 1}},
+        ])
+        .await
+    }
+
+    async fn monthly_records(&self, username: &str) -> Result<Vec<MonthlyRecord>, Status> {
+        let won = doc! {"$eq": ["$winner", username]};
+
+        self.aggregate(vec![
+            doc! {"$match": played_by(username)},
+            doc! {"$group": {
+                "_id": {"$dateToString": {"format": "%Y-%m", "date": "$ended_at"}},
+                "wins": {"$sum": {"$cond": [&won, 1, 0]}},
+                "losses": {"$sum": {"$cond": [&won, 0, 1]}},
+            }},
+            doc! {"$sort": {"_id": 1}},
+            doc! {"$project": {"_id": 0, "month": "$_id", "wins": 1, "losses": 1}},
+        ])
+        .await
+    }
+
+    async fn find_game(&self, id: &str) -> Result<Option<ConnectGame>, Status> {
+        let id = ObjectId::parse_str(id).map_err(|_| Status::BadRequest)?;
+
+        let game = self
+            .games
+            .find_one(doc! {"_id": id}, None)
+            .await
+            .map_err(|_| Status::InternalServerError)?;
+
+        Ok(game.map(ConnectGame::from))
+    }
+
+    async fn delete_game(&self, id: &str) -> Result<Option<ConnectGame>, Status> {
+        let id = ObjectId::parse_str(id).map_err(|_| Status::BadRequest)?;
+
+        let deleted = self
+            .games
+            .find_one_and_delete(doc! {"_id": id}, None)
+            .await
+            .map_err(|_| Status::InternalServerError)?;
+
+        Ok(deleted.map(ConnectGame::from))
+    }
+
+    async fn delete_player_games(&self, username: &str) -> Result<Vec<ConnectGame>, Status> {
+        let deleted: Vec<StoredGame> = self
+            .games
+            .find(played_by(username), None)
+            .await
+            .map_err(|_| Status::InternalServerError)?
+            .try_collect()
+            .await
+            .map_err(|_| Status::InternalServerError)?;
+
+        self.games
+            .delete_many(played_by(username), None)
+            .await
+            .map_err(|_| Status::InternalServerError)?;
+
+        Ok(deleted.into_iter().map(ConnectGame::from).collect())
+    }
+
+    async fn rename_player(&self, from: &str, to: &str) -> Result<(), Status> {
+        for field in ["player1", "player2", "winner"] {
+            self.games
+                .update_many(doc! {field: from}, doc! {"$set": {field: to}}, None)
+                .await
+                .map_err(|_| Status::InternalServerError)?;
+        }
+
+        Ok(())
+    }
+
+    async fn visible_stats(&self, game_type: &GameType) -> Result<Vec<PlayerStats>, Status> {
+        self.find_stats_where(doc! {"game_type": game_type_bson(game_type), "hidden": false})
+            .await
+    }
+
+    async fn player_stats(&self, username: &str) -> Result<Vec<PlayerStats>, Status> {
+        self.find_stats_where(doc! {"username": username}).await
+    }
+
+    async fn update_stats(
+        &self,
+        new: PlayerStats,
+        update: &(dyn for<'s> Fn(&'s mut PlayerStats) + Send + Sync),
+    ) -> Result<(), Status> {
+        let filter = doc! {"game_type": game_type_bson(&new.game_type), "username": &new.username};
+
+        // Each attempt only writes if no other update got in since its read, or else tries again
+        loop {
+            let existing = self
+                .stats
+                .find_one(filter.clone(), None)
+                .await
+                .map_err(|_| Status::InternalServerError)?;
+
+            let mut stats = match existing {
+                Some(stats) => stats,
+                None => {
+                    let mut stats = new.clone();
+                    update(&mut stats);
+
+                    // The unique index on the game type and username turns away a racing insert
+                    match self.stats.insert_one(&stats, None).await {
+                        Ok(_) => return Ok(()),
+                        Err(err) if is_duplicate_key(&err) => continue,
+                        Err(_) => return Err(Status::InternalServerError),
+                    }
+                }
+            };
+
+            let mut unchanged = filter.clone();
+            unchanged.insert(
+                "revision",
+                match stats.revision {
+                    // Stats saved before revisions were counted have none
+                    0 => doc! {"$in": [0, Bson::Null]},
+                    revision => doc! {"$eq": revision},
+                },
+            );
+
+            update(&mut stats);
+            stats.revision += 1;
+
+            let result = self
+                .stats
+                .replace_one(unchanged, &stats, None)
+                .await
+                .map_err(|_| Status::InternalServerError)?;
+
+            if result.matched_count > 0 {
+                return Ok(());
+            }
+        }
+    }
+
+    async fn save_stats(&self, stats: &PlayerStats) -> Result<(), Status> {
+        self.stats
+            .replace_one(
+                doc! {"game_type": game_type_bson(&stats.game_type), "username": &stats.username},
+                stats,
+                ReplaceOptions::builder().upsert(true).build(),
+            )
+            .await
+            .map_err(|_| Status::InternalServerError)?;
+
+        Ok(())
+    }
+
+    async fn delete_stats(&self, game_type: &GameType, username: &str) -> Result<(), Status> {
+        self.stats
+            .delete_one(
+                doc! {"game_type": game_type_bson(game_type), "username": username},
+                None,
+            )
+            .await
+            .map_err(|_| Status::InternalServerError)?;
+
+        Ok(())
+    }
+
+    async fn set_stats_hidden(&self, username: &str, hidden: bool) -> Result<(), Status> {
+        self.stats
+            .update_many(
+                doc! {"username": username},
+                doc! {"$set": {"hidden": hidden}},
+                None,
+            )
+            .await
+            .map_err(|_| Status::InternalServerError)?;
+
+        Ok(())
+    }
+
+    async fn replace_all_stats(&self, stats: Vec<PlayerStats>) -> Result<(), Status> {
+        self.stats
+            .delete_many(doc! {}, None)
+            .await
+            .map_err(|_| Status::InternalServerError)?;
+
+        if !stats.is_empty() {
+            self.stats
+                .insert_many(stats, None)
+                .await
+                .map_err(|_| Status::InternalServerError)?;
+        }
+
+        Ok(())
+    }
+}
@@ -0,0 +1,1227 @@
+use std::collections::HashMap;
+
+use chrono::{Duration, NaiveDate, Utc};
+use client::ai::{self, Engine};
+use client::notation::{GameNotation, Ply, Position};
+use client::{
+    AccountDeletion, AuditEntry, ConnectGame, CpuLevel, FirstMove, GamePage, GameRecord,
+    GameRetention, GameType, HeadToHead, LeaderboardPage, MonthlyRecord, PasswordChange, Profile,
+    Role, Session, TurnRecord, User, UsernameChange, ANONYMOUS_PLAYER,
+};
+use rocket::http::{ContentType, Header, Method, Status};
+use rocket::local::asynchronous::Client;
+
+use super::game;
+use crate::repository::Storage;
+use crate::rocket;
+use crate::{games, users};
+
+const ORIGIN: &str = "http://allowed.example";
+
+async fn client() -> Client {
+    let figment = rocket::Config::figment()
+        .merge(("storage", "memory"))
+        .merge(("cors.allowed_origins", [ORIGIN]))
+        .merge(("cors.allowed_methods", ["GET", "POST", "OPTIONS"]))
+        .merge(("cors.allowed_headers", ["Content-Type"]));
+
+    Client::tracked(rocket().configure(figment))
+        .await
+        .expect("valid rocket instance")
+}
+
+fn credentials(username: &str, password: &str) -> User {
+    User {
+        username: username.to_string(),
+        password: password.to_string(),
+    }
+}
+
+async fn register(client: &Client, username: &str, password: &str) -> Session {
+    let response = client
+        .post("/users/register")
+        .json(&credentials(username, password))
+        .dispatch()
+        .await;
+
+    assert_eq!(response.status(), Status::Ok);
+    response.into_json().await.expect("a session")
+}
+
+async fn create(client: &Client, game: &ConnectGame) {
+    let response = client.post("/games/create").json(game).dispatch().await;
+
+    assert_eq!(response.status(), Status::Ok);
+}
+
+async fn leaderboard(client: &Client, uri: &str) -> Vec<(String, u32, u32)> {
+    let response = client.get(uri.to_string()).dispatch().await;
+    assert_eq!(response.status(), Status::Ok);
+
+    let page: LeaderboardPage = response.into_json().await.expect("a leaderboard page");
+    page.entries
+        .into_iter()
+        .map(|entry| (entry.username, entry.wins, entry.losses))
+        .collect()
+}
+
+#[rocket::async_test]
+async fn register_then_login() {
+    let client = client().await;
+
+    let registered = register(&client, "alice", "hunter2").await;
+    assert_eq!(registered.username, "alice");
+    assert!(!registered.guest);
+
+    let response = client
+        .post("/users/login")
+        .json(&credentials("alice", "hunter2"))
+        .dispatch()
+        .await;
+    assert_eq!(response.status(), Status::Ok);
+
+    let session: Session = response.into_json().await.expect("a session");
+    assert_eq!(session.username, "alice");
+    assert_ne!(session.token, registered.token);
+}
+
+#[rocket::async_test]
+async fn register_rejects_taken_username() {
+    let client = client().await;
+    register(&client, "alice", "hunter2").await;
+
+    let response = client
+        .post("/users/register")
+        .json(&credentials("alice", "something else"))
+        .dispatch()
+        .await;
+    assert_eq!(response.status(), Status::Conflict);
+
+    // The original password still works
+    let response = client
+        .post("/users/login")
+        .json(&credentials("alice", "hunter2"))
+        .dispatch()
+        .await;
+    assert_eq!(response.status(), Status::Ok);
+}
+
+fn bearer(session: &Session) -> Header<'static> {
+    Header::new("Authorization", format!("Bearer {}", session.token))
+}
+
+#[rocket::async_test]
+async fn reserved_usernames_cannot_be_taken() {
+    let client = client().await;
+    let alice = register(&client, "alice", "hunter2").await;
+
+    for username in ["", "[deleted]", "CPU - Hard", "Guest-abc123", "guest-bob"] {
+        let response = client
+            .post("/users/register")
+            .json(&credentials(username, "hunter2"))
+            .dispatch()
+            .await;
+        assert_eq!(response.status(), Status::BadRequest, "{:?}", username);
+
+        let response = client
+            .put("/users/me/username")
+            .header(bearer(&alice))
+            .json(&UsernameChange {
+                username: username.to_string(),
+            })
+            .dispatch()
+            .await;
+        assert_eq!(response.status(), Status::BadRequest, "{:?}", username);
+    }
+}
+
+#[rocket::async_test]
+async fn login_with_wrong_password_or_unknown_user() {
+    let client = client().await;
+    register(&client, "alice", "hunter2").await;
+
+    let response = client
+        .post("/users/login")
+        .json(&credentials("alice", "wrong"))
+        .dispatch()
+        .await;
+    assert_eq!(response.status(), Status::Unauthorized);
+
+    let response = client
+        .post("/users/login")
+        .json(&credentials("nobody", "hunter2"))
+        .dispatch()
+        .await;
+    assert_eq!(response.status(), Status::NotFound);
+}
+
+#[rocket::async_test]
+async fn guests_cannot_log_in_with_a_password() {
+    let client = client().await;
+
+    let response = client.post("/users/guest").dispatch().await;
+    let guest: Session = response.into_json().await.expect("a session");
+
+    let response = client
+        .post("/users/login")
+        .json(&credentials(&guest.username, ""))
+        .dispatch()
+        .await;
+    assert_eq!(response.status(), Status::Unauthorized);
+}
+
+async fn login(client: &Client, username: &str, password: &str) -> Option<Session> {
+    let response = client
+        .post("/users/login")
+        .json(&credentials(username, password))
+        .dispatch()
+        .await;
+
+    response.into_json().await
+}
+
+async fn logout(client: &Client, session: &Session) -> Status {
+    let response = client
+        .post("/users/logout")
+        .header(bearer(session))
+        .dispatch()
+        .await;
+
+    response.status()
+}
+
+#[rocket::async_test]
+async fn logging_out_ends_only_that_session() {
+    let client = client().await;
+    let first = register(&client, "alice", "hunter2").await;
+    let second = login(&client, "alice", "hunter2").await.unwrap();
+
+    assert_eq!(logout(&client, &first).await, Status::Ok);
+    assert_eq!(logout(&client, &first).await, Status::Unauthorized);
+    assert_eq!(logout(&client, &second).await, Status::Ok);
+}
+
+#[rocket::async_test]
+async fn expired_sessions_are_refused() {
+    let client = client().await;
+    register(&client, "alice", "hunter2").await;
+
+    let session = Session {
+        username: "alice".to_string(),
+        token: "expired".to_string(),
+        guest: false,
+        role: Role::Player,
+    };
+    let storage = client.rocket().state::<Storage>().unwrap();
+    storage
+        .users
+        .insert_session(&session, Utc::now() - Duration::seconds(1))
+        .await
+        .unwrap();
+
+    assert_eq!(logout(&client, &session).await, Status::Unauthorized);
+}
+
+#[rocket::async_test]
+async fn changing_password_needs_the_old_one_and_signs_out_other_sessions() {
+    let client = client().await;
+    let current = register(&client, "alice", "hunter2").await;
+    let other = login(&client, "alice", "hunter2").await.unwrap();
+
+    for (old_password, status) in [("wrong", Status::Unauthorized), ("hunter2", Status::Ok)] {
+        let response = client
+            .put("/users/me/password")
+            .header(bearer(&current))
+            .json(&PasswordChange {
+                old_password: old_password.to_string(),
+                new_password: "correct horse".to_string(),
+            })
+            .dispatch()
+            .await;
+        assert_eq!(response.status(), status);
+    }
+
+    assert!(login(&client, "alice", "hunter2").await.is_none());
+    assert!(login(&client, "alice", "correct horse").await.is_some());
+    assert_eq!(logout(&client, &other).await, Status::Unauthorized);
+    assert_eq!(logout(&client, &current).await, Status::Ok);
+}
+
+#[rocket::async_test]
+async fn renaming_carries_stats_games_and_sessions_along() {
+    let client = client().await;
+    let alice = register(&client, "alice", "hunter2").await;
+
+    create(&client, &game(GameType::Connect4, "alice", "bob", "alice")).await;
+    create(&client, &game(GameType::Connect4, "alice", "bob", "bob")).await;
+    create(
+        &client,
+        &game(GameType::Connect4, "carol", "alice", "alice"),
+    )
+    .await;
+
+    let response = client
+        .put("/users/me/username")
+        .header(bearer(&alice))
+        .json(&UsernameChange {
+            username: "alicia".to_string(),
+        })
+        .dispatch()
+        .await;
+    assert_eq!(response.status(), Status::Ok);
+
+    assert_eq!(
+        leaderboard(&client, "/leaderboard/connect4").await,
+        [
+            ("alicia".to_string(), 2, 1),
+            ("bob".to_string(), 1, 1),
+            ("carol".to_string(), 0, 1),
+        ]
+    );
+
+    let response = client.get("/games/all?player=alicia").dispatch().await;
+    let page: GamePage = response.into_json().await.expect("a game page");
+    assert_eq!(page.games.len(), 3);
+
+    assert!(login(&client, "alicia", "hunter2").await.is_some());
+    assert_eq!(logout(&client, &alice).await, Status::Ok);
+}
+
+async fn delete_account(
+    client: &Client,
+    session: &Session,
+    password: &str,
+    games: GameRetention,
+) -> Status {
+    let response = client
+        .delete("/users/me")
+        .header(bearer(session))
+        .json(&AccountDeletion {
+            password: password.to_string(),
+            games,
+        })
+        .dispatch()
+        .await;
+
+    response.status()
+}
+
+#[rocket::async_test]
+async fn deleting_an_account_needs_its_password_and_anonymizes_or_purges_its_games() {
+    let client = client().await;
+    let alice = register(&client, "alice", "hunter2").await;
+    let bob = register(&client, "bob", "hunter2").await;
+
+    create(&client, &game(GameType::Connect4, "alice", "bob", "alice")).await;
+    create(
+        &client,
+        &game(GameType::Connect4, "carol", "alice", "carol"),
+    )
+    .await;
+
+    let status = delete_account(&client, &alice, "wrong", GameRetention::Anonymize).await;
+    assert_eq!(status, Status::Unauthorized);
+    assert!(login(&client, "alice", "hunter2").await.is_some());
+
+    // Opponents keep their results against an anonymized account
+    let status = delete_account(&client, &alice, "hunter2", GameRetention::Anonymize).await;
+    assert_eq!(status, Status::Ok);
+    assert_eq!(
+        leaderboard(&client, "/leaderboard/connect4").await,
+        [("carol".to_string(), 1, 0), ("bob".to_string(), 0, 1)]
+    );
+    assert_eq!(logout(&client, &alice).await, Status::Unauthorized);
+
+    // and lose the ones against a purged account
+    let status = delete_account(&client, &bob, "hunter2", GameRetention::Purge).await;
+    assert_eq!(status, Status::Ok);
+    assert_eq!(
+        leaderboard(&client, "/leaderboard/connect4").await,
+        [("carol".to_string(), 1, 0)]
+    );
+
+    let response = client.get("/games/all").dispatch().await;
+    let page: GamePage = response.into_json().await.expect("a game page");
+    assert_eq!(
+        page.games
+            .iter()
+            .map(|record| (record.game.player1.as_str(), record.game.player2.as_str()))
+            .collect::<Vec<_>>(),
+        [("carol", ANONYMOUS_PLAYER)]
+    );
+}
+
+#[rocket::async_test]
+async fn created_games_are_listed_newest_first() {
+    let client = client().await;
+
+    create(&client, &game(GameType::Connect4, "alice", "bob", "alice")).await;
+    create(
+        &client,
+        &game(GameType::TootAndOtto, "bob", "carol", "carol"),
+    )
+    .await;
+    create(
+        &client,
+        &game(GameType::Connect4, "carol", "alice", "carol"),
+    )
+    .await;
+
+    let response = client.get("/games/all").dispatch().await;
+    assert_eq!(response.status(), Status::Ok);
+
+    let page: GamePage = response.into_json().await.expect("a page of games");
+    let winners: Vec<&str> = page
+        .games
+        .iter()
+        .map(|record| record.game.winner.as_str())
+        .collect();
+    assert_eq!(winners, ["carol", "carol", "alice"]);
+    assert_eq!(page.next_cursor, None);
+}
+
+#[rocket::async_test]
+async fn games_listing_filters_and_pages() {
+    let client = client().await;
+
+    for winner in ["alice", "bob", "alice", "alice"] {
+        create(&client, &game(GameType::Connect4, "alice", "bob", winner)).await;
+    }
+    create(&client, &game(GameType::Connect4, "bob", "carol", "bob")).await;
+
+    let response = client
+        .get("/games/all?player=alice&result=win&limit=2")
+        .dispatch()
+        .await;
+    assert_eq!(response.status(), Status::Ok);
+
+    let first: GamePage = response.into_json().await.expect("a page of games");
+    assert_eq!(first.games.len(), 2);
+    let cursor = first.next_cursor.expect("a second page");
+
+    let response = client
+        .get(format!(
+            "/games/all?player=alice&result=win&limit=2&cursor={}",
+            cursor
+        ))
+        .dispatch()
+        .await;
+    let second: GamePage = response.into_json().await.expect("a page of games");
+    assert_eq!(second.games.len(), 1);
+    assert_eq!(second.next_cursor, None);
+
+    assert!(first
+        .games
+        .iter()
+        .chain(&second.games)
+        .all(|record| record.game.winner == "alice"));
+
+    // A result only makes sense from a player's side
+    let response = client.get("/games/all?result=win").dispatch().await;
+    assert_eq!(response.status(), Status::BadRequest);
+}
+
+#[rocket::async_test]
+async fn games_listing_rejects_unknown_cursors_and_dates_without_a_next_day() {
+    let client = client().await;
+    create(&client, &game(GameType::Connect4, "alice", "bob", "alice")).await;
+
+    let last_day = NaiveDate::MAX
+        .format("%Y-%m-%d")
+        .to_string()
+        .replace('+', "%2B");
+
+    for uri in [
+        "/games/all?cursor=000000000000000000000000".to_string(),
+        "/games/all?cursor=not-an-id".to_string(),
+        format!("/games/all?to={}", last_day),
+    ] {
+        let response = client.get(uri.clone()).dispatch().await;
+        assert_eq!(response.status(), Status::BadRequest, "{}", uri);
+    }
+
+    let response = client.get("/games/all?to=2999-12-31").dispatch().await;
+    let page: GamePage = response.into_json().await.expect("a page of games");
+    assert_eq!(page.games.len(), 1);
+}
+
+#[rocket::async_test]
+async fn cpu_games_replay_from_their_seed() {
+    let client = client().await;
+    let (level, seed) = (CpuLevel::Medium, 42);
+
+    // Play the game the way the browser does, with random moves for the user and who
+    // opens drawn from the seed
+    let mut rng = ai::seeded_rng(seed.into());
+    let mut user_rng = ai::seeded_rng(7);
+    let user = ai::draw_user_side(&GameType::Connect4, &mut rng);
+    let user_first = FirstMove::Random.user_first(&mut rng);
+    let first = if user_first { user } else { user.other() };
+    let mut position = Position::new(GameType::Connect4, 6, 7, first);
+    let mut plies = vec![];
+
+    while !position.is_over() {
+        let (col, piece) = if position.to_move == user {
+            Engine::Random.choose_move(
+                &mut position.board,
+                &GameType::Connect4,
+                user,
+                &mut user_rng,
+            )
+        } else {
+            ai::cpu_move(
+                &mut position.board,
+                &GameType::Connect4,
+                user.other(),
+                level,
+                &mut rng,
+            )
+        };
+        position.play(Ply { col, piece }).unwrap();
+        plies.push(Ply { col, piece });
+    }
+
+    let notation = GameNotation {
+        game_type: GameType::Connect4,
+        rows: 6,
+        cols: 7,
+        first,
+        plies,
+    };
+    let winner = match position.wins() {
+        (true, false) => "alice",
+        _ => level.player_name(),
+    };
+    let mut saved = game(GameType::Connect4, "alice", level.player_name(), winner);
+    saved.seed = Some(seed);
+    saved.notation = Some(notation.to_string());
+    saved.player1_first = Some(user_first);
+    saved.first_move = Some(FirstMove::Random);
+    create(&client, &saved).await;
+
+    let page: GamePage = client
+        .get("/games/all")
+        .dispatch()
+        .await
+        .into_json()
+        .await
+        .unwrap();
+    let storage = client.rocket().state::<Storage>().unwrap();
+    let found = storage
+        .games
+        .find_game(&page.games[0].id)
+        .await
+        .unwrap()
+        .unwrap();
+    assert_eq!(
+        found,
+        ConnectGame {
+            ended_at: found.ended_at,
+            ..saved
+        }
+    );
+
+    let first_move = found.first_move.unwrap();
+    let recorded: GameNotation = found.notation.unwrap().parse().unwrap();
+    let replayed = ai::replay(&recorded, level, first_move, seed).unwrap();
+    assert_eq!(replayed, position);
+
+    // Another seed makes other choices somewhere along the way
+    assert!(ai::replay(&recorded, level, first_move, seed + 1).is_err());
+}
+
+#[rocket::async_test]
+async fn leaderboard_orders_by_wins_then_losses_then_username() {
+    let client = client().await;
+
+    let games = [
+        ("dave", "erin", "dave"),
+        ("dave", "erin", "dave"),
+        ("bob", "erin", "bob"),
+        ("bob", "erin", "bob"),
+        ("bob", "carol", "carol"),
+        ("alice", "erin", "alice"),
+        ("alice", "erin", "alice"),
+        ("alice", "carol", "carol"),
+    ];
+
+    for (player1, player2, winner) in games {
+        create(&client, &game(GameType::Connect4, player1, player2, winner)).await;
+    }
+
+    // Ties on wins go to whoever lost fewer, then alphabetically
+    let expected = [
+        ("carol", 2, 0),
+        ("dave", 2, 0),
+        ("alice", 2, 1),
+        ("bob", 2, 1),
+        ("erin", 0, 6),
+    ]
+    .map(|(username, wins, losses)| (username.to_string(), wins, losses));
+
+    assert_eq!(
+        leaderboard(&client, "/leaderboard/connect4").await,
+        expected
+    );
+
+    let mut reversed = expected.to_vec();
+    reversed.reverse();
+    assert_eq!(
+        leaderboard(&client, "/leaderboard/connect4?order=asc").await,
+        reversed
+    );
+
+    // Each game type has its own leaderboard
+    assert!(leaderboard(&client, "/leaderboard/tootandotto")
+        .await
+        .is_empty());
+}
+
+#[rocket::async_test]
+async fn leaderboard_counts_wins_losses_and_streaks_as_games_are_saved() {
+    let client = client().await;
+
+    for winner in [
+        "alice", "alice", "bob", "alice", "alice", "alice", "bob", "alice",
+    ] {
+        create(&client, &game(GameType::Connect4, "alice", "bob", winner)).await;
+    }
+
+    let response = client.get("/leaderboard/connect4").dispatch().await;
+    let page: LeaderboardPage = response.into_json().await.expect("a leaderboard page");
+
+    assert_eq!(
+        page.entries
+            .iter()
+            .map(|entry| (
+                entry.username.as_str(),
+                entry.wins,
+                entry.losses,
+                entry.current_streak,
+                entry.longest_streak
+            ))
+            .collect::<Vec<_>>(),
+        [("alice", 6, 2, 1, 3), ("bob", 2, 6, 0, 1)]
+    );
+}
+
+#[rocket::async_test]
+async fn leaderboard_rejects_pages_past_any_possible_end() {
+    let client = client().await;
+    create(&client, &game(GameType::Connect4, "alice", "bob", "alice")).await;
+
+    assert!(
+        leaderboard(&client, "/leaderboard/connect4?page=2&limit=100")
+            .await
+            .is_empty()
+    );
+
+    let response = client
+        .get("/leaderboard/connect4?page=4294967295&limit=100")
+        .dispatch()
+        .await;
+    assert_eq!(response.status(), Status::BadRequest);
+}
+
+#[rocket::async_test]
+async fn leaderboard_hides_guests() {
+    let client = client().await;
+
+    let response = client.post("/users/guest").dispatch().await;
+    let guest: Session = response.into_json().await.expect("a session");
+
+    create(
+        &client,
+        &game(
+            GameType::Connect4,
+            &guest.username,
+            "alice",
+            &guest.username,
+        ),
+    )
+    .await;
+
+    assert_eq!(
+        leaderboard(&client, "/leaderboard/connect4").await,
+        [("alice".to_string(), 0, 1)]
+    );
+}
+
+#[rocket::async_test]
+async fn leaderboard_hides_banned_users_until_they_are_unbanned() {
+    let client = client().await;
+    let admin = register_admin(&client, "root").await;
+    register(&client, "mallory", "hunter2").await;
+
+    create(
+        &client,
+        &game(GameType::Connect4, "mallory", "alice", "mallory"),
+    )
+    .await;
+
+    let everyone = [("mallory".to_string(), 1, 0), ("alice".to_string(), 0, 1)];
+    let alice_only = [("alice".to_string(), 0, 1)];
+
+    for (action, expected) in [("ban", &alice_only[..]), ("unban", &everyone[..])] {
+        let response = client
+            .post(format!("/admin/users/mallory/{}", action))
+            .header(bearer(&admin))
+            .dispatch()
+            .await;
+        assert_eq!(response.status(), Status::Ok);
+
+        // Both from the stored stats and when aggregating games
+        for uri in ["/leaderboard/connect4", "/leaderboard/connect4?board=6x7"] {
+            assert_eq!(leaderboard(&client, uri).await, expected, "{}", uri);
+        }
+    }
+}
+
+#[rocket::async_test]
+async fn leaderboard_filters_by_window_opponent_board_and_min_games() {
+    let client = client().await;
+    let easy = CpuLevel::Easy.player_name();
+    let long_ago = Utc::now() - Duration::days(40);
+
+    create(&client, &game(GameType::Connect4, "alice", "bob", "alice")).await;
+    create(&client, &game(GameType::Connect4, "alice", easy, "alice")).await;
+    create(
+        &client,
+        &ConnectGame {
+            rows: Some(5),
+            cols: Some(5),
+            ..game(GameType::Connect4, "bob", "alice", "bob")
+        },
+    )
+    .await;
+
+    // The server stamps games it's sent with the time they arrive
+    let storage = client.rocket().state::<Storage>().unwrap();
+    let old = ConnectGame {
+        started_at: long_ago - Duration::minutes(5),
+        ended_at: long_ago,
+        ..game(GameType::Connect4, "carol", "bob", "carol")
+    };
+    games::save_game(storage, &old).await.unwrap();
+
+    let cases = [
+        ("", vec![("alice", 2, 1), ("carol", 1, 0), ("bob", 1, 2)]),
+        ("window=month", vec![("alice", 2, 1), ("bob", 1, 1)]),
+        ("window=week", vec![("alice", 2, 1), ("bob", 1, 1)]),
+        (
+            "board=6x7",
+            vec![("alice", 2, 0), ("carol", 1, 0), ("bob", 0, 2)],
+        ),
+        ("board=5x5", vec![("bob", 1, 0), ("alice", 0, 1)]),
+        (
+            "opponent=human",
+            vec![("carol", 1, 0), ("alice", 1, 1), ("bob", 1, 2)],
+        ),
+        ("opponent=easy", vec![("alice", 1, 0)]),
+        ("opponent=hard", vec![]),
+        ("min_games=2", vec![("alice", 2, 1), ("bob", 1, 2)]),
+        ("min_games=3&board=6x7", vec![]),
+    ];
+
+    for (query, expected) in cases {
+        let uri = format!("/leaderboard/connect4?{}", query);
+        let expected: Vec<_> = expected
+            .into_iter()
+            .map(|(username, wins, losses)| (username.to_string(), wins, losses))
+            .collect();
+
+        let response = client.get(uri.clone()).dispatch().await;
+        assert_eq!(response.status(), Status::Ok, "{}", uri);
+        let page: LeaderboardPage = response.into_json().await.expect("a leaderboard page");
+        assert_eq!(page.total, expected.len() as u64, "{}", uri);
+        assert_eq!(leaderboard(&client, &uri).await, expected, "{}", uri);
+    }
+}
+
+#[rocket::async_test]
+async fn leaderboard_sorts_by_each_column_and_ranks_in_that_order() {
+    let client = client().await;
+
+    // Oldest first, a day apart, so streaks and last played dates differ
+    let played = [
+        ("alice", "zed", "alice", 10),
+        ("alice", "zed", "alice", 10),
+        ("bob", "alice", "bob", 30),
+        ("bob", "zed", "bob", 30),
+        ("zed", "carol", "zed", 20),
+    ];
+
+    let storage = client.rocket().state::<Storage>().unwrap();
+    for (days_ago, (player1, player2, winner, moves)) in (1..=5).rev().zip(played) {
+        let ended_at = Utc::now() - Duration::days(days_ago);
+        let game = ConnectGame {
+            started_at: ended_at - Duration::minutes(5),
+            ended_at,
+            moves: Some(moves),
+            ..game(GameType::Connect4, player1, player2, winner)
+        };
+        games::save_game(storage, &game).await.unwrap();
+    }
+
+    // Largest first, ties going to the better record and then alphabetically
+    let cases = [
+        ("wins", ["bob", "alice", "zed", "carol"]),
+        ("losses", ["zed", "alice", "carol", "bob"]),
+        ("games", ["zed", "alice", "bob", "carol"]),
+        ("win_percentage", ["bob", "alice", "zed", "carol"]),
+        ("current_streak", ["bob", "zed", "alice", "carol"]),
+        ("longest_streak", ["bob", "alice", "zed", "carol"]),
+        ("average_moves", ["bob", "carol", "zed", "alice"]),
+        ("last_played", ["zed", "carol", "bob", "alice"]),
+        ("username", ["alice", "bob", "carol", "zed"]),
+    ];
+
+    for (sort, expected) in cases {
+        let uri = format!("/leaderboard/connect4?sort={}", sort);
+        let usernames: Vec<String> = leaderboard(&client, &uri)
+            .await
+            .into_iter()
+            .map(|(username, _, _)| username)
+            .collect();
+        assert_eq!(usernames, expected, "{}", uri);
+    }
+
+    // Ranks follow the requested order across pages, and the total counts every page
+    let response = client
+        .get("/leaderboard/connect4?sort=games&page=2&limit=2")
+        .dispatch()
+        .await;
+    let page: LeaderboardPage = response.into_json().await.expect("a leaderboard page");
+    assert_eq!(page.total, 4);
+    assert_eq!(
+        page.entries
+            .iter()
+            .map(|entry| (entry.rank, entry.username.as_str(), entry.games))
+            .collect::<Vec<_>>(),
+        [(3, "bob", 2), (4, "carol", 1)]
+    );
+
+    let response = client
+        .get("/leaderboard/connect4?sort=games&order=asc&limit=1")
+        .dispatch()
+        .await;
+    let page: LeaderboardPage = response.into_json().await.expect("a leaderboard page");
+    assert_eq!(page.total, 4);
+    assert_eq!(
+        page.entries
+            .iter()
+            .map(|entry| (entry.rank, entry.username.as_str()))
+            .collect::<Vec<_>>(),
+        [(1, "carol")]
+    );
+}
+
+/// Registers `username` and makes them an admin.
+async fn register_admin(client: &Client, username: &str) -> Session {
+    let session = register(client, username, "hunter2").await;
+
+    let storage = client.rocket().state::<Storage>().unwrap();
+    storage.users.set_role(username, Role::Admin).await.unwrap();
+
+    session
+}
+
+#[rocket::async_test]
+async fn admin_routes_refuse_anonymous_and_non_admin_callers() {
+    let client = client().await;
+    let player = register(&client, "alice", "hunter2").await;
+    register(&client, "bob", "hunter2").await;
+
+    let routes = [
+        (Method::Get, "/admin/games"),
+        (Method::Delete, "/admin/games/000000000000000000000000"),
+        (Method::Get, "/admin/users"),
+        (Method::Post, "/admin/users/bob/ban"),
+        (Method::Post, "/admin/users/bob/unban"),
+        (Method::Post, "/admin/leaderboard/recompute"),
+        (Method::Get, "/admin/audit"),
+    ];
+
+    for (method, uri) in routes {
+        let response = client.req(method, uri).dispatch().await;
+        assert_eq!(
+            response.status(),
+            Status::Unauthorized,
+            "{} {}",
+            method,
+            uri
+        );
+
+        let response = client
+            .req(method, uri)
+            .header(bearer(&player))
+            .dispatch()
+            .await;
+        assert_eq!(response.status(), Status::Forbidden, "{} {}", method, uri);
+    }
+
+    // None of them went through
+    let storage = client.rocket().state::<Storage>().unwrap();
+    assert!(
+        !storage
+            .users
+            .find_user("bob")
+            .await
+            .unwrap()
+            .unwrap()
+            .banned
+    );
+    assert!(storage.users.audit_log(100).await.unwrap().is_empty());
+}
+
+#[rocket::async_test]
+async fn banning_signs_a_user_out_until_they_are_unbanned() {
+    let client = client().await;
+    let admin = register_admin(&client, "root").await;
+    let mallory = register(&client, "mallory", "hunter2").await;
+
+    let ban = |action: &str, username: &str| {
+        client
+            .post(format!("/admin/users/{}/{}", username, action))
+            .header(bearer(&admin))
+            .dispatch()
+    };
+
+    assert_eq!(ban("ban", "root").await.status(), Status::BadRequest);
+    assert_eq!(ban("ban", "nobody").await.status(), Status::NotFound);
+
+    assert_eq!(ban("ban", "mallory").await.status(), Status::Ok);
+    assert_eq!(logout(&client, &mallory).await, Status::Unauthorized);
+
+    let response = client
+        .post("/users/login")
+        .json(&credentials("mallory", "hunter2"))
+        .dispatch()
+        .await;
+    assert_eq!(response.status(), Status::Forbidden);
+
+    assert_eq!(ban("unban", "mallory").await.status(), Status::Ok);
+    assert!(login(&client, "mallory", "hunter2").await.is_some());
+
+    let response = client
+        .get("/admin/audit")
+        .header(bearer(&admin))
+        .dispatch()
+        .await;
+    let entries: Vec<AuditEntry> = response.into_json().await.expect("audit entries");
+    assert_eq!(
+        entries
+            .iter()
+            .map(|entry| (
+                entry.admin.as_str(),
+                entry.action.as_str(),
+                entry.target.as_str()
+            ))
+            .collect::<Vec<_>>(),
+        [("root", "unban", "mallory"), ("root", "ban", "mallory")]
+    );
+}
+
+#[rocket::async_test]
+async fn admin_listings_reject_limits_below_one() {
+    let client = client().await;
+    let admin = register_admin(&client, "root").await;
+
+    for uri in ["/admin/games", "/admin/audit"] {
+        for limit in ["-1", "0"] {
+            let response = client
+                .get(format!("{}?limit={}", uri, limit))
+                .header(bearer(&admin))
+                .dispatch()
+                .await;
+            assert_eq!(response.status(), Status::BadRequest, "{} {}", uri, limit);
+        }
+    }
+
+    create(&client, &game(GameType::Connect4, "root", "bob", "root")).await;
+    create(&client, &game(GameType::Connect4, "root", "bob", "bob")).await;
+
+    let response = client
+        .get("/admin/games?limit=1")
+        .header(bearer(&admin))
+        .dispatch()
+        .await;
+    let games: Vec<GameRecord> = response.into_json().await.expect("games");
+    assert_eq!(games.len(), 1);
+}
+
+#[rocket::async_test]
+async fn admin_routes_name_game_types_by_slug_and_audit_only_changes() {
+    let client = client().await;
+    let admin = register_admin(&client, "root").await;
+
+    create(&client, &game(GameType::Connect4, "alice", "bob", "alice")).await;
+    create(&client, &game(GameType::TootAndOtto, "alice", "bob", "bob")).await;
+
+    let response = client
+        .get("/admin/games?game_type=tootandotto")
+        .header(bearer(&admin))
+        .dispatch()
+        .await;
+    let games: Vec<GameRecord> = response.into_json().await.expect("games");
+    assert_eq!(
+        games
+            .iter()
+            .map(|record| &record.game.game_type)
+            .collect::<Vec<_>>(),
+        [&GameType::TootAndOtto]
+    );
+
+    let response = client
+        .get("/admin/games?game_type=TootAndOtto")
+        .header(bearer(&admin))
+        .dispatch()
+        .await;
+    assert_eq!(response.status(), Status::BadRequest);
+
+    let response = client
+        .get("/admin/users?search=ali")
+        .header(bearer(&admin))
+        .dispatch()
+        .await;
+    assert_eq!(response.status(), Status::Ok);
+
+    let response = client
+        .post("/admin/leaderboard/recompute")
+        .header(bearer(&admin))
+        .dispatch()
+        .await;
+    let leaderboards: HashMap<String, LeaderboardPage> =
+        response.into_json().await.expect("leaderboards");
+    let mut slugs: Vec<_> = leaderboards.keys().map(String::as_str).collect();
+    slugs.sort();
+    assert_eq!(slugs, ["connect4", "tootandotto"]);
+
+    // Searching changes nothing, so only the recompute is recorded
+    let response = client
+        .get("/admin/audit")
+        .header(bearer(&admin))
+        .dispatch()
+        .await;
+    let entries: Vec<AuditEntry> = response.into_json().await.expect("audit entries");
+    assert_eq!(
+        entries
+            .iter()
+            .map(|entry| entry.action.as_str())
+            .collect::<Vec<_>>(),
+        ["recompute_leaderboards"]
+    );
+}
+
+#[rocket::async_test]
+async fn expiring_guests_deletes_them_and_anonymizes_their_games() {
+    let client = client().await;
+
+    let response = client.post("/users/guest").dispatch().await;
+    let guest: Session = response.into_json().await.expect("a session");
+
+    create(
+        &client,
+        &game(GameType::Connect4, &guest.username, "alice", "alice"),
+    )
+    .await;
+
+    let storage = client.rocket().state::<Storage>().unwrap();
+
+    let expired = users::expire_guests(storage, Utc::now() - Duration::days(1))
+        .await
+        .unwrap();
+    assert!(expired.is_empty());
+
+    let expired = users::expire_guests(storage, Utc::now() + Duration::seconds(1))
+        .await
+        .unwrap();
+    assert_eq!(expired, vec![guest.username.clone()]);
+    assert!(storage
+        .users
+        .find_user(&guest.username)
+        .await
+        .unwrap()
+        .is_none());
+
+    // Their opponents keep the result
+    assert_eq!(
+        leaderboard(&client, "/leaderboard/connect4").await,
+        [("alice".to_string(), 1, 0)]
+    );
+    let games = storage.games.player_games(ANONYMOUS_PLAYER, 0, 10).await;
+    assert_eq!(games.unwrap().1, 1);
+}
+
+#[rocket::async_test]
+async fn leaderboard_splits_records_by_who_moved_first() {
+    let client = client().await;
+
+    let games = [
+        ("alice", "bob", "alice", Some(true)),
+        ("alice", "bob", "alice", Some(false)),
+        ("bob", "alice", "bob", Some(true)),
+        // Older games don't say, and only count towards the totals
+        ("alice", "bob", "bob", None),
+    ];
+
+    for (player1, player2, winner, player1_first) in games {
+        let mut game = game(GameType::Connect4, player1, player2, winner);
+        game.player1_first = player1_first;
+        create(&client, &game).await;
+    }
+
+    let response = client.get("/leaderboard/connect4").dispatch().await;
+    let page: LeaderboardPage = response.into_json().await.expect("a leaderboard page");
+    let records: Vec<_> = page
+        .entries
+        .into_iter()
+        .map(|entry| (entry.username, entry.moving_first, entry.moving_second))
+        .collect();
+
+    let record = |wins, losses| TurnRecord { wins, losses };
+    assert_eq!(
+        records,
+        [
+            ("alice".to_string(), record(1, 0), record(1, 1)),
+            ("bob".to_string(), record(1, 1), record(0, 1)),
+        ]
+    );
+}
+
+async fn profile(client: &Client, uri: &str) -> Profile {
+    let response = client.get(uri.to_string()).dispatch().await;
+    assert_eq!(response.status(), Status::Ok);
+
+    response.into_json().await.expect("a profile")
+}
+
+#[rocket::async_test]
+async fn profile_shows_stats_opponents_months_and_pages_of_games() {
+    let client = client().await;
+    register(&client, "alice", "hunter2").await;
+
+    let cpu = CpuLevel::Easy.player_name();
+    let games = [
+        game(GameType::Connect4, "alice", "bob", "alice"),
+        game(GameType::Connect4, "bob", "alice", "bob"),
+        game(GameType::TootAndOtto, "alice", cpu, "alice"),
+    ];
+    for game in &games {
+        create(&client, game).await;
+    }
+
+    let first = profile(&client, "/users/alice/profile?limit=2").await;
+    assert_eq!(first.total_games, 3);
+
+    let stats: Vec<_> = first
+        .stats
+        .iter()
+        .map(|entry| {
+            (
+                entry.game_type.clone(),
+                entry.stats.wins,
+                entry.stats.losses,
+            )
+        })
+        .collect();
+    assert_eq!(
+        stats,
+        [(GameType::Connect4, 1, 1), (GameType::TootAndOtto, 1, 0)]
+    );
+
+    // Most played opponent first
+    let record = |opponent: &str, wins, losses| HeadToHead {
+        opponent: opponent.to_string(),
+        wins,
+        losses,
+    };
+    assert_eq!(first.head_to_head, [record("bob", 1, 1), record(cpu, 1, 0)]);
+
+    assert_eq!(
+        first.history,
+        [MonthlyRecord {
+            month: Utc::now().format("%Y-%m").to_string(),
+            wins: 2,
+            losses: 1,
+        }]
+    );
+
+    // Newest first, a page at a time
+    // The server stamps `ended_at`, so games are told apart by who played
+    let players = |profile: &Profile| -> Vec<(String, String)> {
+        profile
+            .recent_games
+            .iter()
+            .map(|record| (record.game.player1.clone(), record.game.player2.clone()))
+            .collect()
+    };
+    let pair = |game: &ConnectGame| (game.player1.clone(), game.player2.clone());
+    assert_eq!(players(&first), [pair(&games[2]), pair(&games[1])]);
+
+    let second = profile(&client, "/users/alice/profile?limit=2&page=2").await;
+    assert_eq!(players(&second), [pair(&games[0])]);
+    assert_eq!(second.total_games, 3);
+}
+
+#[rocket::async_test]
+async fn profiles_of_unknown_guest_and_banned_users_are_not_found() {
+    let client = client().await;
+    register(&client, "alice", "hunter2").await;
+    register(&client, "mallory", "hunter2").await;
+
+    let response = client.post("/users/guest").dispatch().await;
+    let guest: Session = response.into_json().await.expect("a session");
+
+    let storage = client.rocket().state::<Storage>().unwrap();
+    storage.users.set_banned("mallory", true).await.unwrap();
+
+    for username in ["nobody", &guest.username, "mallory"] {
+        let response = client
+            .get(format!("/users/{}/profile", username))
+            .dispatch()
+            .await;
+        assert_eq!(response.status(), Status::NotFound, "{}", username);
+    }
+
+    let response = client
+        .get("/users/alice/profile?page=4294967295&limit=100")
+        .dispatch()
+        .await;
+    assert_eq!(response.status(), Status::BadRequest);
+}
+
+#[rocket::async_test]
+async fn preflight_for_api_routes() {
+    let client = client().await;
+
+    let response = client
+        .options("/games/create")
+        .header(Header::new("Origin", ORIGIN))
+        .header(Header::new("Access-Control-Request-Method", "POST"))
+        .header(Header::new(
+            "Access-Control-Request-Headers",
+            "content-type",
+        ))
+        .dispatch()
+        .await;
+    assert_eq!(response.status(), Status::NoContent);
+    assert_eq!(
+        response.headers().get_one("Access-Control-Allow-Origin"),
+        Some(ORIGIN)
+    );
+
+    // The actual request carries the header too
+    let response = client
+        .post("/users/register")
+        .header(Header::new("Origin", ORIGIN))
+        .header(ContentType::JSON)
+        .body(r#"{"username": "alice", "password": "hunter2"}"#)
+        .dispatch()
+        .await;
+    assert_eq!(response.status(), Status::Ok);
+    assert_eq!(
+        response.headers().get_one("Access-Control-Allow-Origin"),
+        Some(ORIGIN)
+    );
+}
@@ -0,0 +1,137 @@
+use rocket::http::{Header, Status};
+use rocket::local::blocking::Client;
+
+use crate::rocket;
+
+const ALLOWED: &str = "http://allowed.example";
+const DENIED: &str = "http://denied.example";
+
+fn client() -> Client {
+    let figment = rocket::Config::figment()
+        .merge(("storage", "memory"))
+        .merge(("cors.allowed_origins", [ALLOWED]))
+        .merge(("cors.allowed_methods", ["GET", "POST", "OPTIONS"]))
+        .merge(("cors.allowed_headers", ["Content-Type"]));
+
+    Client::tracked(rocket().configure(figment)).expect("valid rocket instance")
+}
+
+#[test]
+fn preflight_from_allowed_origin() {
+    let client = client();
+    let response = client
+        .options("/users/login")
+        .header(Header::new("Origin", ALLOWED))
+        .header(Header::new("Access-Control-Request-Method", "POST"))
+        .header(Header::new(
+            "Access-Control-Request-Headers",
+            "content-type",
+        ))
+        .dispatch();
+
+    assert_eq!(response.status(), Status::NoContent);
+    let headers = response.headers();
+    assert_eq!(
+        headers.get_one("Access-Control-Allow-Origin"),
+        Some(ALLOWED)
+    );
+    assert_eq!(
+        headers.get_one("Access-Control-Allow-Methods"),
+        Some("GET, POST, OPTIONS")
+    );
+    assert_eq!(
+        headers.get_one("Access-Control-Allow-Headers"),
+        Some("Content-Type")
+    );
+}
+
+#[test]
+fn preflight_from_denied_origin() {
+    let client = client();
+    let response = client
+        .options("/users/login")
+        .header(Header::new("Origin", DENIED))
+        .header(Header::new("Access-Control-Request-Method", "POST"))
+        .dispatch();
+
+    assert_eq!(response.status(), Status::Forbidden);
+    assert_eq!(
+        response.headers().get_one("Access-Control-Allow-Origin"),
+        None
+    );
+    assert_eq!(response.headers().get_one("Vary"), Some("Origin"));
+}
+
+#[test]
+fn preflight_with_disallowed_method_or_header() {
+    let client = client();
+    let response = client
+        .options("/users/me")
+        .header(Header::new("Origin", ALLOWED))
+        .header(Header::new("Access-Control-Request-Method", "DELETE"))
+        .dispatch();
+
+    assert_eq!(response.status(), Status::Forbidden);
+    assert_eq!(
+        response.headers().get_one("Access-Control-Allow-Origin"),
+        None
+    );
+
+    let response = client
+        .options("/users/login")
+        .header(Header::new("Origin", ALLOWED))
+        .header(Header::new("Access-Control-Request-Method", "POST"))
+        .header(Header::new("Access-Control-Request-Headers", "X-Custom"))
+        .dispatch();
+
+    assert_eq!(response.status(), Status::Forbidden);
+}
+
+#[test]
+fn simple_request_echoes_only_allowed_origins() {
+    let client = client();
+
+    let response = client
+        .get("/nowhere")
+        .header(Header::new("Origin", ALLOWED))
+        .dispatch();
+    assert_eq!(
+        response.headers().get_one("Access-Control-Allow-Origin"),
+        Some(ALLOWED)
+    );
+    assert_eq!(response.headers().get_one("Vary"), Some("Origin"));
+
+    let response = client
+        .get("/nowhere")
+        .header(Header::new("Origin", DENIED))
+        .dispatch();
+    assert_eq!(
+        response.headers().get_one("Access-Control-Allow-Origin"),
+        None
+    );
+    assert_eq!(response.headers().get_one("Vary"), Some("Origin"));
+}
+
+#[test]
+fn wildcard_origin() {
+    let figment = rocket::Config::figment()
+        .merge(("storage", "memory"))
+        .merge(("cors.allowed_origins", ["*"]));
+    let client = Client::tracked(rocket().configure(figment)).expect("valid rocket instance");
+
+    // Any origin is allowed, but never with credentials
+    let response = client
+        .get("/nowhere")
+        .header(Header::new("Origin", DENIED))
+        .dispatch();
+    assert_eq!(
+        response.headers().get_one("Access-Control-Allow-Origin"),
+        Some("*")
+    );
+    assert_eq!(
+        response
+            .headers()
+            .get_one("Access-Control-Allow-Credentials"),
+        None
+    );
+}
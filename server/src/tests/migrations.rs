@@ -0,0 +1,327 @@
+use std::collections::HashMap;
+use std::sync::Mutex;
+
+use chrono::{DateTime, Duration, TimeZone, Utc};
+use mongodb::bson::{self, doc, Bson, Document};
+use rocket::http::Status;
+
+use crate::migrations::{self, MigrationReport, MigrationTarget, MIGRATIONS};
+
+/// Collections held in memory, understanding just the parts of Mongo's query and
+/// aggregation languages that the migrations use.
+#[derive(Default)]
+struct Collections {
+    documents: Mutex<HashMap<String, Vec<Document>>>,
+    applied: Mutex<Vec<String>>,
+}
+
+impl Collections {
+    fn insert(&self, collection: &str, document: Document) {
+        let mut documents = self.documents.lock().unwrap();
+        documents
+            .entry(collection.to_string())
+            .or_default()
+            .push(document);
+    }
+
+    fn find(&self, collection: &str) -> Vec<Document> {
+        let documents = self.documents.lock().unwrap();
+        documents.get(collection).cloned().unwrap_or_default()
+    }
+
+    fn applied(&self) -> Vec<String> {
+        self.applied.lock().unwrap().clone()
+    }
+}
+
+/// Whether `document` meets every `{field: {operator: operand}}` condition in `filter`.
+fn matches(document: &Document, filter: &Document) -> bool {
+    filter.iter().all(|(field, condition)| {
+        let condition = condition.as_document().expect("an operator document");
+
+        condition
+            .iter()
+            .all(|(operator, operand)| match operator.as_str() {
+                "$type" => {
+                    operand.as_str() == Some("string")
+                        && matches!(document.get(field), Some(Bson::String(_)))
+                }
+                "$exists" => document.contains_key(field) == operand.as_bool().unwrap(),
+                _ => panic!("unsupported query operator {}", operator),
+            })
+    })
+}
+
+/// Evaluates an aggregation expression with `document` as its input.
+fn evaluate(document: &Document, expression: &Bson) -> Bson {
+    let operator = match expression {
+        Bson::String(path) if path.starts_with('$') => {
+            return document.get(&path[1..]).cloned().unwrap_or(Bson::Null);
+        }
+        Bson::Document(operator) => operator,
+        literal => return literal.clone(),
+    };
+
+    let (name, argument) = operator.iter().next().expect("an operator");
+    let arguments = |count: usize| -> Vec<Bson> {
+        let arguments = argument.as_array().expect("an argument list");
+        assert_eq!(arguments.len(), count, "arguments to {}", name);
+
+        arguments
+            .iter()
+            .map(|argument| evaluate(document, argument))
+            .collect()
+    };
+
+    match name.as_str() {
+        "$split" => {
+            let arguments = arguments(2);
+            let text = arguments[0].as_str().unwrap();
+            let separator = arguments[1].as_str().unwrap();
+
+            Bson::Array(
+                text.split(separator)
+                    .map(|part| Bson::String(part.to_string()))
+                    .collect(),
+            )
+        }
+        "$arrayElemAt" => {
+            let arguments = arguments(2);
+            let index = arguments[1].as_i32().unwrap() as usize;
+
+            arguments[0].as_array().unwrap()[index].clone()
+        }
+        "$in" => {
+            let arguments = arguments(2);
+
+            Bson::Boolean(arguments[1].as_array().unwrap().contains(&arguments[0]))
+        }
+        "$toInt" => {
+            let text = evaluate(document, argument);
+            Bson::Int32(text.as_str().unwrap().parse().unwrap())
+        }
+        "$dateFromString" => {
+            let parts = argument.as_document().unwrap();
+            let text = evaluate(document, parts.get("dateString").unwrap());
+            let date = DateTime::parse_from_rfc3339(text.as_str().unwrap()).unwrap();
+
+            Bson::DateTime(bson::DateTime::from_chrono(date.with_timezone(&Utc)))
+        }
+        "$dateFromParts" => {
+            let parts = argument.as_document().unwrap();
+            let part = |name: &str| {
+                evaluate(document, parts.get(name).unwrap())
+                    .as_i32()
+                    .unwrap()
+            };
+            let date = Utc
+                .with_ymd_and_hms(
+                    part("year"),
+                    part("month") as u32,
+                    part("day") as u32,
+                    0,
+                    0,
+                    0,
+                )
+                .unwrap();
+
+            Bson::DateTime(bson::DateTime::from_chrono(date))
+        }
+        _ => panic!("unsupported expression operator {}", name),
+    }
+}
+
+/// Runs an update pipeline of `$set` and `$unset` stages on one document.
+fn run_pipeline(document: &Document, pipeline: &[Document]) -> Document {
+    let mut document = document.clone();
+
+    for stage in pipeline {
+        let (name, argument) = stage.iter().next().expect("a stage");
+
+        match name.as_str() {
+            "$set" => {
+                let input = document.clone();
+
+                for (field, expression) in argument.as_document().unwrap() {
+                    document.insert(field, evaluate(&input, expression));
+                }
+            }
+            "$unset" => {
+                document.remove(argument.as_str().unwrap());
+            }
+            _ => panic!("unsupported stage {}", name),
+        }
+    }
+
+    document
+}
+
+#[rocket::async_trait]
+impl MigrationTarget for Collections {
+    async fn is_applied(&self, name: &str) -> Result<bool, Status> {
+        Ok(self.applied().iter().any(|applied| applied == name))
+    }
+
+    async fn record_applied(&self, name: &str, _documents: u64) -> Result<(), Status> {
+        self.applied.lock().unwrap().push(name.to_string());
+        Ok(())
+    }
+
+    async fn count(&self, collection: &str, filter: Document) -> Result<u64, Status> {
+        Ok(self
+            .find(collection)
+            .iter()
+            .filter(|document| matches(document, &filter))
+            .count() as u64)
+    }
+
+    async fn update(
+        &self,
+        collection: &str,
+        filter: Document,
+        update: Vec<Document>,
+    ) -> Result<u64, Status> {
+        let mut documents = self.documents.lock().unwrap();
+        let mut modified = 0;
+
+        for document in documents.entry(collection.to_string()).or_default() {
+            if !matches(document, &filter) {
+                continue;
+            }
+
+            let updated = run_pipeline(document, &update);
+            if updated != *document {
+                *document = updated;
+                modified += 1;
+            }
+        }
+
+        Ok(modified)
+    }
+}
+
+fn summary(reports: &[MigrationReport]) -> Vec<(&str, u64, bool)> {
+    reports
+        .iter()
+        .map(|report| (report.name, report.documents, report.already_applied))
+        .collect()
+}
+
+/// A database from before documents carried timestamps or schema versions.
+fn old_database() -> Collections {
+    let db = Collections::default();
+    db.insert("games", doc! {"player1": "alice", "date": "2023-4-9"});
+    db.insert("games", doc! {"player1": "bob", "date": "2022-12-31"});
+    db.insert("users", doc! {"username": "alice"});
+    db.insert("leaderboard", doc! {"username": "alice", "wins": 1});
+    db.insert("leaderboard", doc! {"username": "CPU - Easy", "wins": 0});
+    db.insert(
+        "audit_log",
+        doc! {"action": "ban", "date": "2023-04-09T12:30:00.123456789+00:00"},
+    );
+    db
+}
+
+fn midnight(year: i32, month: u32, day: u32) -> bson::DateTime {
+    bson::DateTime::from_chrono(Utc.with_ymd_and_hms(year, month, day, 0, 0, 0).unwrap())
+}
+
+#[rocket::async_test]
+async fn migrations_turn_dates_into_timestamps_and_version_documents() {
+    let db = old_database();
+    let reports = migrations::apply(&db, MIGRATIONS, false).await.unwrap();
+
+    assert_eq!(
+        summary(&reports),
+        [
+            ("0001-game-timestamps", 2, false),
+            ("0002-games-schema-version", 2, false),
+            ("0003-users-schema-version", 1, false),
+            ("0004-leaderboard-hidden", 2, false),
+            ("0005-audit-log-dates", 1, false),
+        ]
+    );
+
+    // Unpadded dates become midnight UTC that day
+    assert_eq!(
+        db.find("games"),
+        [
+            doc! {
+                "player1": "alice",
+                "started_at": midnight(2023, 4, 9),
+                "ended_at": midnight(2023, 4, 9),
+                "schema_version": 1,
+            },
+            doc! {
+                "player1": "bob",
+                "started_at": midnight(2022, 12, 31),
+                "ended_at": midnight(2022, 12, 31),
+                "schema_version": 1,
+            },
+        ]
+    );
+    assert_eq!(
+        db.find("users"),
+        [doc! {"username": "alice", "schema_version": 1}]
+    );
+    // CPU players are kept off leaderboards
+    assert_eq!(
+        db.find("leaderboard"),
+        [
+            doc! {"username": "alice", "wins": 1, "hidden": false},
+            doc! {"username": "CPU - Easy", "wins": 0, "hidden": true},
+        ]
+    );
+    // Audit log dates become datetimes, to the millisecond BSON keeps
+    let banned_at =
+        Utc.with_ymd_and_hms(2023, 4, 9, 12, 30, 0).unwrap() + Duration::milliseconds(123);
+    assert_eq!(
+        db.find("audit_log"),
+        [doc! {"action": "ban", "date": bson::DateTime::from_chrono(banned_at)}]
+    );
+    assert_eq!(
+        db.applied(),
+        MIGRATIONS.iter().map(|m| m.name).collect::<Vec<_>>()
+    );
+}
+
+#[rocket::async_test]
+async fn migrations_run_once_and_are_harmless_to_repeat() {
+    let db = old_database();
+    migrations::apply(&db, MIGRATIONS, false).await.unwrap();
+    let migrated = db.find("games");
+
+    // Recorded migrations are skipped
+    let reports = migrations::apply(&db, MIGRATIONS, false).await.unwrap();
+    assert!(reports
+        .iter()
+        .all(|report| report.already_applied && report.documents == 0));
+    assert_eq!(db.applied().len(), MIGRATIONS.len());
+
+    // Even if the records were lost, no migration matches a document it already changed
+    db.applied.lock().unwrap().clear();
+    let reports = migrations::apply(&db, MIGRATIONS, false).await.unwrap();
+    assert!(reports
+        .iter()
+        .all(|report| !report.already_applied && report.documents == 0));
+    assert_eq!(db.find("games"), migrated);
+}
+
+#[rocket::async_test]
+async fn dry_runs_count_without_writing() {
+    let db = old_database();
+    let reports = migrations::apply(&db, MIGRATIONS, true).await.unwrap();
+
+    assert_eq!(
+        summary(&reports),
+        [
+            ("0001-game-timestamps", 2, false),
+            ("0002-games-schema-version", 2, false),
+            ("0003-users-schema-version", 1, false),
+            ("0004-leaderboard-hidden", 2, false),
+            ("0005-audit-log-dates", 1, false),
+        ]
+    );
+    assert_eq!(db.find("games"), old_database().find("games"));
+    assert!(db.applied().is_empty());
+}
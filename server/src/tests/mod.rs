@@ -0,0 +1,30 @@
+use chrono::{Duration, Utc};
+use client::{ConnectGame, GameType};
+
+mod api;
+mod cors;
+mod migrations;
+mod tournament;
+mod transfer;
+
+/// A five minute game on the standard board that just ended, with none of the optional
+/// details recorded. Tests that need them set them with struct update syntax.
+fn game(game_type: GameType, player1: &str, player2: &str, winner: &str) -> ConnectGame {
+    let ended_at = Utc::now();
+
+    ConnectGame {
+        game_type,
+        player1: player1.to_string(),
+        player2: player2.to_string(),
+        winner: winner.to_string(),
+        started_at: ended_at - Duration::minutes(5),
+        ended_at,
+        rows: Some(6),
+        cols: Some(7),
+        moves: None,
+        seed: None,
+        notation: None,
+        player1_first: None,
+        first_move: None,
+    }
+}
@@ -0,0 +1,88 @@
+use client::ai::Engine;
+use client::GameType;
+
+use crate::tournament::{Matchup, Rate, Tournament};
+
+fn tournament(engine: &str, against: &str, games: u32) -> Tournament {
+    Tournament {
+        engine: engine.parse().unwrap(),
+        against: against.parse().unwrap(),
+        matchups: vec![
+            Matchup {
+                game_type: GameType::Connect4,
+                rows: 4,
+                cols: 5,
+            },
+            Matchup {
+                game_type: GameType::TootAndOtto,
+                rows: 4,
+                cols: 4,
+            },
+        ],
+        games,
+        seed: 7,
+    }
+}
+
+#[test]
+fn engines_parse_and_print() {
+    for name in ["random", "minmax:3", "windows:5"] {
+        assert_eq!(name.parse::<Engine>().unwrap().to_string(), name);
+    }
+
+    assert_eq!("hard".parse::<Engine>().unwrap().to_string(), "minmax:6");
+    assert!("minmax:0".parse::<Engine>().is_err());
+    assert!("windows".parse::<Engine>().is_err());
+}
+
+#[test]
+fn the_same_seed_plays_the_same_games() {
+    let counts = |tournament: &Tournament| -> Vec<(u32, u32, u32)> {
+        tournament
+            .run()
+            .iter()
+            .map(|result| (result.wins, result.draws, result.losses))
+            .collect()
+    };
+
+    let tournament = tournament("windows:2", "random", 6);
+    assert_eq!(counts(&tournament), counts(&tournament));
+}
+
+#[test]
+fn results_cover_every_matchup_and_the_total() {
+    let results = tournament("minmax:4", "random", 10).run();
+    let boards: Vec<(&str, &str)> = results
+        .iter()
+        .map(|result| (result.game_type.as_str(), result.board.as_str()))
+        .collect();
+    assert_eq!(
+        boards,
+        [("connect4", "4x5"), ("tootandotto", "4x4"), ("all", "all")]
+    );
+
+    let total = &results[2];
+    assert_eq!(total.games, 20);
+    assert_eq!(total.wins + total.draws + total.losses, 20);
+    assert_eq!(total.wins, results[0].wins + results[1].wins);
+
+    // Looking ahead should beat moving at random
+    assert!(results[0].wins > results[0].losses);
+}
+
+#[test]
+fn wilson_intervals_stay_in_bounds() {
+    let none = Rate::wilson(0, 10);
+    assert_eq!(none.rate, 0.0);
+    assert_eq!(none.low, 0.0);
+    assert!((none.high - 0.2775).abs() < 0.001);
+
+    let half = Rate::wilson(10, 20);
+    assert_eq!(half.rate, 0.5);
+    assert!((half.low - 0.2993).abs() < 0.001);
+    assert!((half.high - 0.7007).abs() < 0.001);
+
+    let all = Rate::wilson(20, 20);
+    assert_eq!(all.high, 1.0);
+    assert!(all.low > 0.8);
+}
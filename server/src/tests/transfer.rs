@@ -0,0 +1,237 @@
+use chrono::Duration;
+use client::{ConnectGame, GameType, Role, UserSummary};
+use rocket::serde::json::serde_json;
+
+use super::game;
+use crate::repository::Storage;
+use crate::transfer::{self, LineError, PortableUser};
+use crate::users;
+
+fn lines<T: rocket::serde::Serialize>(items: &[T]) -> String {
+    items
+        .iter()
+        .map(|item| serde_json::to_string(item).unwrap() + "\n")
+        .collect()
+}
+
+#[rocket::async_test]
+async fn games_round_trip_between_storages() {
+    let source = Storage::memory();
+    let games = [
+        ConnectGame {
+            moves: Some(20),
+            player1_first: Some(true),
+            ..game(GameType::Connect4, "alice", "bob", "alice")
+        },
+        game(GameType::TootAndOtto, "bob", "carol", "carol"),
+    ];
+    transfer::import_games(&source, &lines(&games))
+        .await
+        .unwrap();
+
+    let exported = transfer::export_games(&source).await.unwrap();
+    assert_eq!(exported, games);
+
+    let target = Storage::memory();
+    let report = transfer::import_games(&target, &lines(&exported))
+        .await
+        .unwrap();
+    assert_eq!(report.imported, 2);
+    assert_eq!(transfer::export_games(&target).await.unwrap(), games);
+
+    // Imported games count towards the leaderboard
+    let stats = target.games.player_stats("alice").await.unwrap();
+    assert_eq!(
+        stats
+            .iter()
+            .map(|stats| (&stats.game_type, stats.wins, stats.losses))
+            .collect::<Vec<_>>(),
+        [(&GameType::Connect4, 1, 0)]
+    );
+}
+
+#[rocket::async_test]
+async fn game_import_reports_bad_lines_and_skips_duplicates() {
+    let storage = Storage::memory();
+    let saved = game(GameType::Connect4, "alice", "bob", "alice");
+    transfer::import_games(&storage, &lines(&[&saved]))
+        .await
+        .unwrap();
+
+    let repeated = game(GameType::Connect4, "carol", "dave", "dave");
+    let mut backwards = game(GameType::Connect4, "alice", "bob", "bob");
+    backwards.started_at = backwards.ended_at + Duration::minutes(1);
+    let mut unplayable = game(GameType::Connect4, "alice", "bob", "bob");
+    unplayable.notation = Some("connect4 6x7 R 9".to_string());
+    let mut resized = game(GameType::Connect4, "alice", "bob", "bob");
+    resized.notation = Some("connect4 7x7 R 4 4 3 5".to_string());
+
+    let text = [
+        serde_json::to_string(&saved).unwrap(),
+        String::new(),
+        "not json".to_string(),
+        serde_json::to_string(&game(GameType::Connect4, "alice", "bob", "carol")).unwrap(),
+        serde_json::to_string(&backwards).unwrap(),
+        serde_json::to_string(&unplayable).unwrap(),
+        serde_json::to_string(&resized).unwrap(),
+        serde_json::to_string(&repeated).unwrap(),
+        serde_json::to_string(&repeated).unwrap(),
+    ]
+    .join("\n");
+
+    let report = transfer::import_games(&storage, &text).await.unwrap();
+
+    assert_eq!(report.imported, 1);
+    assert_eq!(report.duplicates, 2);
+    assert_eq!(
+        report
+            .errors
+            .iter()
+            .map(|LineError { line, .. }| *line)
+            .collect::<Vec<_>>(),
+        [3, 4, 5, 6, 7]
+    );
+    assert_eq!(transfer::export_games(&storage).await.unwrap().len(), 2);
+}
+
+#[rocket::async_test]
+async fn game_import_checks_the_record_against_its_notation_and_board() {
+    let storage = Storage::memory();
+
+    // Red, moving first as alice, wins down the first column on the seventh move
+    let played = ConnectGame {
+        moves: Some(7),
+        player1_first: Some(true),
+        notation: Some("connect4 6x7 R 1 2 1 2 1 2 1".to_string()),
+        ..game(GameType::Connect4, "alice", "bob", "alice")
+    };
+
+    let text = [
+        played.clone(),
+        ConnectGame {
+            winner: "bob".to_string(),
+            ..played.clone()
+        },
+        ConnectGame {
+            moves: Some(6),
+            ..played.clone()
+        },
+        ConnectGame {
+            notation: Some("connect4 6x7 R 1 2".to_string()),
+            ..played.clone()
+        },
+        ConnectGame {
+            rows: Some(21),
+            ..game(GameType::Connect4, "alice", "bob", "alice")
+        },
+        ConnectGame {
+            rows: Some(3),
+            cols: Some(3),
+            ..game(GameType::Connect4, "alice", "bob", "alice")
+        },
+    ];
+
+    let report = transfer::import_games(&storage, &lines(&text))
+        .await
+        .unwrap();
+
+    assert_eq!(report.imported, 1);
+    assert_eq!(
+        report
+            .errors
+            .iter()
+            .map(|LineError { line, .. }| *line)
+            .collect::<Vec<_>>(),
+        [2, 3, 4, 5, 6]
+    );
+}
+
+fn portable(username: &str, password_hash: Option<String>) -> PortableUser {
+    PortableUser {
+        user: UserSummary {
+            username: username.to_string(),
+            role: Role::Player,
+            guest: false,
+            banned: false,
+        },
+        password_hash,
+    }
+}
+
+#[rocket::async_test]
+async fn users_export_hashes_only_when_asked() {
+    let source = Storage::memory();
+    let hash = crate::auth::hash_password("hunter2").unwrap();
+    transfer::import_users(&source, &lines(&[portable("alice", Some(hash))]), false)
+        .await
+        .unwrap();
+
+    let without = transfer::export_users(&source, false).await.unwrap();
+    assert_eq!(without, [portable("alice", None)]);
+    assert!(!lines(&without).contains("password"));
+
+    let with = transfer::export_users(&source, true).await.unwrap();
+    let target = Storage::memory();
+    transfer::import_users(&target, &lines(&with), false)
+        .await
+        .unwrap();
+
+    let user = users::authenticate(&target, "alice", "hunter2")
+        .await
+        .expect("the password carried over");
+    assert_eq!(user.role, Role::Player);
+}
+
+#[rocket::async_test]
+async fn user_import_validates_and_skips_taken_usernames() {
+    let storage = Storage::memory();
+
+    let text = lines(&[
+        portable("alice", None),
+        portable("alice", None),
+        portable("CPU - Hard", None),
+        portable("bob", Some("not a hash".to_string())),
+        portable("Guest-abc123", None),
+        PortableUser {
+            user: UserSummary {
+                guest: true,
+                ..portable("Guest-def456", None).user
+            },
+            password_hash: None,
+        },
+    ]);
+    let report = transfer::import_users(&storage, &text, false)
+        .await
+        .unwrap();
+
+    // Only guests can have a guest's name
+    assert_eq!(report.imported, 2);
+    assert_eq!(report.duplicates, 1);
+    assert_eq!(
+        report
+            .errors
+            .iter()
+            .map(|LineError { line, .. }| *line)
+            .collect::<Vec<_>>(),
+        [3, 4, 5]
+    );
+
+    // Without a hash the password is random, so it has to be reset first
+    assert!(users::authenticate(&storage, "alice", "").await.is_err());
+}
+
+#[rocket::async_test]
+async fn user_import_makes_everyone_a_player_unless_keeping_roles() {
+    let mut admin = portable("root", None);
+    admin.user.role = Role::Admin;
+
+    for (keep_roles, role) in [(false, Role::Player), (true, Role::Admin)] {
+        let storage = Storage::memory();
+        transfer::import_users(&storage, &lines(&[&admin]), keep_roles)
+            .await
+            .unwrap();
+
+        let user = storage.users.find_user("root").await.unwrap().unwrap();
+        assert_eq!(user.role, role);
+    }
+}
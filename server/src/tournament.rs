@@ -0,0 +1,307 @@
+//! Playing engines against each other, to measure whether a change makes the CPU stronger.
+
+use std::time::{Duration, Instant};
+
+use clap::Args;
+use client::ai::{self, Engine};
+use client::engine::{Side, MAX_SIZE, MIN_SIZE};
+use client::notation::{Ply, Position};
+use client::{parse_board, GameType};
+use rocket::serde::Serialize;
+
+use crate::cli::{fail, CliError};
+
+#[derive(Args)]
+pub struct TournamentArgs {
+    /// The engine being measured: `random`, `minmax:<depth>`, `windows:<depth>`, or a
+    /// CPU level (`easy`, `medium` or `hard`).
+    #[arg(long, default_value = "windows:4")]
+    engine: String,
+    /// What it plays against, written the same way.
+    #[arg(long, default_value = "medium")]
+    against: String,
+    /// Games for each game type and board size. Each engine moves first in half of them.
+    #[arg(long, default_value_t = 20)]
+    games: u32,
+    /// Comma separated, by slug.
+    #[arg(long, value_delimiter = ',', default_value = "connect4,tootandotto")]
+    game_types: Vec<String>,
+    /// Comma separated, as rows x cols.
+    #[arg(long, value_delimiter = ',', default_value = "4x4,6x7")]
+    boards: Vec<String>,
+    /// The seed of each matchup's first game. The games after it count up from there, so
+    /// a tournament plays out the same way every time.
+    #[arg(long, default_value_t = 0)]
+    seed: u64,
+}
+
+impl TournamentArgs {
+    pub fn parse(&self) -> Result<Tournament, CliError> {
+        let engine = |name: &str| match name.parse::<Engine>() {
+            Ok(engine) => Ok(engine),
+            Err(_) => fail(format!("Unknown engine {:?}", name)),
+        };
+
+        if self.games == 0 {
+            return fail("A tournament needs at least one game");
+        }
+
+        let mut matchups = vec![];
+
+        for slug in &self.game_types {
+            let game_type = match GameType::from_slug(slug) {
+                Some(game_type) => game_type,
+                None => return fail(format!("Unknown game type {:?}", slug)),
+            };
+
+            for board in &self.boards {
+                let (rows, cols) = match parse_board(board) {
+                    Some((rows, cols))
+                        if (MIN_SIZE..=MAX_SIZE).contains(&(rows as usize))
+                            && (MIN_SIZE..=MAX_SIZE).contains(&(cols as usize)) =>
+                    {
+                        (rows as usize, cols as usize)
+                    }
+                    _ => return fail(format!("Invalid board {:?}", board)),
+                };
+
+                matchups.push(Matchup {
+                    game_type: game_type.clone(),
+                    rows,
+                    cols,
+                });
+            }
+        }
+
+        Ok(Tournament {
+            engine: engine(&self.engine)?,
+            against: engine(&self.against)?,
+            matchups,
+            games: self.games,
+            seed: self.seed,
+        })
+    }
+}
+
+/// One game type on one board size.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Matchup {
+    pub game_type: GameType,
+    pub rows: usize,
+    pub cols: usize,
+}
+
+pub struct Tournament {
+    pub engine: Engine,
+    pub against: Engine,
+    pub matchups: Vec<Matchup>,
+    /// For each matchup.
+    pub games: u32,
+    pub seed: u64,
+}
+
+/// A rate along with its 95% confidence interval.
+#[derive(Serialize, Debug, Clone, Copy, PartialEq)]
+#[serde(crate = "rocket::serde")]
+pub struct Rate {
+    pub rate: f64,
+    pub low: f64,
+    pub high: f64,
+}
+
+impl Rate {
+    /// The Wilson score interval, which stays within 0 and 1 even for few games or
+    /// lopsided results.
+    pub fn wilson(count: u32, total: u32) -> Rate {
+        const Z: f64 = 1.96;
+
+        if total == 0 {
+            return Rate {
+                rate: 0.0,
+                low: 0.0,
+                high: 1.0,
+            };
+        }
+
+        let n = total as f64;
+        let p = count as f64 / n;
+        let denominator = 1.0 + Z * Z / n;
+        let center = (p + Z * Z / (2.0 * n)) / denominator;
+        let margin = Z * (p * (1.0 - p) / n + Z * Z / (4.0 * n * n)).sqrt() / denominator;
+
+        Rate {
+            rate: p,
+            low: (center - margin).max(0.0),
+            high: (center + margin).min(1.0),
+        }
+    }
+}
+
+/// How the measured engine did in one matchup, or across all of them.
+#[derive(Serialize, Debug, Clone, PartialEq)]
+#[serde(crate = "rocket::serde")]
+pub struct MatchupResult {
+    pub engine: String,
+    pub against: String,
+    /// A slug, or `all`.
+    pub game_type: String,
+    /// Like `6x7`, or `all`.
+    pub board: String,
+    pub games: u32,
+    pub wins: u32,
+    pub draws: u32,
+    pub losses: u32,
+    pub win_rate: Rate,
+    pub draw_rate: Rate,
+    pub loss_rate: Rate,
+    pub engine_ms_per_move: f64,
+    pub against_ms_per_move: f64,
+}
+
+/// Time spent thinking by one engine.
+#[derive(Default, Clone, Copy)]
+struct Clock {
+    total: Duration,
+    moves: u32,
+}
+
+impl Clock {
+    fn add(&mut self, other: Clock) {
+        self.total += other.total;
+        self.moves += other.moves;
+    }
+
+    fn ms_per_move(&self) -> f64 {
+        if self.moves == 0 {
+            return 0.0;
+        }
+
+        self.total.as_secs_f64() * 1000.0 / self.moves as f64
+    }
+}
+
+#[derive(Default, Clone, Copy)]
+struct Tally {
+    wins: u32,
+    draws: u32,
+    losses: u32,
+    engine: Clock,
+    against: Clock,
+}
+
+impl Tally {
+    fn add(&mut self, other: Tally) {
+        self.wins += other.wins;
+        self.draws += other.draws;
+        self.losses += other.losses;
+        self.engine.add(other.engine);
+        self.against.add(other.against);
+    }
+
+    fn result(&self, tournament: &Tournament, game_type: String, board: String) -> MatchupResult {
+        let games = self.wins + self.draws + self.losses;
+
+        MatchupResult {
+            engine: tournament.engine.to_string(),
+            against: tournament.against.to_string(),
+            game_type,
+            board,
+            games,
+            wins: self.wins,
+            draws: self.draws,
+            losses: self.losses,
+            win_rate: Rate::wilson(self.wins, games),
+            draw_rate: Rate::wilson(self.draws, games),
+            loss_rate: Rate::wilson(self.losses, games),
+            engine_ms_per_move: self.engine.ms_per_move(),
+            against_ms_per_move: self.against.ms_per_move(),
+        }
+    }
+}
+
+/// Plays one game from an empty board, `first` moving first as `Side::One`. Returns the
+/// winning side, if either won alone, and how long each side spent choosing its moves.
+fn play_game(
+    matchup: &Matchup,
+    first: Engine,
+    second: Engine,
+    seed: u64,
+) -> (Option<Side>, [Clock; 2]) {
+    let game_type = &matchup.game_type;
+    let mut rng = ai::seeded_rng(seed);
+    let mut position = Position::new(game_type.clone(), matchup.rows, matchup.cols, Side::One);
+    let mut clocks = [Clock::default(); 2];
+
+    while !position.is_over() {
+        let (engine, clock) = match position.to_move {
+            Side::One => (first, &mut clocks[0]),
+            Side::Two => (second, &mut clocks[1]),
+        };
+
+        let started = Instant::now();
+        let (col, piece) =
+            engine.choose_move(&mut position.board, game_type, position.to_move, &mut rng);
+        clock.total += started.elapsed();
+        clock.moves += 1;
+
+        position
+            .play(Ply { col, piece })
+            .expect("engines only choose legal moves");
+    }
+
+    let winner = match position.wins() {
+        (true, false) => Some(Side::One),
+        (false, true) => Some(Side::Two),
+        _ => None,
+    };
+
+    (winner, clocks)
+}
+
+impl Tournament {
+    /// Plays every matchup, returning each one's result followed by the overall result.
+    pub fn run(&self) -> Vec<MatchupResult> {
+        let mut results = vec![];
+        let mut overall = Tally::default();
+
+        for matchup in &self.matchups {
+            let mut tally = Tally::default();
+
+            for game in 0..self.games {
+                // The measured engine opens the even games
+                let engine_side = if game % 2 == 0 { Side::One } else { Side::Two };
+                let (first, second) = match engine_side {
+                    Side::One => (self.engine, self.against),
+                    Side::Two => (self.against, self.engine),
+                };
+
+                let seed = self.seed.wrapping_add(game as u64);
+                let (winner, [first_clock, second_clock]) = play_game(matchup, first, second, seed);
+
+                match winner {
+                    Some(side) if side == engine_side => tally.wins += 1,
+                    Some(_) => tally.losses += 1,
+                    None => tally.draws += 1,
+                }
+
+                let (engine_clock, against_clock) = match engine_side {
+                    Side::One => (first_clock, second_clock),
+                    Side::Two => (second_clock, first_clock),
+                };
+                tally.engine.add(engine_clock);
+                tally.against.add(against_clock);
+            }
+
+            overall.add(tally);
+            results.push(tally.result(
+                self,
+                matchup.game_type.slug().to_string(),
+                format!("{}x{}", matchup.rows, matchup.cols),
+            ));
+        }
+
+        results.push(overall.result(self, "all".to_string(), "all".to_string()));
+
+        results
+    }
+}
@@ -0,0 +1,328 @@
+//! Moving games and users between databases as JSON Lines, one record per line.
+
+use std::collections::HashSet;
+
+use chrono::{DateTime, Utc};
+use client::engine::{Side, MAX_SIZE, MIN_SIZE};
+use client::notation::GameNotation;
+use client::{ConnectGame, GameType, Role, UserSummary};
+use mongodb::bson;
+use rocket::{
+    http::Status,
+    serde::{json::serde_json, Deserialize, Serialize},
+};
+
+use crate::auth::{hash_password, random_string, verify_password};
+use crate::games;
+use crate::leaderboard;
+use crate::migrations::USERS_SCHEMA_VERSION;
+use crate::repository::{GameSelection, Storage};
+use crate::users::{is_reserved_for_accounts, is_reserved_username, UserDocument};
+
+/// A user as exported, with their password hash only when asked for.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(crate = "rocket::serde")]
+pub struct PortableUser {
+    #[serde(flatten)]
+    pub user: UserSummary,
+    /// Argon2 encoded, as stored. Users imported without one get a random password.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub password_hash: Option<String>,
+}
+
+/// One line an import skipped, and why.
+#[derive(Serialize, Debug, PartialEq)]
+#[serde(crate = "rocket::serde")]
+pub struct LineError {
+    /// 1-based.
+    pub line: usize,
+    pub message: String,
+}
+
+/// What an import did.
+#[derive(Serialize, Debug, Default, PartialEq)]
+#[serde(crate = "rocket::serde")]
+pub struct ImportReport {
+    pub imported: usize,
+    /// Records already in the database, or earlier in the same file.
+    pub duplicates: usize,
+    pub errors: Vec<LineError>,
+}
+
+/// What makes two games the same game, since ids don't carry over between databases.
+type GameKey = (
+    GameType,
+    String,
+    String,
+    String,
+    DateTime<Utc>,
+    DateTime<Utc>,
+);
+
+fn game_key(game: &ConnectGame) -> GameKey {
+    (
+        game.game_type.clone(),
+        game.player1.clone(),
+        game.player2.clone(),
+        game.winner.clone(),
+        game.started_at,
+        game.ended_at,
+    )
+}
+
+fn validate_game(game: &ConnectGame) -> Result<(), String> {
+    if game.player1.is_empty() || game.player2.is_empty() {
+        return Err("both players need a name".to_string());
+    }
+
+    if game.player1 == game.player2 {
+        return Err("a player can't play themselves".to_string());
+    }
+
+    if game.winner != game.player1 && game.winner != game.player2 {
+        return Err(format!(
+            "the winner {:?} isn't one of the players",
+            game.winner
+        ));
+    }
+
+    if game.started_at > game.ended_at {
+        return Err("the game ends before it starts".to_string());
+    }
+
+    if game.rows.is_some() != game.cols.is_some() {
+        return Err("the board needs both rows and cols, or neither".to_string());
+    }
+
+    if let (Some(rows), Some(cols)) = (game.rows, game.cols) {
+        let sizes = MIN_SIZE as u32..=MAX_SIZE as u32;
+
+        if !sizes.contains(&rows) || !sizes.contains(&cols) {
+            return Err(format!(
+                "a {}x{} board is outside the {} to {} rows and columns allowed",
+                rows, cols, MIN_SIZE, MAX_SIZE
+            ));
+        }
+    }
+
+    if let Some(notation) = &game.notation {
+        let notation = notation
+            .parse::<GameNotation>()
+            .map_err(|err| format!("invalid notation: {}", err))?;
+
+        if notation.game_type != game.game_type {
+            return Err("the notation is for the other game type".to_string());
+        }
+
+        let board = (game.rows, game.cols);
+        if board != (Some(notation.rows as u32), Some(notation.cols as u32)) {
+            return Err(format!(
+                "the notation is for a {}x{} board, which rows and cols don't match",
+                notation.rows, notation.cols
+            ));
+        }
+
+        // Draws aren't saved, so the moves have to end in a win for one side
+        let position = notation
+            .replay()
+            .map_err(|err| format!("invalid notation: {}", err))?;
+        let winning_side = match position.wins() {
+            (true, false) => Side::One,
+            (false, true) => Side::Two,
+            _ => return Err("the notation doesn't end in a win".to_string()),
+        };
+
+        if let Some(moves) = game.moves {
+            if moves as usize != notation.plies.len() {
+                return Err(format!(
+                    "the notation has {} moves, not {}",
+                    notation.plies.len(),
+                    moves
+                ));
+            }
+        }
+
+        // Which player had which side is only known from who moved first
+        if let Some(player1_first) = game.player1_first {
+            let (first, second) = if player1_first {
+                (&game.player1, &game.player2)
+            } else {
+                (&game.player2, &game.player1)
+            };
+            let winner = if winning_side == notation.first {
+                first
+            } else {
+                second
+            };
+
+            if *winner != game.winner {
+                return Err(format!(
+                    "the notation is won by {:?}, not {:?}",
+                    winner, game.winner
+                ));
+            }
+        }
+    }
+
+    Ok(())
+}
+
+fn validate_user(user: &PortableUser) -> Result<(), String> {
+    let username = &user.user.username;
+
+    // Guests are named like guests, and only they can be
+    let reserved = if user.user.guest {
+        is_reserved_username(username)
+    } else {
+        is_reserved_for_accounts(username)
+    };
+
+    if reserved {
+        return Err(format!("{:?} can't be used as a username", username));
+    }
+
+    if let Some(hash) = &user.password_hash {
+        // Any password will do; a hash that can't be decoded fails either way
+        if verify_password(hash, "").is_err() {
+            return Err("the password hash isn't Argon2 encoded".to_string());
+        }
+    }
+
+    Ok(())
+}
+
+/// The non-blank lines of `text`, numbered from 1.
+fn records(text: &str) -> impl Iterator<Item = (usize, &str)> {
+    text.lines()
+        .enumerate()
+        .map(|(index, line)| (index + 1, line.trim()))
+        .filter(|(_, line)| !line.is_empty())
+}
+
+/// Every game, oldest first.
+pub async fn export_games(storage: &Storage) -> Result<Vec<ConnectGame>, Status> {
+    Ok(storage
+        .games
+        .select_games(&GameSelection::default())
+        .await?
+        .into_iter()
+        .map(|record| record.game)
+        .collect())
+}
+
+/// Every user in alphabetical order, with their password hashes if `with_passwords` is set.
+pub async fn export_users(
+    storage: &Storage,
+    with_passwords: bool,
+) -> Result<Vec<PortableUser>, Status> {
+    Ok(storage
+        .users
+        .search_users(None, i64::MAX)
+        .await?
+        .into_iter()
+        .map(|user| PortableUser {
+            // Guests have no password to carry over
+            password_hash: (with_passwords && !user.guest).then(|| user.password.clone()),
+            user: UserSummary {
+                username: user.username,
+                role: user.role,
+                guest: user.guest,
+                banned: user.banned,
+            },
+        })
+        .collect())
+}
+
+/// Saves each game in `text` that is valid and not already saved, counting it
+/// towards the leaderboard as if it had just been played.
+pub async fn import_games(storage: &Storage, text: &str) -> Result<ImportReport, Status> {
+    let mut report = ImportReport::default();
+    let mut seen: HashSet<GameKey> = export_games(storage).await?.iter().map(game_key).collect();
+
+    for (line, record) in records(text) {
+        let game = match serde_json::from_str::<ConnectGame>(record)
+            .map_err(|err| err.to_string())
+            .and_then(|game| validate_game(&game).map(|_| game))
+        {
+            Ok(game) => game,
+            Err(message) => {
+                report.errors.push(LineError { line, message });
+                continue;
+            }
+        };
+
+        if !seen.insert(game_key(&game)) {
+            report.duplicates += 1;
+            continue;
+        }
+
+        games::save_game(storage, &game).await?;
+        report.imported += 1;
+    }
+
+    Ok(report)
+}
+
+/// Creates each user in `text` that is valid and whose username is free. Everyone is
+/// imported as a player unless `keep_roles` is set, so a file can't hand out admin rights.
+pub async fn import_users(
+    storage: &Storage,
+    text: &str,
+    keep_roles: bool,
+) -> Result<ImportReport, Status> {
+    let mut report = ImportReport::default();
+
+    for (line, record) in records(text) {
+        let user = match serde_json::from_str::<PortableUser>(record)
+            .map_err(|err| err.to_string())
+            .and_then(|user| validate_user(&user).map(|_| user))
+        {
+            Ok(user) => user,
+            Err(message) => {
+                report.errors.push(LineError { line, message });
+                continue;
+            }
+        };
+
+        if storage
+            .users
+            .find_user(&user.user.username)
+            .await?
+            .is_some()
+        {
+            report.duplicates += 1;
+            continue;
+        }
+
+        let password = match user.password_hash {
+            Some(hash) => hash,
+            None if user.user.guest => String::new(),
+            None => hash_password(&random_string(32))?,
+        };
+
+        storage
+            .users
+            .insert_user(UserDocument {
+                schema_version: USERS_SCHEMA_VERSION,
+                username: user.user.username.clone(),
+                password,
+                guest: user.user.guest,
+                role: if keep_roles {
+                    user.user.role
+                } else {
+                    Role::Player
+                },
+                banned: user.user.banned,
+                created_at: Some(bson::DateTime::now()),
+            })
+            .await?;
+        report.imported += 1;
+
+        // Games imported before their players keep guests and banned users off leaderboards
+        if user.user.guest || user.user.banned {
+            leaderboard::update_visibility(storage, &user.user.username).await?;
+        }
+    }
+
+    Ok(report)
+}
@@ -0,0 +1,299 @@
+use chrono::{DateTime, Utc};
+use client::{
+    AccountDeletion, CpuLevel, GameRetention, PasswordChange, Role, Session, User, UsernameChange,
+    ANONYMOUS_PLAYER,
+};
+use mongodb::bson;
+use rocket::{
+    http::Status,
+    serde::{json::Json, Deserialize, Serialize},
+    State,
+};
+
+use crate::auth::{create_session, hash_password, random_string, verify_password, AuthUser};
+use crate::leaderboard;
+use crate::migrations::USERS_SCHEMA_VERSION;
+use crate::repository::Storage;
+
+/// A document in the `users` collection.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(crate = "rocket::serde")]
+pub struct UserDocument {
+    /// Zero for users saved before documents were versioned.
+    #[serde(default)]
+    pub schema_version: u32,
+    pub username: String,
+    pub password: String,
+    /// Guests are created by `/users/guest`, have no password and stay off the leaderboard.
+    #[serde(default)]
+    pub guest: bool,
+    #[serde(default)]
+    pub role: Role,
+    #[serde(default)]
+    pub banned: bool,
+    /// Unset for users saved before this was recorded.
+    #[serde(default)]
+    pub created_at: Option<bson::DateTime>,
+}
+
+/// What every guest's username starts with.
+const GUEST_PREFIX: &str = "Guest-";
+
+/// Whether no account may be called `username`: it's blank, or it's a name games already
+/// use for someone else, like deleted users or a CPU difficulty.
+pub fn is_reserved_username(username: &str) -> bool {
+    username.is_empty()
+        || username == ANONYMOUS_PLAYER
+        || CpuLevel::from_player_name(username).is_some()
+}
+
+/// Whether a registered account can't be called `username`: it's reserved, or it looks
+/// like a guest's, which would pass the account off as a guest on other players' screens.
+pub fn is_reserved_for_accounts(username: &str) -> bool {
+    is_reserved_username(username)
+        || username
+            .to_lowercase()
+            .starts_with(&GUEST_PREFIX.to_lowercase())
+}
+
+/// Rewrites every reference to `from` in the saved games to `to`,
+/// carrying the leaderboard stats along with it.
+async fn rename_in_games(storage: &Storage, from: &str, to: &str) -> Result<(), Status> {
+    storage.games.rename_player(from, to).await?;
+
+    leaderboard::rename_player(storage, from, to).await
+}
+
+/// Checks a username and password, returning the account they sign in to.
+///
+/// Guests can't sign in with a password and banned users can't sign in at all.
+pub async fn authenticate(
+    storage: &Storage,
+    username: &str,
+    password: &str,
+) -> Result<UserDocument, Status> {
+    match storage.users.find_user(username).await? {
+        Some(user) if user.guest => Err(Status::Unauthorized),
+        Some(user) => match verify_password(&user.password, password)? {
+            true if user.banned => Err(Status::Forbidden),
+            true => Ok(user),
+            false => Err(Status::Unauthorized),
+        },
+        None => Err(Status::NotFound),
+    }
+}
+
+#[post("/login", data = "<user_payload>")]
+pub async fn login(
+    storage: &State<Storage>,
+    user_payload: Json<User>,
+) -> Result<Json<Session>, Status> {
+    let user = authenticate(storage, &user_payload.username, &user_payload.password).await?;
+
+    Ok(Json(
+        create_session(storage, &user.username, false, user.role).await?,
+    ))
+}
+
+#[post("/guest")]
+pub async fn guest(storage: &State<Storage>) -> Result<Json<Session>, Status> {
+    loop {
+        let username = format!("{}{}", GUEST_PREFIX, random_string(6));
+
+        if storage.users.find_user(&username).await?.is_some() {
+            continue;
+        }
+
+        let guest = UserDocument {
+            schema_version: USERS_SCHEMA_VERSION,
+            username: username.clone(),
+            password: String::new(),
+            guest: true,
+            role: Role::Player,
+            banned: false,
+            created_at: Some(bson::DateTime::now()),
+        };
+
+        storage.users.insert_user(guest).await?;
+
+        return Ok(Json(
+            create_session(storage, &username, true, Role::Player).await?,
+        ));
+    }
+}
+
+/// Registers a new user. When called with a guest's session, the guest's games
+/// are handed over to the new account and the guest is removed.
+#[post("/register", data = "<user_payload>")]
+pub async fn register(
+    storage: &State<Storage>,
+    auth: Option<AuthUser>,
+    user_payload: Json<User>,
+) -> Result<Json<Session>, Status> {
+    if is_reserved_for_accounts(&user_payload.username) {
+        return Err(Status::BadRequest);
+    }
+
+    let existing = storage.users.find_user(&user_payload.username).await?;
+
+    let hashed_user = UserDocument {
+        schema_version: USERS_SCHEMA_VERSION,
+        username: user_payload.username.clone(),
+        password: hash_password(&user_payload.password)?,
+        guest: false,
+        role: Role::Player,
+        banned: false,
+        created_at: Some(bson::DateTime::now()),
+    };
+
+    match existing {
+        Some(_) => Err(Status::Conflict),
+        None => {
+            storage.users.insert_user(hashed_user).await?;
+
+            if let Some(guest) = auth.filter(|auth| auth.guest) {
+                claim_guest(storage, &guest, &user_payload.username).await?;
+            }
+
+            Ok(Json(
+                create_session(storage, &user_payload.username, false, Role::Player).await?,
+            ))
+        }
+    }
+}
+
+/// Moves a guest's history over to `username` and removes the guest account.
+async fn claim_guest(storage: &Storage, guest: &AuthUser, username: &str) -> Result<(), Status> {
+    rename_in_games(storage, &guest.username, username).await?;
+
+    storage.users.delete_user(&guest.username).await
+}
+
+#[put("/me/password", data = "<change>")]
+pub async fn change_password(
+    storage: &State<Storage>,
+    auth: AuthUser,
+    change: Json<PasswordChange>,
+) -> Result<(), Status> {
+    if auth.guest {
+        return Err(Status::Forbidden);
+    }
+
+    if change.new_password.is_empty() {
+        return Err(Status::BadRequest);
+    }
+
+    let user = storage
+        .users
+        .find_user(&auth.username)
+        .await?
+        .ok_or(Status::NotFound)?;
+
+    if !verify_password(&user.password, &change.old_password)? {
+        return Err(Status::Unauthorized);
+    }
+
+    storage
+        .users
+        .set_password(&auth.username, &hash_password(&change.new_password)?)
+        .await?;
+
+    // Sign out every other session that may have been opened with the old password
+    storage
+        .users
+        .delete_sessions(&auth.username, Some(&auth.token))
+        .await
+}
+
+#[put("/me/username", data = "<change>")]
+pub async fn change_username(
+    storage: &State<Storage>,
+    auth: AuthUser,
+    change: Json<UsernameChange>,
+) -> Result<Json<Session>, Status> {
+    if auth.guest {
+        return Err(Status::Forbidden);
+    }
+
+    if is_reserved_for_accounts(&change.username) {
+        return Err(Status::BadRequest);
+    }
+
+    let session = Session {
+        username: change.username.clone(),
+        token: auth.token.clone(),
+        guest: false,
+        role: auth.role,
+    };
+
+    if change.username == auth.username {
+        return Ok(Json(session));
+    }
+
+    if storage.users.find_user(&change.username).await?.is_some() {
+        return Err(Status::Conflict);
+    }
+
+    storage
+        .users
+        .rename_user(&auth.username, &change.username)
+        .await?;
+
+    rename_in_games(storage, &auth.username, &change.username).await?;
+
+    Ok(Json(session))
+}
+
+/// Deletes an account, doing with its games what `retention` asks.
+pub async fn delete_user(
+    storage: &Storage,
+    username: &str,
+    retention: GameRetention,
+) -> Result<(), Status> {
+    match retention {
+        GameRetention::Anonymize => {
+            rename_in_games(storage, username, ANONYMOUS_PLAYER).await?;
+        }
+        GameRetention::Purge => {
+            let purged = storage.games.delete_player_games(username).await?;
+
+            // Opponents lose the wins and losses they had against this account
+            leaderboard::forget_games(storage, &purged).await?;
+        }
+    }
+
+    storage.users.delete_user(username).await
+}
+
+/// Deletes the guests created before `before`, along with any guests from before creation
+/// times were recorded, anonymizing their games. Returns their usernames.
+pub async fn expire_guests(
+    storage: &Storage,
+    before: DateTime<Utc>,
+) -> Result<Vec<String>, Status> {
+    let expired = storage.users.guests_created_before(before).await?;
+
+    for username in &expired {
+        delete_user(storage, username, GameRetention::Anonymize).await?;
+    }
+
+    Ok(expired)
+}
+
+/// Signs out of the session the request was made with.
+#[post("/logout")]
+pub async fn logout(storage: &State<Storage>, auth: AuthUser) -> Result<(), Status> {
+    storage.users.delete_session(&auth.token).await
+}
+
+#[delete("/me", data = "<deletion>")]
+pub async fn delete_account(
+    storage: &State<Storage>,
+    auth: AuthUser,
+    deletion: Json<AccountDeletion>,
+) -> Result<(), Status> {
+    // A session left signed in somewhere isn't enough to delete the account
+    authenticate(storage, &auth.username, &deletion.password).await?;
+
+    delete_user(storage, &auth.username, deletion.games).await
+}